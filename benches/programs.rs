@@ -0,0 +1,77 @@
+//! Times the interpreter at every optimization level on a few classic programs, run with
+//! `cargo bench`. Pass a name to only run the programs containing it, or a level, like
+//! `cargo bench -- hello` or `cargo bench --bench programs -- -O0`
+
+use brainfuck::{interpret_to_vec, Args};
+use std::{
+    env,
+    hint::black_box,
+    time::{Duration, Instant},
+};
+
+const HELLO: &str = "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.>++.";
+
+const SIERPINSKI: &str = "
+++++++++[>+>++++<<-]>++>>+<[-[>>+<<-]+>>]>+[
+    -<<<[
+        ->[+[-]+>++>>>-<<]<[<]>>++++++[<<+++++>>-]+<<++.[-]<<
+    ]>.>+[>>]>+
+]";
+
+/// Loops that no pass can fold, where the time is in stepping through the instructions, which
+/// is what tightening the dispatch loop of the interpreter sped up
+const DISPATCH: &str = "-[>-[>--[>+<--]<-]<-]>>>.";
+
+const PROGRAMS: &[(&str, &str)] = &[
+    ("hello", HELLO),
+    ("sierpinski", SIERPINSKI),
+    ("dispatch", DISPATCH),
+    ("mandelbrot", include_str!("../mandelbrot.bf")),
+];
+
+/// How long every program is repeated for, it still runs once if that takes longer
+const DURATION: Duration = Duration::from_secs(2);
+
+fn main() {
+    // cargo passes --bench, everything else is a filter
+    let filter = env::args().skip(1).find(|arg| arg != "--bench");
+
+    // Every level, so that a pass that slows the code down shows, and eval on top of the last
+    let mut modes = Vec::new();
+    for (mode, level) in [("-O0", 0), ("-O1", 1), ("-O2", 2), ("-O3", 3), ("eval", 3)] {
+        let mut args = Args::default();
+        args.opt_level = level;
+        args.eval = mode == "eval";
+        modes.push((mode, args));
+    }
+    if cfg!(feature = "jit") {
+        let mut args = Args::default();
+        args.jit = true;
+        modes.push(("jit", args));
+    }
+
+    for (name, code) in PROGRAMS {
+        for (mode, args) in &modes {
+            if filter
+                .as_ref()
+                .is_some_and(|filter| !name.contains(filter.as_str()) && mode != filter)
+            {
+                continue;
+            }
+            let start = Instant::now();
+            let mut runs = 0;
+            while runs == 0 || start.elapsed() < DURATION {
+                let output = interpret_to_vec(black_box(code), args, b"");
+                black_box(output.expect("the program failed"));
+                runs += 1;
+            }
+            println!(
+                "{:<12} {:<12} {:>12.3?} per run ({} runs)",
+                name,
+                mode,
+                start.elapsed() / runs,
+                runs
+            );
+        }
+    }
+}
@@ -0,0 +1,833 @@
+//! Writes the nodes as x86-64 assembly for Linux, or AArch64 assembly for Linux and macOS, for
+//! `--asm`.
+//!
+//! The program needs no libc: `.` and `,` go through a small runtime of `read`, `write` and `exit`
+//! syscalls that is written along with the code. `rbx` or `x19` holds the address of the current
+//! cell, and every node is commented with how `--emit=ir` shows it, to see what each one became
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+use std::{env, fmt::Write};
+
+/// The assembler the code is written for
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Syntax {
+    /// The GNU assembler, in its Intel syntax
+    Gas,
+    Nasm,
+}
+
+impl Syntax {
+    fn comment(self) -> &'static str {
+        match self {
+            Syntax::Gas => "#",
+            Syntax::Nasm => ";",
+        }
+    }
+
+    /// The operand of `size` at `address`
+    fn ptr(self, size: &str, address: &str) -> String {
+        match self {
+            Syntax::Gas => format!("{} ptr {}", size, address),
+            Syntax::Nasm => format!("{} {}", size, address),
+        }
+    }
+
+    /// The address of `symbol`, relative to the instruction so the program can be anywhere
+    fn symbol(self, symbol: &str) -> String {
+        match self {
+            Syntax::Gas => format!("[rip + {}]", symbol),
+            // `default rel` makes every address of a symbol relative
+            Syntax::Nasm => format!("[{}]", symbol),
+        }
+    }
+}
+
+/// The machine and the system the assembly is for, which have different instructions and
+/// syscalls
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    X86_64Linux,
+    /// Like on the Raspberry Pi
+    Aarch64Linux,
+    /// Apple Silicon
+    Aarch64MacOs,
+}
+
+impl Target {
+    /// The target of the target triple `triple`, if the assembly can be written for it
+    pub fn of(triple: &str) -> Option<Target> {
+        let arch = triple.split('-').next().unwrap_or_default();
+        let apple = ["apple", "darwin", "macos"]
+            .iter()
+            .any(|os| triple.contains(os));
+        match arch {
+            "x86_64" if triple.contains("linux") => Some(Target::X86_64Linux),
+            "aarch64" | "arm64" if triple.contains("linux") => Some(Target::Aarch64Linux),
+            "aarch64" | "arm64" if apple => Some(Target::Aarch64MacOs),
+            _ => None,
+        }
+    }
+
+    /// This machine, if the assembly can be written for it
+    pub fn host() -> Option<Target> {
+        Target::of(&format!("{}-{}", env::consts::ARCH, env::consts::OS))
+    }
+}
+
+/// How many bytes of output are kept before they are written, like the buffer of stdout in C
+const BUFFER_SIZE: usize = 4096;
+
+/// The assembly of `code`, a whole program. Only the GNU assembler is for AArch64
+pub fn translate(
+    code: &[Node],
+    options: &CompileOptions,
+    syntax: Syntax,
+    target: Target,
+) -> Result<String, String> {
+    brainfuck_only("asm", code)?;
+    Ok(match target {
+        Target::X86_64Linux => x86_64(code, options, syntax),
+        _ => aarch64(code, options, target),
+    })
+}
+
+/// The x86-64 assembly of `code`, starting at `_start`
+fn x86_64(code: &[Node], options: &CompileOptions, syntax: Syntax) -> String {
+    let cell_bytes = options.cell_size as isize / 8;
+    let mut writer = X86 {
+        code: String::new(),
+        syntax,
+        cell_bytes,
+        unbuffered: options.unbuffered,
+        labels: 0,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+    };
+    let start = format!("mem + {}", options.offset as isize * cell_bytes);
+    writer.line(&format!("lea rbx, {}", syntax.symbol(&start)));
+    writer.block(code);
+    writer.line("call bf_flush");
+    // The same status as the interpreter exits with, of which the system keeps the low byte
+    let exit_cell = match options.exit_cell {
+        Some(ExitCell::Current) => Some("[rbx]".to_owned()),
+        Some(ExitCell::Cell(cell)) => {
+            Some(syntax.symbol(&format!("mem + {}", cell as isize * cell_bytes)))
+        }
+        None => None,
+    };
+    match exit_cell {
+        Some(address) => writer.load("edi", &address),
+        None => writer.line("xor edi, edi"),
+    }
+    writer.line("mov eax, 60");
+    writer.line("syscall");
+
+    let (q, b) = (
+        |address: &str| syntax.ptr("qword", address),
+        |address: &str| syntax.ptr("byte", address),
+    );
+    let c = syntax.comment();
+    let input = options
+        .input
+        .iter()
+        .map(u8::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    let (header, text, rodata, bytes, bss, reserve, stack) = match syntax {
+        Syntax::Gas => (
+            ".intel_syntax noprefix\n.globl _start",
+            ".text",
+            ".section .rodata",
+            ".byte",
+            ".bss",
+            ".zero",
+            ".section .note.GNU-stack,\"\",@progbits",
+        ),
+        Syntax::Nasm => (
+            "default rel\nglobal _start",
+            "section .text",
+            "section .rodata",
+            "db",
+            "section .bss",
+            "resb",
+            "section .note.GNU-stack noalloc noexec nowrite progbits",
+        ),
+    };
+    let input = match input.is_empty() {
+        true => String::new(),
+        false => format!("    {} {}\n", bytes, input),
+    };
+    let mut asm = format!("{}\n\n{}\n\n_start:\n{}", header, text, writer.code);
+    let _ = write!(
+        asm,
+        "
+{c} Adds the byte in al to the output, and writes it when the buffer is full
+bf_putchar:
+    mov rcx, {buffer_len}
+    lea rdx, {buffer}
+    mov {at_index}, al
+    inc rcx
+    mov {buffer_len}, rcx
+    cmp rcx, {BUFFER_SIZE}
+    je bf_flush
+    ret
+
+{c} Writes the output to stdout. When it cannot be written, it is dropped
+bf_flush:
+    mov rdx, {buffer_len}
+    lea rsi, {buffer}
+bf_flush_loop:
+    test rdx, rdx
+    jz bf_flush_done
+    mov edi, 1
+    mov eax, 1
+    syscall
+    test rax, rax
+    jle bf_flush_done
+    add rsi, rax
+    sub rdx, rax
+    jmp bf_flush_loop
+bf_flush_done:
+    mov {buffer_len}, 0
+    ret
+
+{c} Reads a byte into eax for `,`, from the given input and then stdin, where the end of the
+{c} input reads as 0 like in the interpreter
+bf_getch:
+    mov rcx, {input_pos}
+    cmp rcx, {input_len}
+    jae bf_getch_stdin
+    lea rdx, {input_symbol}
+    movzx eax, {at_index}
+    inc rcx
+    mov {input_pos}, rcx
+    ret
+bf_getch_stdin:
+    call bf_flush
+    sub rsp, 8
+    xor edi, edi
+    mov rsi, rsp
+    mov edx, 1
+    xor eax, eax
+    syscall
+    movzx ecx, {top}
+    add rsp, 8
+    cmp rax, 1
+    mov eax, 0
+    cmove eax, ecx
+    ret
+
+{rodata}
+{c} What `,` reads before stdin
+input:
+{input}
+{bss}
+mem:
+    {reserve} {mem_bytes}
+buffer:
+    {reserve} {BUFFER_SIZE}
+buffer_len:
+    {reserve} 8
+input_pos:
+    {reserve} 8
+
+{c} The stack is not executable
+{stack}
+",
+        buffer_len = q(&syntax.symbol("buffer_len")),
+        buffer = syntax.symbol("buffer"),
+        at_index = b("[rdx + rcx]"),
+        input_pos = q(&syntax.symbol("input_pos")),
+        input_len = options.input.len(),
+        input_symbol = syntax.symbol("input"),
+        top = b("[rsp]"),
+        mem_bytes = options.mem_size as isize * cell_bytes,
+    );
+    asm
+}
+
+/// Writes the x86-64 assembly for the nodes into `_start`
+struct X86 {
+    code: String,
+    syntax: Syntax,
+    /// The bytes of a cell, which the offsets and moves are multiplied by
+    cell_bytes: isize,
+    /// Whether the output is written after every `.`
+    unbuffered: bool,
+    /// How many labels were named, which numbers the next ones
+    labels: usize,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+}
+
+impl X86 {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.code, "    {}", text);
+    }
+
+    fn label(&mut self, name: &str) {
+        let _ = writeln!(self.code, "{}:", name);
+    }
+
+    /// The size of a cell in the operands
+    fn size(&self) -> &'static str {
+        match self.cell_bytes {
+            1 => "byte",
+            2 => "word",
+            _ => "dword",
+        }
+    }
+
+    /// The part of `eax` a cell fits in
+    fn reg(&self) -> &'static str {
+        match self.cell_bytes {
+            1 => "al",
+            2 => "ax",
+            _ => "eax",
+        }
+    }
+
+    /// The address of the cell `offset` away from the pointer
+    fn address(&self, offset: isize) -> String {
+        match offset * self.cell_bytes {
+            0 => "[rbx]".to_owned(),
+            bytes if bytes < 0 => format!("[rbx - {}]", bytes.unsigned_abs()),
+            bytes => format!("[rbx + {}]", bytes),
+        }
+    }
+
+    /// The cell `offset` away from the pointer, as an operand
+    fn cell(&self, offset: isize) -> String {
+        self.syntax.ptr(self.size(), &self.address(offset))
+    }
+
+    /// Loads the cell at `address` into the 32 bit register `reg`
+    fn load(&mut self, reg: &str, address: &str) {
+        let cell = self.syntax.ptr(self.size(), address);
+        match self.cell_bytes {
+            4 => self.line(&format!("mov {}, {}", reg, cell)),
+            _ => self.line(&format!("movzx {}, {}", reg, cell)),
+        }
+    }
+
+    fn putchar(&mut self) {
+        self.line("call bf_putchar");
+        if self.unbuffered {
+            self.line("call bf_flush");
+        }
+    }
+
+    /// Makes the names of the labels of a new loop
+    fn labels(&mut self, kind: &str) -> (String, String) {
+        self.labels += 1;
+        let start = format!("{}{}", kind, self.labels);
+        let end = format!("{}_end", start);
+        (start, end)
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        let comment = format!("{} {}", self.syntax.comment(), node);
+        self.line(&comment);
+        match *node {
+            Node::Move(amount) => self.move_by(amount),
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } => {
+                let cell = self.cell(offset);
+                self.line(&format!("add {}, {}", cell, amount as i8));
+            }
+            Node::SetZero => {
+                let cell = self.cell(0);
+                self.line(&format!("mov {}, 0", cell));
+            }
+            Node::Scan(amount) => {
+                let (start, end) = self.labels("scan");
+                let cell = self.cell(0);
+                self.label(&start);
+                self.line(&format!("cmp {}, 0", cell));
+                self.line(&format!("je {}", end));
+                self.move_by(amount);
+                self.line(&format!("jmp {}", start));
+                self.label(&end);
+            }
+            Node::MulAdd { offset, factor } => {
+                let skip = match self.guard {
+                    true => {
+                        let (_, skip) = self.labels("mul");
+                        let cell = self.cell(0);
+                        self.line(&format!("cmp {}, 0", cell));
+                        self.line(&format!("je {}", skip));
+                        Some(skip)
+                    }
+                    false => None,
+                };
+                self.load("eax", "[rbx]");
+                let (cell, reg) = (self.cell(offset), self.reg());
+                match factor {
+                    1 => self.line(&format!("add {}, {}", cell, reg)),
+                    255 => self.line(&format!("sub {}, {}", cell, reg)),
+                    _ => {
+                        self.line(&format!("imul eax, eax, {}", factor as i8));
+                        self.line(&format!("add {}, {}", cell, reg));
+                    }
+                }
+                if let Some(skip) = skip {
+                    self.label(&skip);
+                }
+            }
+            Node::Output { offset } => {
+                // Only the low byte is written, like for the C code
+                let byte = self.syntax.ptr("byte", &self.address(offset));
+                self.line(&format!("mov al, {}", byte));
+                self.putchar();
+            }
+            Node::Print(value) => {
+                self.line(&format!("mov al, {}", value));
+                self.putchar();
+            }
+            Node::Input { offset } => {
+                self.line("call bf_getch");
+                let (cell, reg) = (self.cell(offset), self.reg());
+                self.line(&format!("mov {}, {}", cell, reg));
+            }
+            Node::Loop { ref body, .. } => {
+                let (start, end) = self.labels("loop");
+                let cell = self.cell(0);
+                self.line(&format!("cmp {}, 0", cell));
+                self.line(&format!("je {}", end));
+                self.label(&start);
+                self.block(body);
+                self.line(&format!("cmp {}, 0", cell));
+                self.line(&format!("jne {}", start));
+                self.label(&end);
+            }
+            // There is no debug mode in the assembly
+            Node::DebugCell | Node::DebugMem => (),
+            // Turned down by `translate`
+            _ => (),
+        }
+    }
+
+    /// Moves the pointer by `amount` cells
+    fn move_by(&mut self, amount: isize) {
+        match amount < 0 {
+            true => self.line(&format!(
+                "sub rbx, {}",
+                amount.unsigned_abs() as isize * self.cell_bytes
+            )),
+            false => self.line(&format!("add rbx, {}", amount * self.cell_bytes)),
+        }
+    }
+}
+
+/// The two instructions that put the address of `symbol` in `reg`
+fn address_of(apple: bool, reg: &str, symbol: &str) -> String {
+    match apple {
+        true => format!(
+            "    adrp {reg}, {symbol}@PAGE\n    add {reg}, {reg}, {symbol}@PAGEOFF",
+            reg = reg,
+            symbol = symbol
+        ),
+        false => format!(
+            "    adrp {reg}, {symbol}\n    add {reg}, {reg}, :lo12:{symbol}",
+            reg = reg,
+            symbol = symbol
+        ),
+    }
+}
+
+/// The instructions of the syscall `name`, which Linux and macOS number differently
+fn syscall(apple: bool, name: &str) -> String {
+    let number = match (apple, name) {
+        (false, "read") => 63,
+        (false, "write") => 64,
+        (false, _) => 93,
+        (true, "read") => 3,
+        (true, "write") => 4,
+        (true, _) => 1,
+    };
+    match apple {
+        true => format!("    mov x16, #{}\n    svc #0x80", number),
+        false => format!("    mov x8, #{}\n    svc #0", number),
+    }
+}
+
+/// The AArch64 assembly of `code`, starting at `_start` on Linux, and at `_main` on macOS where
+/// `cc` links it
+fn aarch64(code: &[Node], options: &CompileOptions, target: Target) -> String {
+    let apple = target == Target::Aarch64MacOs;
+    let cell_bytes = options.cell_size as isize / 8;
+    let mut writer = Arm {
+        code: String::new(),
+        cell_bytes,
+        unbuffered: options.unbuffered,
+        labels: 0,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+    };
+    writer.code += &address_of(apple, "x19", "mem");
+    writer.code.push('\n');
+    writer.add("x19", "x19", options.offset as isize * cell_bytes);
+    writer.block(code);
+    writer.line("bl bf_flush");
+    // The same status as the interpreter exits with, of which the system keeps the low byte
+    match options.exit_cell {
+        Some(ExitCell::Current) => writer.load("w0", "[x19]"),
+        Some(ExitCell::Cell(cell)) => {
+            writer.code += &address_of(apple, "x9", "mem");
+            writer.code.push('\n');
+            writer.add("x9", "x9", cell as isize * cell_bytes);
+            writer.load("w0", "[x9]");
+        }
+        None => writer.line("mov w0, #0"),
+    }
+    writer.code += &syscall(apple, "exit");
+
+    let input = match options.input.is_empty() {
+        true => String::new(),
+        false => format!(
+            "    .byte {}\n",
+            options
+                .input
+                .iter()
+                .map(u8::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+    let (entry, data) = match apple {
+        true => (
+            "_main",
+            format!(
+                "\
+.section __TEXT,__const
+.p2align 3
+input_len:
+    .quad {}
+// What `,` reads before stdin
+input:
+{}
+.zerofill __DATA,__bss,buffer_len,8,3
+.zerofill __DATA,__bss,input_pos,8,3
+.zerofill __DATA,__bss,buffer,{},3
+.zerofill __DATA,__bss,mem,{},3
+",
+                options.input.len(),
+                input,
+                BUFFER_SIZE,
+                options.mem_size as isize * cell_bytes
+            ),
+        ),
+        false => (
+            "_start",
+            format!(
+                "\
+.section .rodata
+.p2align 3
+input_len:
+    .quad {}
+// What `,` reads before stdin
+input:
+{}
+.bss
+.p2align 3
+buffer_len:
+    .zero 8
+input_pos:
+    .zero 8
+buffer:
+    .zero {}
+mem:
+    .zero {}
+
+// The stack is not executable
+.section .note.GNU-stack,\"\",%progbits
+",
+                options.input.len(),
+                input,
+                BUFFER_SIZE,
+                options.mem_size as isize * cell_bytes
+            ),
+        ),
+    };
+    // macOS sets the carry flag when a syscall fails, and returns the error as a positive number
+    let failed = match apple {
+        true => "    b.cs bf_flush_done\n",
+        false => "",
+    };
+    format!(
+        "\
+.globl {entry}
+
+.text
+.p2align 2
+{entry}:
+{code}
+
+// Adds the byte in w0 to the output, and writes it when the buffer is full
+bf_putchar:
+{buffer_len_x1}
+    ldr x2, [x1]
+{buffer_x3}
+    strb w0, [x3, x2]
+    add x2, x2, #1
+    str x2, [x1]
+    cmp x2, #{BUFFER_SIZE}
+    b.eq bf_flush
+    ret
+
+// Writes the output to stdout. When it cannot be written, it is dropped
+bf_flush:
+{buffer_len_x9}
+    ldr x22, [x9]
+{buffer_x21}
+bf_flush_loop:
+    cbz x22, bf_flush_done
+    mov x0, #1
+    mov x1, x21
+    mov x2, x22
+{write}
+{failed}    cmp x0, #0
+    b.le bf_flush_done
+    add x21, x21, x0
+    sub x22, x22, x0
+    b bf_flush_loop
+bf_flush_done:
+{buffer_len_x9}
+    str xzr, [x9]
+    ret
+
+// Reads a byte into w0 for `,`, from the given input and then stdin, where the end of the
+// input reads as 0 like in the interpreter
+bf_getch:
+{input_pos_x9}
+    ldr x10, [x9]
+{input_len_x11}
+    ldr x11, [x11]
+    cmp x10, x11
+    b.hs bf_getch_stdin
+{input_x11}
+    ldrb w0, [x11, x10]
+    add x10, x10, #1
+    str x10, [x9]
+    ret
+bf_getch_stdin:
+    stp x29, x30, [sp, #-32]!
+    bl bf_flush
+    strb wzr, [sp, #16]
+    mov x0, #0
+    add x1, sp, #16
+    mov x2, #1
+{read}
+    cmp x0, #1
+    ldrb w0, [sp, #16]
+    csel w0, w0, wzr, eq
+    ldp x29, x30, [sp], #32
+    ret
+
+{data}",
+        entry = entry,
+        code = writer.code,
+        buffer_len_x1 = address_of(apple, "x1", "buffer_len"),
+        buffer_x3 = address_of(apple, "x3", "buffer"),
+        buffer_len_x9 = address_of(apple, "x9", "buffer_len"),
+        buffer_x21 = address_of(apple, "x21", "buffer"),
+        write = syscall(apple, "write"),
+        failed = failed,
+        input_pos_x9 = address_of(apple, "x9", "input_pos"),
+        input_len_x11 = address_of(apple, "x11", "input_len"),
+        input_x11 = address_of(apple, "x11", "input"),
+        read = syscall(apple, "read"),
+        data = data,
+    )
+}
+
+/// Writes the AArch64 assembly for the nodes
+struct Arm {
+    code: String,
+    /// The bytes of a cell, which the offsets and moves are multiplied by
+    cell_bytes: isize,
+    /// Whether the output is written after every `.`
+    unbuffered: bool,
+    /// How many labels were named, which numbers the next ones
+    labels: usize,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+}
+
+impl Arm {
+    fn line(&mut self, text: &str) {
+        let _ = writeln!(self.code, "    {}", text);
+    }
+
+    fn label(&mut self, name: &str) {
+        let _ = writeln!(self.code, "{}:", name);
+    }
+
+    /// Sets `to` to `from` plus `bytes`, which only fits in the instruction up to 4095
+    fn add(&mut self, to: &str, from: &str, bytes: isize) {
+        let op = match bytes < 0 {
+            true => "sub",
+            false => "add",
+        };
+        let amount = bytes.unsigned_abs();
+        match amount {
+            0 if to == from => (),
+            0..=4095 => self.line(&format!("{} {}, {}, #{}", op, to, from, amount)),
+            _ => {
+                // Built 16 bits at a time
+                self.line(&format!("movz x10, #{}", amount & 0xffff));
+                for shift in [16, 32, 48] {
+                    let part = (amount >> shift) & 0xffff;
+                    if part != 0 {
+                        self.line(&format!("movk x10, #{}, lsl #{}", part, shift));
+                    }
+                }
+                self.line(&format!("{} {}, {}, x10", op, to, from));
+            }
+        }
+    }
+
+    /// The address of the cell `offset` away from the pointer, which goes in `x9` when it is too
+    /// far for the offset of a load
+    fn address(&mut self, offset: isize) -> String {
+        match offset * self.cell_bytes {
+            0 => "[x19]".to_owned(),
+            bytes @ -256..=4095 => format!("[x19, #{}]", bytes),
+            bytes => {
+                self.add("x9", "x19", bytes);
+                "[x9]".to_owned()
+            }
+        }
+    }
+
+    /// The loads and stores of a cell end with this
+    fn suffix(&self) -> &'static str {
+        match self.cell_bytes {
+            1 => "b",
+            2 => "h",
+            _ => "",
+        }
+    }
+
+    fn load(&mut self, reg: &str, address: &str) {
+        let line = format!("ldr{} {}, {}", self.suffix(), reg, address);
+        self.line(&line);
+    }
+
+    fn store(&mut self, reg: &str, address: &str) {
+        let line = format!("str{} {}, {}", self.suffix(), reg, address);
+        self.line(&line);
+    }
+
+    fn putchar(&mut self) {
+        self.line("bl bf_putchar");
+        if self.unbuffered {
+            self.line("bl bf_flush");
+        }
+    }
+
+    /// Makes the names of the labels of a new loop
+    fn labels(&mut self, kind: &str) -> (String, String) {
+        self.labels += 1;
+        let start = format!("{}{}", kind, self.labels);
+        let end = format!("{}_end", start);
+        (start, end)
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        self.line(&format!("// {}", node));
+        match *node {
+            Node::Move(amount) => self.add("x19", "x19", amount * self.cell_bytes),
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } => {
+                let address = self.address(offset);
+                self.load("w0", &address);
+                match amount as i8 {
+                    amount if amount < 0 => {
+                        self.line(&format!("sub w0, w0, #{}", amount.unsigned_abs()))
+                    }
+                    amount => self.line(&format!("add w0, w0, #{}", amount)),
+                }
+                self.store("w0", &address);
+            }
+            Node::SetZero => self.store("wzr", "[x19]"),
+            Node::Scan(amount) => {
+                let (start, end) = self.labels("scan");
+                self.label(&start);
+                self.load("w0", "[x19]");
+                self.line(&format!("cbz w0, {}", end));
+                self.add("x19", "x19", amount * self.cell_bytes);
+                self.line(&format!("b {}", start));
+                self.label(&end);
+            }
+            Node::MulAdd { offset, factor } => {
+                self.load("w0", "[x19]");
+                let skip = match self.guard {
+                    true => {
+                        let (_, skip) = self.labels("mul");
+                        self.line(&format!("cbz w0, {}", skip));
+                        Some(skip)
+                    }
+                    false => None,
+                };
+                let address = self.address(offset);
+                self.load("w1", &address);
+                match factor {
+                    1 => self.line("add w1, w1, w0"),
+                    255 => self.line("sub w1, w1, w0"),
+                    _ => {
+                        self.line(&format!("mov w2, #{}", factor as i8));
+                        self.line("madd w1, w0, w2, w1");
+                    }
+                }
+                self.store("w1", &address);
+                if let Some(skip) = skip {
+                    self.label(&skip);
+                }
+            }
+            Node::Output { offset } => {
+                // Only the low byte is written, like for the C code
+                let address = self.address(offset);
+                self.line(&format!("ldrb w0, {}", address));
+                self.putchar();
+            }
+            Node::Print(value) => {
+                self.line(&format!("mov w0, #{}", value));
+                self.putchar();
+            }
+            Node::Input { offset } => {
+                self.line("bl bf_getch");
+                let address = self.address(offset);
+                self.store("w0", &address);
+            }
+            Node::Loop { ref body, .. } => {
+                let (start, end) = self.labels("loop");
+                self.load("w0", "[x19]");
+                self.line(&format!("cbz w0, {}", end));
+                self.label(&start);
+                self.block(body);
+                self.load("w0", "[x19]");
+                self.line(&format!("cbnz w0, {}", start));
+                self.label(&end);
+            }
+            // There is no debug mode in the assembly
+            Node::DebugCell | Node::DebugMem => (),
+            // Turned down by `translate`
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    interpreter::{BfError, Config},
+    ir,
+};
+use alloc::vec::Vec;
+use core::{fmt, mem};
+
+/// A piece of the source as it was written, for `--emit=ast`. Unlike [`ir::Node`], it keeps
+/// where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Ast {
+    /// A run of instructions without brackets
+    Code { span: Span, instructions: usize },
+    /// `[...]`, where `id` is the position of its `[` among all the `[` of the source
+    Loop {
+        id: usize,
+        span: Span,
+        instructions: usize,
+        body: Vec<Ast>,
+    },
+}
+
+/// Where something is in the source, as the byte offsets of its first and last character
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Ast {
+    pub fn span(&self) -> Span {
+        match *self {
+            Ast::Code { span, .. } | Ast::Loop { span, .. } => span,
+        }
+    }
+
+    /// How many instructions there are in it, brackets and nested loops included
+    pub fn instructions(&self) -> usize {
+        match *self {
+            Ast::Code { instructions, .. } | Ast::Loop { instructions, .. } => instructions,
+        }
+    }
+}
+
+/// Parses the source into the tree of its loops, see [`ir::parse`] for what counts as an
+/// instruction
+pub fn parse(contents: &str, config: &Config) -> Result<Vec<Ast>, BfError> {
+    // Gives the same errors as the nodes
+    ir::parse(contents, config)?;
+    let mut current = Vec::new();
+    // The id and the start of every loop that is still open, with the block it is in
+    let mut open = Vec::new();
+    let mut brackets = 0;
+
+    for (at, ch) in contents.char_indices() {
+        match ch {
+            '[' => {
+                open.push((brackets, at, mem::take(&mut current)));
+                brackets += 1;
+            }
+            ']' => {
+                let (id, start, outer) = open.pop().ok_or(BfError::UnbalancedBrackets)?;
+                let body = mem::replace(&mut current, outer);
+                current.push(Ast::Loop {
+                    id,
+                    span: Span { start, end: at },
+                    instructions: 2 + body.iter().map(Ast::instructions).sum::<usize>(),
+                    body,
+                });
+            }
+            '>' | '<' | '+' | '-' | '.' | ',' => code(&mut current, at),
+            '#' | '|' if config.debug => code(&mut current, at),
+            ch if config.dialect.instructions().contains(ch) => code(&mut current, at),
+            _ => (),
+        }
+    }
+    Ok(current)
+}
+
+/// Adds the instruction at `at` to the code at the end of `block`
+fn code(block: &mut Vec<Ast>, at: usize) {
+    match block.last_mut() {
+        Some(Ast::Code { span, instructions }) => {
+            span.end = at;
+            *instructions += 1;
+        }
+        _ => block.push(Ast::Code {
+            span: Span { start: at, end: at },
+            instructions: 1,
+        }),
+    }
+}
+
+/// The line and the column of the character at the byte offset `at` of `contents`, both from 1
+pub fn position(contents: &str, at: usize) -> (usize, usize) {
+    let before = &contents[..at];
+    let line = before.matches('\n').count() + 1;
+    let column = before.chars().rev().take_while(|&ch| ch != '\n').count() + 1;
+    (line, column)
+}
+
+/// Shows the tree one node per line, with the line and column where it starts and ends in
+/// `contents`, its nesting depth, and how many instructions it has
+pub fn dump<'a>(contents: &'a str, ast: &'a [Ast]) -> impl fmt::Display + 'a {
+    Tree {
+        contents,
+        ast,
+        depth: 0,
+    }
+}
+
+struct Tree<'a> {
+    contents: &'a str,
+    ast: &'a [Ast],
+    depth: usize,
+}
+
+impl fmt::Display for Tree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indent = self.depth * 4;
+        for node in self.ast {
+            match node {
+                Ast::Code { .. } => write!(f, "{:indent$}code", "")?,
+                Ast::Loop { id, .. } => write!(f, "{:indent$}loop {}", "", id)?,
+            }
+            let start = position(self.contents, node.span().start);
+            let end = position(self.contents, node.span().end);
+            writeln!(
+                f,
+                " {}:{}-{}:{}, depth {}, {} instructions",
+                start.0,
+                start.1,
+                end.0,
+                end.1,
+                self.depth,
+                node.instructions()
+            )?;
+            if let Ast::Loop { body, .. } = node {
+                let body = Tree {
+                    contents: self.contents,
+                    ast: body,
+                    depth: self.depth + 1,
+                };
+                write!(f, "{}", body)?;
+            }
+        }
+        Ok(())
+    }
+}
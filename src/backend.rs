@@ -0,0 +1,158 @@
+//! The backends that write the optimized nodes as something else, for `--backend`.
+//!
+//! A [`Backend`] turns the nodes into an [`Artifact`], and a [`Registry`] finds it by its name.
+//! [`Registry::new`] has the backends of this crate, and other crates add their own with
+//! [`Registry::register`] and pass the registry to [`compile_with`](crate::compile_with)
+
+use crate::{
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+
+/// What the program is built with, besides the nodes
+pub struct CompileOptions<'a> {
+    pub mem_size: usize,
+    pub offset: usize,
+    /// The bits of a cell, 8, 16 or 32
+    pub cell_size: u8,
+    /// What `,` reads before stdin, from after a `!` in the source and `--input-str`
+    pub input: &'a [u8],
+    pub exit_cell: Option<ExitCell>,
+    /// Whether `#` and `|` print the cells
+    pub debug: bool,
+    /// Whether they only do when `BF_DEBUG` is set, like with `--debug-env` in C
+    pub debug_env: bool,
+    /// Whether the output is flushed after every write
+    pub unbuffered: bool,
+}
+
+/// What a backend writes, which goes in the output with its extension
+pub struct Artifact {
+    pub contents: Vec<u8>,
+    /// The extension of the file, with its `.`
+    pub extension: &'static str,
+}
+
+impl Artifact {
+    /// Source code, like the C code
+    pub fn text(code: String, extension: &'static str) -> Artifact {
+        Artifact {
+            contents: code.into_bytes(),
+            extension,
+        }
+    }
+}
+
+/// A target the nodes can be written for
+pub trait Backend {
+    /// The name `--backend` takes
+    fn name(&self) -> &'static str;
+
+    /// Writes `code`, or says why it cannot be
+    fn emit(&self, code: &[Node], options: &CompileOptions) -> Result<Artifact, String>;
+}
+
+/// The backends, by their name
+pub struct Registry {
+    backends: Vec<Box<dyn Backend>>,
+}
+
+impl Registry {
+    /// The backends of this crate
+    pub fn new() -> Registry {
+        let mut registry = Registry {
+            backends: Vec::new(),
+        };
+        registry.register(Box::new(crate::c::CBackend));
+        registry.register(Box::new(Text {
+            name: "rust",
+            extension: ".rs",
+            translate: crate::rust::translate,
+        }));
+        registry.register(Box::new(Text {
+            name: "llvm-ir",
+            extension: ".ll",
+            translate: crate::llvm::translate,
+        }));
+        registry.register(Box::new(Text {
+            name: "java",
+            extension: ".java",
+            translate: crate::java::translate,
+        }));
+        registry.register(Box::new(Wasm));
+        registry
+    }
+
+    /// Adds `backend`, in place of the one with the same name if there is one
+    pub fn register(&mut self, backend: Box<dyn Backend>) {
+        self.backends.retain(|other| other.name() != backend.name());
+        self.backends.push(backend);
+    }
+
+    /// The backend called `name`
+    pub fn get(&self, name: &str) -> Option<&dyn Backend> {
+        self.backends
+            .iter()
+            .find(|backend| backend.name() == name)
+            .map(|backend| &**backend)
+    }
+
+    /// The names of the backends, in the order they were added
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.backends.iter().map(|backend| backend.name())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Registry::new()
+    }
+}
+
+/// A backend that writes source code with a function
+struct Text {
+    name: &'static str,
+    extension: &'static str,
+    translate: fn(&[Node], &CompileOptions) -> Result<String, String>,
+}
+
+impl Backend for Text {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn emit(&self, code: &[Node], options: &CompileOptions) -> Result<Artifact, String> {
+        Ok(Artifact::text(
+            (self.translate)(code, options)?,
+            self.extension,
+        ))
+    }
+}
+
+/// The WebAssembly module of `--emit=wasm`
+struct Wasm;
+
+impl Backend for Wasm {
+    fn name(&self) -> &'static str {
+        "wasm"
+    }
+
+    fn emit(&self, code: &[Node], options: &CompileOptions) -> Result<Artifact, String> {
+        Ok(Artifact {
+            contents: crate::wasm::program(code, options)?,
+            extension: ".wasm",
+        })
+    }
+}
+
+/// Fails for the backends other than the C code if `code` has instructions of the dialects,
+/// which they all call before writing it
+pub(crate) fn brainfuck_only(name: &str, code: &[Node]) -> Result<(), String> {
+    match ir::contains(code, ir::is_extension) {
+        true => Err(format!(
+            "The {} backend only has the instructions of Brainfuck",
+            name
+        )),
+        false => Ok(()),
+    }
+}
@@ -0,0 +1,92 @@
+//! The Brainloller front-end, for `.png` files.
+//!
+//! Brainloller has the eight instructions as colors of the pixels of an image. The code runs
+//! from the top left pixel to the right, and two more colors turn the way it goes, until it
+//! leaves the image. Every other color is a comment
+
+use crate::png;
+use alloc::string::String;
+
+/// The way the code goes, in the order a clockwise turn changes it to
+const WAYS: [(isize, isize); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+
+/// The Brainfuck code of the Brainloller image in the PNG `bytes`
+pub fn translate(bytes: &[u8]) -> Result<String, String> {
+    let image = png::decode(bytes)?;
+    let mut code = String::new();
+    let (mut x, mut y, mut way) = (0, 0, 0);
+    // Every pixel and way the path is at comes from only one pixel and way, and nothing in the
+    // image comes to the top left pixel going right, so the path can't go in circles
+    while x < image.width && y < image.height {
+        match image.pixel(x, y) {
+            [255, 0, 0] => code.push('>'),
+            [128, 0, 0] => code.push('<'),
+            [0, 255, 0] => code.push('+'),
+            [0, 128, 0] => code.push('-'),
+            [0, 0, 255] => code.push('.'),
+            [0, 0, 128] => code.push(','),
+            [255, 255, 0] => code.push('['),
+            [128, 128, 0] => code.push(']'),
+            [0, 255, 255] => way = (way + 1) % WAYS.len(),
+            [0, 128, 128] => way = (way + WAYS.len() - 1) % WAYS.len(),
+            _ => (),
+        }
+        // Leaving by the top or the left wraps around to past the other side
+        x = x.wrapping_add_signed(WAYS[way].0);
+        y = y.wrapping_add_signed(WAYS[way].1);
+    }
+    Ok(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::{vec, vec::Vec};
+
+    /// A PNG of RGB `rows`, stored in the zlib stream without compression
+    fn png(width: u32, rows: &[&[[u8; 3]]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for row in rows {
+            data.push(0);
+            data.extend(row.iter().flatten());
+        }
+        let len = data.len() as u16;
+        let mut zlib = vec![0x78, 0x01, 0x01];
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(data);
+        zlib.extend([0; 4]);
+
+        let mut header = Vec::new();
+        header.extend(width.to_be_bytes());
+        header.extend((rows.len() as u32).to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);
+        let mut bytes = b"\x89PNG\r\n\x1a\n".to_vec();
+        for (kind, body) in [(b"IHDR", header), (b"IDAT", zlib), (b"IEND", Vec::new())] {
+            bytes.extend((body.len() as u32).to_be_bytes());
+            bytes.extend(kind);
+            bytes.extend(body);
+            // The checksums are not checked
+            bytes.extend([0; 4]);
+        }
+        bytes
+    }
+
+    #[test]
+    fn colors_become_instructions_and_turns() {
+        const PLUS: [u8; 3] = [0, 255, 0];
+        const MINUS: [u8; 3] = [0, 128, 0];
+        const DOT: [u8; 3] = [0, 0, 255];
+        const CLOCKWISE: [u8; 3] = [0, 255, 255];
+        const WHITE: [u8; 3] = [255, 255, 255];
+        let image = png(
+            4,
+            &[
+                &[PLUS, WHITE, DOT, CLOCKWISE],
+                &[DOT, MINUS, WHITE, CLOCKWISE],
+            ],
+        );
+        assert_eq!(translate(&image).as_deref(), Ok("+.-."));
+        assert_eq!(translate(b"GIF89a"), Err("Not a PNG image".into()));
+    }
+}
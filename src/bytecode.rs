@@ -0,0 +1,532 @@
+use crate::{
+    interpreter::{BfError, Config},
+    ir::{self, Bits, Node},
+};
+use alloc::{format, vec::Vec};
+use core::fmt;
+
+/// One instruction of the program, with the jumps already resolved
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Moves the pointer by the given amount, once for all the `>` and `<` before it
+    Move(isize),
+    /// A run of `+` or `-`, adding `amount` to the cell `offset` away from the pointer
+    Add {
+        offset: isize,
+        amount: u8,
+    },
+    /// A `[-]` or `[+]` loop, which always ends with the cell at 0
+    SetZero,
+    /// A `[-]` followed by `+` or `-`, setting the cell to the given value
+    Set(u8),
+    /// An [`Instr::Add`] followed by an [`Instr::Move`] by `by`
+    AddMove {
+        offset: isize,
+        amount: u8,
+        by: isize,
+    },
+    /// A `[>]` or `[<]` loop, moving by the given amount until the cell is 0
+    Scan(isize),
+    /// Adds the current cell times `factor` to the cell `offset` away, see [`Node::MulAdd`]
+    MulAdd {
+        offset: isize,
+        factor: u8,
+    },
+    Output {
+        offset: isize,
+    },
+    Input {
+        offset: isize,
+    },
+    /// Outputs the given value, see [`Node::Print`]
+    Print(u8),
+    /// `[`, jumps to the matching `]` at the given index if the cell is 0, so that
+    /// no bracket ever has to be looked up while running
+    JumpIfZero(usize),
+    /// `]`, jumps back to the matching `[` at the given index if the cell is not 0
+    JumpUnlessZero(usize),
+    /// `#`, prints the current cell in the debug mode
+    DebugCell,
+    /// `|`, prints the memory around the current cell in the debug mode
+    DebugMem,
+    /// `(`, makes the instructions after it the procedure of the value of the current cell, and
+    /// jumps to its [`Instr::Return`] at the given index
+    Proc(usize),
+    /// `)`, goes back to after the [`Instr::Call`] that ran the procedure
+    Return,
+    /// `:`, runs the procedure of the value of the current cell
+    Call,
+    /// `$`, copies the current cell to the storage
+    Store,
+    /// `!`, copies the storage to the current cell
+    Retrieve,
+    /// Changes the bits of the current cell, see [`Bits`]
+    Bits(Bits),
+    /// `@`, ends the program
+    End,
+    /// `{` and `}`, goes to the tape the given amount after the current one
+    Tape(isize),
+    /// `Y`, forks the program
+    Fork,
+    /// `?`, sets the current cell to a random byte
+    Random,
+    /// `/` and `\`, open the file named from the current cell on
+    FileOpen {
+        write: bool,
+    },
+    /// `;`, reads a byte of the file
+    FileRead,
+    /// `:`, writes the current cell to the file
+    FileWrite,
+    /// `=`, tells whether the last `;` read a byte
+    FileStatus,
+}
+
+/// Turns the source into instructions, see [`ir::parse`]
+pub fn lower(contents: &str, config: &Config) -> Result<Vec<Instr>, BfError> {
+    lower_with_info(contents, config).map(|lowered| lowered.code)
+}
+
+/// The instructions of [`lower_with_info`], with what is known about them
+pub(crate) struct Lowered {
+    pub code: Vec<Instr>,
+    /// The id of the loop every [`Instr::JumpIfZero`] comes from
+    pub loops: Vec<usize>,
+    /// The cells the code can touch, see [`ir::bounds`]
+    pub bounds: Option<ir::Bounds>,
+}
+
+/// [`lower`], along with what the nodes tell about the instructions
+pub(crate) fn lower_with_info(contents: &str, config: &Config) -> Result<Lowered, BfError> {
+    let mut code = Vec::new();
+    let mut loops = Vec::new();
+    let nodes = ir::optimize_with(ir::parse(contents, config)?, config)?;
+    flatten(&nodes, &mut code, &mut loops);
+    // Fusing only changes how the instructions are dispatched, but it is left out along with
+    // the passes so that the instructions match the source one to one
+    if config.passes != 0 {
+        code = fuse(code);
+    }
+    if config.verify_ir {
+        check_jumps(&code)?;
+    }
+    Ok(Lowered {
+        code,
+        loops,
+        bounds: ir::bounds(&nodes),
+    })
+}
+
+/// Appends the instructions of `nodes` to `code`, resolving the jumps of every loop
+fn flatten(nodes: &[Node], code: &mut Vec<Instr>, loops: &mut Vec<usize>) {
+    for node in nodes {
+        let instr = match *node {
+            Node::Move(amount) => Instr::Move(amount),
+            Node::Add { offset, amount } => Instr::Add { offset, amount },
+            Node::SetZero => Instr::SetZero,
+            Node::Scan(amount) => Instr::Scan(amount),
+            Node::MulAdd { offset, factor } => Instr::MulAdd { offset, factor },
+            Node::Output { offset } => Instr::Output { offset },
+            Node::Input { offset } => Instr::Input { offset },
+            Node::Print(value) => Instr::Print(value),
+            Node::Loop { id, ref body } => {
+                let start = code.len();
+                code.push(Instr::JumpIfZero(0));
+                loops.push(id);
+                flatten(body, code, loops);
+                code[start] = Instr::JumpIfZero(code.len());
+                Instr::JumpUnlessZero(start)
+            }
+            Node::DebugCell => Instr::DebugCell,
+            Node::DebugMem => Instr::DebugMem,
+            Node::Proc { ref body } => {
+                let start = code.len();
+                code.push(Instr::Proc(0));
+                flatten(body, code, loops);
+                code[start] = Instr::Proc(code.len());
+                Instr::Return
+            }
+            Node::Call => Instr::Call,
+            Node::Store => Instr::Store,
+            Node::Retrieve => Instr::Retrieve,
+            Node::Bits(op) => Instr::Bits(op),
+            Node::End => Instr::End,
+            Node::Tape(amount) => Instr::Tape(amount),
+            Node::Fork => Instr::Fork,
+            Node::Random => Instr::Random,
+            Node::FileOpen { write } => Instr::FileOpen { write },
+            Node::FileRead => Instr::FileRead,
+            Node::FileWrite => Instr::FileWrite,
+            Node::FileStatus => Instr::FileStatus,
+        };
+        code.push(instr);
+    }
+}
+
+/// Combines instructions that often come one after another into one, so that the
+/// interpreter goes through a single instruction for both
+fn fuse(code: Vec<Instr>) -> Vec<Instr> {
+    let mut fused = Vec::with_capacity(code.len());
+    // Where every instruction of `code` ended up in `fused`
+    let mut moved = Vec::with_capacity(code.len());
+
+    for instr in code {
+        let combined = match (fused.last(), instr) {
+            (Some(Instr::SetZero), Instr::Add { offset: 0, amount }) => Some(Instr::Set(amount)),
+            (Some(&Instr::Add { offset, amount }), Instr::Move(by)) => {
+                Some(Instr::AddMove { offset, amount, by })
+            }
+            _ => None,
+        };
+        if combined.is_some() {
+            fused.pop();
+        }
+        fused.push(combined.unwrap_or(instr));
+        moved.push(fused.len() - 1);
+    }
+    for instr in &mut fused {
+        if let Instr::JumpIfZero(target) | Instr::JumpUnlessZero(target) | Instr::Proc(target) =
+            instr
+        {
+            *target = moved[*target]
+        }
+    }
+    fused
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Instr::Move(amount) => write!(f, "move {}", amount),
+            Instr::Add { offset, amount } => write!(f, "add [{}] {}", offset, amount as i8),
+            Instr::SetZero => write!(f, "zero"),
+            Instr::Set(value) => write!(f, "set {}", value),
+            Instr::AddMove { offset, amount, by } => {
+                write!(f, "add [{}] {}, move {}", offset, amount as i8, by)
+            }
+            Instr::Scan(amount) => write!(f, "scan {}", amount),
+            Instr::MulAdd { offset, factor } => write!(f, "mul [{}] {}", offset, factor),
+            Instr::Output { offset } => write!(f, "out [{}]", offset),
+            Instr::Input { offset } => write!(f, "in [{}]", offset),
+            Instr::Print(value) => write!(f, "out {}", value),
+            Instr::JumpIfZero(target) => write!(f, "jz {}", target),
+            Instr::JumpUnlessZero(target) => write!(f, "jnz {}", target),
+            Instr::DebugCell => write!(f, "debug cell"),
+            Instr::DebugMem => write!(f, "debug mem"),
+            Instr::Proc(target) => write!(f, "proc {}", target),
+            Instr::Return => write!(f, "return"),
+            Instr::Call => write!(f, "call"),
+            Instr::Store => write!(f, "store"),
+            Instr::Retrieve => write!(f, "retrieve"),
+            Instr::Bits(op) => write!(f, "bits {}", op.symbol()),
+            Instr::End => write!(f, "end program"),
+            Instr::Tape(amount) => write!(f, "tape {}", amount),
+            Instr::Fork => write!(f, "fork"),
+            Instr::Random => write!(f, "random"),
+            Instr::FileOpen { write: false } => write!(f, "open file for reading"),
+            Instr::FileOpen { write: true } => write!(f, "open file for writing"),
+            Instr::FileRead => write!(f, "read file"),
+            Instr::FileWrite => write!(f, "write file"),
+            Instr::FileStatus => write!(f, "file status"),
+        }
+    }
+}
+
+/// The start of every `.bfc` file, changed whenever the instructions change
+const MAGIC: &[u8; 4] = b"BFC9";
+
+/// A hash of the code and the settings that change its instructions, to tell whether a `.bfc`
+/// file was made from it
+pub fn hash(contents: &str, config: &Config) -> u64 {
+    // FNV-1a
+    let flags = [
+        config.debug as u8,
+        config.verbose as u8,
+        config.dialect as u8,
+    ];
+    contents
+        .bytes()
+        .chain(flags)
+        .chain(config.passes.to_le_bytes())
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+/// Writes the instructions in the `.bfc` format, with the [`hash`] of their source
+pub fn encode(code: &[Instr], hash: u64) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(hash.to_le_bytes());
+    bytes.extend((code.len() as u64).to_le_bytes());
+    for &instr in code {
+        match instr {
+            Instr::Move(amount) => {
+                bytes.push(0);
+                bytes.extend((amount as i64).to_le_bytes());
+            }
+            Instr::Fork => bytes.push(22),
+            Instr::Random => bytes.push(23),
+            Instr::FileOpen { write } => bytes.extend([24, write as u8]),
+            Instr::FileRead => bytes.push(25),
+            Instr::FileWrite => bytes.push(26),
+            Instr::FileStatus => bytes.push(27),
+            Instr::Add { offset, amount } => {
+                bytes.push(1);
+                bytes.extend((offset as i64).to_le_bytes());
+                bytes.push(amount);
+            }
+            Instr::SetZero => bytes.push(2),
+            Instr::Set(value) => bytes.extend([3, value]),
+            Instr::AddMove { offset, amount, by } => {
+                bytes.push(4);
+                bytes.extend((offset as i64).to_le_bytes());
+                bytes.push(amount);
+                bytes.extend((by as i64).to_le_bytes());
+            }
+            Instr::Scan(amount) => {
+                bytes.push(5);
+                bytes.extend((amount as i64).to_le_bytes());
+            }
+            Instr::Output { offset } => {
+                bytes.push(6);
+                bytes.extend((offset as i64).to_le_bytes());
+            }
+            Instr::Input { offset } => {
+                bytes.push(7);
+                bytes.extend((offset as i64).to_le_bytes());
+            }
+            Instr::JumpIfZero(target) => {
+                bytes.push(8);
+                bytes.extend((target as u64).to_le_bytes());
+            }
+            Instr::JumpUnlessZero(target) => {
+                bytes.push(9);
+                bytes.extend((target as u64).to_le_bytes());
+            }
+            Instr::DebugCell => bytes.push(10),
+            Instr::DebugMem => bytes.push(11),
+            Instr::MulAdd { offset, factor } => {
+                bytes.push(12);
+                bytes.extend((offset as i64).to_le_bytes());
+                bytes.push(factor);
+            }
+            Instr::Print(value) => bytes.extend([13, value]),
+            Instr::Proc(target) => {
+                bytes.push(14);
+                bytes.extend((target as u64).to_le_bytes());
+            }
+            Instr::Return => bytes.push(15),
+            Instr::Call => bytes.push(16),
+            Instr::Store => bytes.push(17),
+            Instr::Retrieve => bytes.push(18),
+            Instr::Bits(op) => bytes.extend([19, op.symbol() as u8]),
+            Instr::End => bytes.push(20),
+            Instr::Tape(amount) => {
+                bytes.push(21);
+                bytes.extend((amount as i64).to_le_bytes());
+            }
+        }
+    }
+    bytes
+}
+
+/// The bytes of a `.bfc` file that are left to read
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], BfError> {
+        if self.0.len() < N {
+            return Err(BfError::InvalidBytecode("unexpected end of file".into()));
+        }
+        let (head, rest) = self.0.split_at(N);
+        self.0 = rest;
+        let mut bytes = [0; N];
+        bytes.copy_from_slice(head);
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, BfError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    fn word(&mut self) -> Result<u64, BfError> {
+        Ok(u64::from_le_bytes(self.take()?))
+    }
+
+    fn signed(&mut self) -> Result<isize, BfError> {
+        Ok(i64::from_le_bytes(self.take()?) as isize)
+    }
+}
+
+/// Reads a `.bfc` file back into the hash of its source and its instructions, making sure
+/// that every jump lands on its matching bracket
+pub fn decode(bytes: &[u8]) -> Result<(u64, Vec<Instr>), BfError> {
+    let mut reader = Reader(bytes);
+    if &reader.take()? != MAGIC {
+        return Err(BfError::InvalidBytecode("not a .bfc file".into()));
+    }
+    let hash = reader.word()?;
+    let len = reader.word()?;
+
+    let mut code = Vec::new();
+    for _ in 0..len {
+        let instr =
+            match reader.byte()? {
+                0 => Instr::Move(reader.signed()?),
+                1 => Instr::Add {
+                    offset: reader.signed()?,
+                    amount: reader.byte()?,
+                },
+                2 => Instr::SetZero,
+                3 => Instr::Set(reader.byte()?),
+                4 => Instr::AddMove {
+                    offset: reader.signed()?,
+                    amount: reader.byte()?,
+                    by: reader.signed()?,
+                },
+                5 => Instr::Scan(reader.signed()?),
+                6 => Instr::Output {
+                    offset: reader.signed()?,
+                },
+                7 => Instr::Input {
+                    offset: reader.signed()?,
+                },
+                8 => Instr::JumpIfZero(reader.word()? as usize),
+                9 => Instr::JumpUnlessZero(reader.word()? as usize),
+                10 => Instr::DebugCell,
+                11 => Instr::DebugMem,
+                12 => Instr::MulAdd {
+                    offset: reader.signed()?,
+                    factor: reader.byte()?,
+                },
+                13 => Instr::Print(reader.byte()?),
+                14 => Instr::Proc(reader.word()? as usize),
+                15 => Instr::Return,
+                16 => Instr::Call,
+                17 => Instr::Store,
+                18 => Instr::Retrieve,
+                19 => {
+                    let symbol = reader.byte()?;
+                    Instr::Bits(Bits::from_symbol(symbol as char).ok_or_else(|| {
+                        BfError::InvalidBytecode(format!("unknown bits {}", symbol))
+                    })?)
+                }
+                20 => Instr::End,
+                21 => Instr::Tape(reader.signed()?),
+                22 => Instr::Fork,
+                23 => Instr::Random,
+                24 => Instr::FileOpen {
+                    write: match reader.byte()? {
+                        0 => false,
+                        1 => true,
+                        mode => {
+                            return Err(BfError::InvalidBytecode(format!(
+                                "unknown file mode {}",
+                                mode
+                            )))
+                        }
+                    },
+                },
+                25 => Instr::FileRead,
+                26 => Instr::FileWrite,
+                27 => Instr::FileStatus,
+                tag => {
+                    return Err(BfError::InvalidBytecode(format!(
+                        "unknown instruction {}",
+                        tag
+                    )))
+                }
+            };
+        code.push(instr);
+    }
+    check_jumps(&code)?;
+    Ok((hash, code))
+}
+
+/// Makes sure that every jump lands on its matching bracket, and every procedure ends in a
+/// [`Instr::Return`]
+fn check_jumps(code: &[Instr]) -> Result<(), BfError> {
+    for (i, &instr) in code.iter().enumerate() {
+        let matched = match instr {
+            Instr::JumpIfZero(target) => code.get(target) == Some(&Instr::JumpUnlessZero(i)),
+            Instr::JumpUnlessZero(target) => code.get(target) == Some(&Instr::JumpIfZero(i)),
+            Instr::Proc(target) => target > i && code.get(target) == Some(&Instr::Return),
+            _ => true,
+        };
+        if !matched {
+            return Err(BfError::InvalidBytecode(format!(
+                "jump {} is not matched",
+                i
+            )));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Dialect;
+
+    /// Programs of each dialect, with instructions of every kind between them
+    const PROGRAMS: &[(Dialect, &str)] = &[
+        (Dialect::Brainfuck, "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+."),
+        (Dialect::Brainfuck, ",[->+>++<<]>.>.[-]+++.[>]<<[<]"),
+        (Dialect::Pbrain, "+(-[->+<]>.):>+++:"),
+        (Dialect::Extended1, "+++$>!{}~^&|.@+"),
+        (Dialect::Multitape, "+{+}>+[-{+}]"),
+        (Dialect::Brainfork, "+Y."),
+        (Dialect::Random, "?."),
+        (Dialect::Files, "+/;=.\\:"),
+    ];
+
+    #[test]
+    fn encode_then_decode_gives_the_same_code() {
+        for &(dialect, contents) in PROGRAMS {
+            for level in 0..=4 {
+                let config = Config {
+                    dialect,
+                    passes: ir::level_passes(level),
+                    ..Config::default()
+                };
+                let code = lower(contents, &config).unwrap();
+                let hash = hash(contents, &config);
+                assert_eq!(
+                    decode(&encode(&code, hash)).unwrap(),
+                    (hash, code),
+                    "{} at level {}",
+                    contents,
+                    level
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn decode_turns_down_broken_files() {
+        let code = lower("+[->+<]>.", &Config::default()).unwrap();
+        let bytes = encode(&code, 0);
+
+        let mut magic = bytes.clone();
+        magic[0] = b'X';
+        assert!(decode(&magic).is_err());
+
+        for len in 0..bytes.len() {
+            assert!(decode(&bytes[..len]).is_err(), "cut at {}", len);
+        }
+
+        let mut tag = bytes.clone();
+        tag[20] = 255;
+        assert!(decode(&tag).is_err());
+
+        for broken in [
+            &[Instr::JumpIfZero(1), Instr::JumpUnlessZero(1)][..],
+            &[Instr::JumpIfZero(5)],
+            &[Instr::JumpUnlessZero(0)],
+            &[Instr::Return, Instr::Proc(0)],
+            &[Instr::Proc(1), Instr::End],
+        ] {
+            assert!(decode(&encode(broken, 0)).is_err(), "{:?}", broken);
+        }
+    }
+}
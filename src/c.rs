@@ -0,0 +1,1677 @@
+//! Writes the nodes as C code, for `--emit=c`, the libraries, the C compilers and the backend
+//! `c`.
+//!
+//! The code is in `main`, or in `bf_program` for the libraries, with the memory in a static
+//! array and the pointer in a local. [`translate`] takes the settings every backend has in the
+//! [`CompileOptions`], and the ones only the C code has, which come from its flags, in
+//! [`CFlags`]
+
+use crate::{
+    ast,
+    backend::{Artifact, Backend, CompileOptions},
+    interpreter::{ExitCell, LoopCount, CALL_DEPTH, THREAD_LIMIT},
+    ir::{self, Bits, Node},
+    BfError, Config, Emit,
+};
+use std::path::Path;
+
+/// The settings of the C code that only come from its flags
+pub struct CFlags<'a> {
+    /// What the code is written as, the program of `main` for `None`
+    pub emit: Option<Emit>,
+    /// Whether `,` reads with `getchar` instead of reading keys from the terminal
+    pub getchar: bool,
+    /// Whether the code needs no libc, see `--freestanding`
+    pub freestanding: bool,
+    /// Whether the pointer is checked before it leaves the memory
+    pub checked: bool,
+    /// Whether the memory is on the heap and grows instead
+    pub grow_tape: bool,
+    /// The tapes of the multitape dialect
+    pub tapes: usize,
+    /// Where `?` starts from, or `None` for it to be different every run
+    pub seed: Option<u64>,
+    /// The output, which the libraries include their header by
+    pub output: &'a str,
+    /// How often every loop ran, from `--profile-use`
+    pub profile: Option<&'a [LoopCount]>,
+    /// The C file the code goes in where it says `{{BODY}}`, from `--template`
+    pub template: Option<&'a str>,
+    /// Where the nodes came from, for `--line-directives` and `--annotate`
+    pub source: Option<&'a mut Source>,
+}
+
+impl Default for CFlags<'_> {
+    fn default() -> Self {
+        CFlags {
+            emit: None,
+            getchar: false,
+            freestanding: false,
+            checked: false,
+            grow_tape: false,
+            tapes: 2,
+            seed: None,
+            output: "output",
+            profile: None,
+            template: None,
+            source: None,
+        }
+    }
+}
+
+/// Average iterations per entry from which a loop gets unrolled
+const HOT_LOOP: u64 = 16;
+
+/// The most instructions of the source an `--annotate` comment shows
+const SNIPPET_LEN: usize = 60;
+
+/// `memrchr` where it is in the libc, and the same function where it is not
+const MEMRCHR: &str = "\
+#ifdef __GLIBC__
+#define bf_memrchr memrchr
+#else
+static void* bf_memrchr(const void* s, int c, size_t n) {
+    const unsigned char* p = (const unsigned char*)s + n;
+    while (n--) if (*--p == (unsigned char)c) return (void*)p;
+    return NULL;
+}
+#endif
+";
+
+/// The header of `--emit=c-lib`, declaring the function the code is in
+pub fn lib_header(options: &CompileOptions, flags: &CFlags) -> String {
+    let main = match flags.emit {
+        Some(Emit::CDylib) => {
+            "
+/* Runs the code on a memory of its own, reading stdin and writing stdout */
+BF_EXPORT int bf_main(void);
+"
+        }
+        _ => "",
+    };
+    format!(
+        "\
+#ifndef BF_PROGRAM_H
+#define BF_PROGRAM_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+typedef uint{}_t bf_cell;
+
+/* What the shared library exports the functions with */
+#ifndef BF_EXPORT
+#define BF_EXPORT
+#endif
+
+/* What , and . go through, with `data` passed to both */
+typedef struct bf_io {{
+    void* data;
+    void (*put)(void* data, unsigned char c);
+    int (*get)(void* data);
+}} bf_io;
+
+/* Runs the code on the `len` cells of `tape`, starting at cell {}. Returns the exit code, or -1
+   without running it if `tape` has less than the {} cells the code was compiled for */
+BF_EXPORT int bf_program(bf_cell* tape, size_t len, bf_io* io);
+{}
+#endif
+",
+        options.cell_size, options.offset, options.mem_size, main
+    )
+}
+
+/// The files of the files dialect, the one `/` opens for `;` to read and the one `\` opens
+/// for `:` to write, and whether the last `;` read a byte, for `=`
+const FILES: &str = "
+/* The files of the files dialect, and whether the last read got a byte */
+static FILE* bf_files[2];
+static int bf_file_status;
+
+/* Opens the file named by the cells from `name` up to the next 0, with `room` cells left in
+   the memory, for reading or for writing, after closing the one that was open for the same.
+   An empty name only closes it */
+static bf_cell bf_open(const bf_cell* name, long room, int write) {
+    long len = 0, i;
+    char* path;
+    while (len < room && name[len]) len++;
+    if (bf_files[write]) fclose(bf_files[write]);
+    bf_files[write] = NULL;
+    if (!len || !(path = malloc(len + 1))) return 0;
+    for (i = 0; i < len; i++) path[i] = (char)name[i];
+    path[len] = 0;
+    bf_files[write] = fopen(path, write ? \"wb\" : \"rb\");
+    free(path);
+    return bf_files[write] != NULL;
+}
+
+/* The next byte of the file open for reading, or 0 at its end like the input */
+static bf_cell bf_read_file(void) {
+    int ch = bf_files[0] ? getc(bf_files[0]) : EOF;
+    bf_file_status = ch != EOF;
+    return ch == EOF ? 0 : ch;
+}
+";
+
+/// The threads of `--pthreads`, for a memory of `len` cells. Every thread runs `bf_code` from
+/// the fork it was started by, and `main` joins them once its own code has ended
+fn thread_runtime(len: usize) -> String {
+    format!(
+        "
+#include <pthread.h>
+
+/* A thread started by a fork, which runs the code from there on a memory of its own */
+struct bf_thread {{
+    bf_cell* mem;
+    long cell;
+    int start;
+    pthread_t id;
+    struct bf_thread* next;
+}};
+
+/* The threads that were not joined yet, and how many are running with `main` */
+static struct bf_thread* bf_threads;
+static unsigned int bf_running = 1;
+static int bf_exit;
+static pthread_mutex_t bf_lock = PTHREAD_MUTEX_INITIALIZER;
+
+static int bf_code(bf_cell* mem, bf_cell* ptr, int bf_start);
+
+static void bf_thread_fail(const char* what) {{
+    fflush(stdout);
+    fprintf(stderr, \"%s\\n\", what);
+    exit(1);
+}}
+
+/* The exit code is the one of the thread that ends last, like in the interpreter */
+static void bf_done(int code) {{
+    pthread_mutex_lock(&bf_lock);
+    bf_exit = code;
+    bf_running--;
+    pthread_mutex_unlock(&bf_lock);
+}}
+
+static void* bf_run(void* data) {{
+    struct bf_thread* thread = data;
+    bf_done(bf_code(thread->mem, thread->mem + thread->cell, thread->start));
+    return NULL;
+}}
+
+/* Starts a thread from the fork `start`, with a copy of the memory where the next cell is 1 */
+static void bf_fork(bf_cell* mem, bf_cell* ptr, int start) {{
+    struct bf_thread* thread;
+    if (ptr - mem + 1 >= {len}) bf_thread_fail(\"Memory index out of bound\");
+    thread = malloc(sizeof(*thread));
+    if (!thread || !(thread->mem = malloc({len} * sizeof(*mem)))) bf_thread_fail(\"out of memory\");
+    memcpy(thread->mem, mem, {len} * sizeof(*mem));
+    thread->cell = ptr - mem + 1;
+    thread->mem[thread->cell] = 1;
+    thread->start = start;
+    pthread_mutex_lock(&bf_lock);
+    if (bf_running == {limit}) bf_thread_fail(\"Too many threads\");
+    bf_running++;
+    thread->next = bf_threads;
+    bf_threads = thread;
+    if (pthread_create(&thread->id, NULL, bf_run, thread)) bf_thread_fail(\"Too many threads\");
+    pthread_mutex_unlock(&bf_lock);
+}}
+
+/* Waits for every thread, also the ones started by the threads it waits for */
+static void bf_join(void) {{
+    for (;;) {{
+        struct bf_thread* thread;
+        pthread_mutex_lock(&bf_lock);
+        thread = bf_threads;
+        if (thread) bf_threads = thread->next;
+        pthread_mutex_unlock(&bf_lock);
+        if (!thread) return;
+        pthread_join(thread->id, NULL);
+        free(thread->mem);
+        free(thread);
+    }}
+}}
+",
+        len = len,
+        limit = THREAD_LIMIT
+    )
+}
+
+/// The C code of `--emit=c-lib`, up to where the code goes, with how `,` reads a byte in it
+fn lib_prelude(options: &CompileOptions, flags: &CFlags) -> (String, &'static str) {
+    let name = Path::new(flags.output)
+        .file_name()
+        .map_or(flags.output.into(), |name| name.to_string_lossy());
+    let input = options.input;
+    // The position in the input is a local, so that every call reads it from the start
+    let (getch, input_code, input_pos) = match input.is_empty() {
+        false => (
+            "bf_getch(io, &input_pos)",
+            format!(
+                "
+static const bf_cell input[] = {{{}}};
+
+static int bf_getch(bf_io* io, size_t* pos) {{
+    if (*pos < sizeof(input) / sizeof(*input)) return input[(*pos)++];
+    return io->get(io->data);
+}}
+",
+                input
+                    .iter()
+                    .map(u8::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            "    size_t input_pos = 0;\n",
+        ),
+        true => ("io->get(io->data)", String::new(), ""),
+    };
+    let export = match flags.emit {
+        Some(Emit::CDylib) => {
+            "\
+#include <stdio.h>
+#include <stdlib.h>
+
+#ifdef _WIN32
+#define BF_EXPORT __declspec(dllexport)
+#else
+#define BF_EXPORT __attribute__((visibility(\"default\")))
+#endif
+"
+        }
+        _ => "",
+    };
+    let code = format!(
+        "\
+#define _GNU_SOURCE
+#include <string.h>
+{}#include \"{}.h\"
+
+{}
+static inline void bf_write(bf_io* io, const char* s, size_t n) {{
+    while (n--) io->put(io->data, (unsigned char)*s++);
+}}
+{}
+int bf_program(bf_cell* tape, size_t len, bf_io* io) {{
+    bf_cell* mem = tape;
+    bf_cell* ptr = mem + {};
+{}    /* Nothing checks the pointer, so all the memory the code was compiled for has to be there */
+    if (len < {}) return -1;
+",
+        export, name, MEMRCHR, input_code, options.offset, input_pos, options.mem_size
+    );
+    (code, getch)
+}
+
+/// The C code of the program, as the backend `c`. It is the program `--emit=c` writes, without
+/// what only comes from the source or the flags of the C code
+pub struct CBackend;
+
+impl Backend for CBackend {
+    fn name(&self) -> &'static str {
+        "c"
+    }
+
+    fn emit(&self, code: &[Node], options: &CompileOptions) -> Result<Artifact, String> {
+        Ok(Artifact::text(
+            translate(code, options, CFlags::default()),
+            ".c",
+        ))
+    }
+}
+
+/// The C code of `code`, the program of `main` unless `flags` say otherwise
+pub fn translate(code: &[Node], options: &CompileOptions, mut flags: CFlags) -> String {
+    let (profile, template) = (flags.profile, flags.template);
+    let source = flags.source.take();
+    let (mem, offset, debug, input) = (
+        options.mem_size,
+        options.offset,
+        options.debug,
+        options.input,
+    );
+    let (getch_name, input_code) = match input.is_empty() {
+        false => (
+            "bf_term_getch",
+            format!(
+                "
+static const bf_cell input[] = {{{}}};
+static unsigned long input_pos = 0;
+
+int bf_getch() {{
+    if (input_pos < sizeof(input) / sizeof(*input)) return input[input_pos++];
+    return bf_term_getch();
+}}
+",
+                input
+                    .iter()
+                    .map(u8::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        ),
+        true => ("bf_getch", String::new()),
+    };
+    // Raw terminal mode only works when stdin is a terminal
+    let (includes, getch_body) = match flags.getchar {
+        _ if flags.freestanding => ("", "    return bf_getc();"),
+        true => ("", "    fflush(stdout);\n    return bf_getchar();"),
+        // Raw mode is only for terminals, piped input is read as it is
+        false => (
+            "\
+#ifdef _WIN32
+#include <conio.h>
+#include <io.h>
+#else
+#include <termios.h>
+#include <unistd.h>
+#endif
+",
+            "\
+    fflush(stdout);
+#ifdef _WIN32
+    if (!_isatty(_fileno(stdin))) return bf_getchar();
+    return _getch();
+#else
+    struct termios old, new;
+    int ch;
+    if (!isatty(0)) return bf_getchar();
+    tcgetattr(0, &old);
+    new = old;
+    new.c_lflag &= ~ICANON;
+    new.c_lflag &= ~ECHO;
+    tcsetattr(0, TCSANOW, &new);
+    ch = bf_getchar();
+    tcsetattr(0, TCSANOW, &old);
+    return ch;
+#endif",
+        ),
+    };
+    // The output is flushed by the reads and at the end, so it can be fully buffered
+    let buffering = match options.unbuffered {
+        _ if flags.freestanding => "",
+        true => "    setvbuf(stdout, NULL, _IONBF, 0);\n",
+        false => "    setvbuf(stdout, NULL, _IOFBF, 1 << 16);\n",
+    };
+    let random = ir::contains(code, |node| *node == Node::Random);
+    // The libraries, the templates and the code without the libc may have no clock to seed `?`
+    // with when they run, so the seed is picked now
+    let seed = match flags.seed {
+        None if flags.freestanding
+            || flags.emit.is_some_and(Emit::is_lib)
+            || template.is_some() =>
+        {
+            Some(crate::driver::random_seed())
+        }
+        seed => seed,
+    };
+    let clock = match random && seed.is_none() {
+        true => "#include <time.h>\n",
+        false => "",
+    };
+    let runtime = match flags.freestanding {
+        true => format!(
+            "\
+#include <stdint.h>
+
+typedef uint{}_t bf_cell;
+
+/* Given by the program this is built into */
+extern void bf_putc(unsigned char c);
+extern int bf_getc(void);
+
+static inline void bf_write(const char* s, unsigned long n) {{
+    while (n--) bf_putc((unsigned char)*s++);
+}}
+
+static inline void bf_memset(void* s, int c, unsigned long n) {{
+    unsigned char* p = (unsigned char*)s;
+    while (n--) *p++ = (unsigned char)c;
+}}
+
+static inline void* bf_memchr(const void* s, int c, unsigned long n) {{
+    const unsigned char* p = (const unsigned char*)s;
+    for (; n--; p++) if (*p == (unsigned char)c) return (void*)p;
+    return 0;
+}}
+
+static inline void* bf_memrchr(const void* s, int c, unsigned long n) {{
+    const unsigned char* p = (const unsigned char*)s + n;
+    while (n--) if (*--p == (unsigned char)c) return (void*)p;
+    return 0;
+}}
+",
+            options.cell_size
+        ),
+        false => format!(
+            "\
+#define _GNU_SOURCE
+#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+#include <string.h>
+{}{}
+typedef uint{}_t bf_cell;
+#define BF_CELL PRIu{}
+
+{}
+/* The end of the input reads as 0, like in the interpreter */
+static int bf_getchar() {{
+    int ch = getchar();
+    return ch == EOF ? 0 : ch;
+}}
+",
+            clock, includes, options.cell_size, options.cell_size, MEMRCHR
+        ),
+    };
+    // The loops that never ran are hinted with a builtin that MSVC does not have
+    let runtime = match profile {
+        Some(_) => {
+            runtime + "\n#ifndef __GNUC__\n#define __builtin_expect(x, expected) (x)\n#endif\n"
+        }
+        None => runtime,
+    };
+    let files = ir::contains(code, |node| {
+        matches!(
+            node,
+            Node::FileOpen { .. } | Node::FileRead | Node::FileWrite | Node::FileStatus
+        )
+    });
+    let runtime = match files {
+        true => runtime + FILES,
+        false => runtime,
+    };
+    let lib = flags.emit.is_some_and(Emit::is_lib);
+    let arduino = flags.emit == Some(Emit::Arduino);
+    // The sketch runs the code from `setup`
+    let main_name = match arduino {
+        true => "bf_main",
+        false => "main",
+    };
+    // The number of cells, as a C expression
+    let len = match flags.grow_tape {
+        _ if lib => "(long)len".to_owned(),
+        true => "(long)mem_len".to_owned(),
+        false => mem.to_string(),
+    };
+    // Nothing to check if the pointer can be shown to stay in the memory
+    let checked = (flags.checked || flags.grow_tape)
+        && !ir::bounds(code).is_some_and(|bounds| {
+            offset as isize + bounds.low >= 0 && offset as isize + bounds.high < mem as isize
+        });
+    let tapes = ir::contains(code, |node| matches!(node, Node::Tape(_))).then_some(flags.tapes);
+    let (tape, tape_code) = match flags.grow_tape {
+        // `mem` is the tape in use, and `bf_ptrs` has the pointers of the others
+        _ if tapes.is_some() => (
+            format!(
+                "\
+    static bf_cell bf_tapes[{}][{}];
+    bf_cell* bf_ptrs[{}];
+    bf_cell* mem = bf_tapes[0];
+    bf_cell* ptr = mem + {};
+    unsigned int bf_tape = 0, bf_i;
+    for (bf_i = 0; bf_i < {}; bf_i++) bf_ptrs[bf_i] = bf_tapes[bf_i] + {};",
+                flags.tapes, mem, flags.tapes, offset, flags.tapes, offset
+            ),
+            String::new(),
+        ),
+        true => (
+            format!(
+                "\
+    mem = calloc(mem_len, sizeof(*mem));
+    if (!mem) bf_out_of_memory();
+    bf_cell* ptr = mem + {};",
+                offset
+            ),
+            format!(
+                "
+static bf_cell* mem;
+static size_t mem_len = {};
+
+static void bf_out_of_memory() {{
+    fflush(stdout);
+    fprintf(stderr, \"out of memory\\n\");
+    exit(1);
+}}
+
+/* Doubles the memory until the cell `at` is in it, and moves `ptr` with it */
+static bf_cell* bf_grow(bf_cell* ptr, long at) {{
+    long pos = ptr - mem;
+    size_t len = mem_len;
+    while (len <= (size_t)at) len *= 2;
+    mem = realloc(mem, len * sizeof(*mem));
+    if (!mem) bf_out_of_memory();
+    memset(mem + mem_len, 0, (len - mem_len) * sizeof(*mem));
+    mem_len = len;
+    return mem + pos;
+}}
+",
+                mem.max(1)
+            ),
+        ),
+        // Static, so that it starts zeroed like the memory of the interpreter, and is not on
+        // the stack
+        false => (
+            format!(
+                "    static bf_cell mem[{}];\n    bf_cell* ptr = mem + {};",
+                mem, offset
+            ),
+            String::new(),
+        ),
+    };
+    let overflow = match flags.grow_tape {
+        true => "ptr = bf_grow(ptr, bf_at)",
+        false => "bf_fail(\"overflow\", node)",
+    };
+    let check_code = match checked {
+        true => format!(
+            "
+static void bf_fail(const char* what, unsigned long node) {{
+    fflush(stdout);
+    fprintf(stderr, \"tape %s at instruction %lu\\n\", what, node);
+    exit(1);
+}}
+
+#define BF_CHECK(reach, node) do {{ \\
+    long bf_at = (long)(ptr - mem) + (reach); \\
+    if (bf_at < 0) bf_fail(\"underflow\", node); \\
+    if (bf_at >= {}) {}; \\
+}} while (0)
+",
+            len, overflow
+        ),
+        false => String::new(),
+    };
+    // The code is in a function of its own that every thread runs, jumping to its fork
+    let forks = ir::contains(code, |node| *node == Node::Fork);
+    let (mut cpp_code, getch) = match lib {
+        true => lib_prelude(options, &flags),
+        false if forks => (
+            format!(
+                "\
+{}{}{}
+int {}() {{
+{}
+}}
+{}{}
+static int bf_code(bf_cell* mem, bf_cell* ptr, int bf_start) {{
+",
+                runtime,
+                tape_code,
+                check_code,
+                getch_name,
+                getch_body,
+                input_code,
+                thread_runtime(mem)
+            ),
+            "bf_getch()",
+        ),
+        false => (
+            format!(
+                "\
+{}{}{}
+int {}() {{
+{}
+}}
+{}
+int {}() {{
+{}
+{}
+",
+                runtime,
+                tape_code,
+                check_code,
+                getch_name,
+                getch_body,
+                input_code,
+                main_name,
+                tape,
+                buffering
+            ),
+            "bf_getch()",
+        ),
+    };
+    // The code goes where the template says, or at the end of the default one
+    let before = match template {
+        Some(template) => template.split("{{BODY}}").next().unwrap_or_default(),
+        None => &cpp_code,
+    };
+    let mut writer = CWriter {
+        c_line: before.matches('\n').count() + 1,
+        code: String::new(),
+        source,
+        offset: Some(0),
+        debug,
+        len,
+        profile,
+        cell_size: options.cell_size,
+        checked,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+        grow: flags.grow_tape,
+        freestanding: flags.freestanding,
+        debug_env: options.debug_env,
+        lib,
+        getch,
+        count: 0,
+        procs: 0,
+        calls: 0,
+        tapes,
+        forks: 0,
+    };
+    let end = writer
+        .source
+        .as_ref()
+        .map_or(0, |source| source.contents.len());
+    if debug {
+        writer.code.push_str("\tunsigned int debug_count = 0;\n");
+        writer.c_line += 1;
+    }
+    if debug && options.debug_env {
+        let env = "const char* bf_env = getenv(\"BF_DEBUG\");";
+        let check = "int bf_debug = bf_env && *bf_env && strcmp(bf_env, \"0\") != 0;";
+        writer.code += &format!("\t{}\n\t{}\n", env, check);
+        writer.c_line += 2;
+    }
+    let procedures = ir::contains(code, |node| matches!(node, Node::Proc { .. } | Node::Call));
+    if procedures {
+        writer.code += &format!(
+            "\tunsigned int bf_procs[256] = {{0}};\n\tstatic unsigned int bf_calls[{}];\n\tunsigned int bf_depth = 0, bf_proc;\n",
+            CALL_DEPTH
+        );
+        writer.c_line += 3;
+    }
+    let storage = ir::contains(code, |node| {
+        matches!(node, Node::Store | Node::Retrieve | Node::Bits(_))
+    });
+    if storage {
+        writer.code.push_str("\tbf_cell bf_storage = 0;\n");
+        writer.c_line += 1;
+    }
+    if random {
+        let seed = match seed {
+            Some(seed) => format!("{}ULL", seed),
+            None => "(uint64_t)time(NULL)".to_owned(),
+        };
+        writer.code += &format!("\tuint64_t bf_rng = {};\n", seed);
+        writer.c_line += 1;
+    }
+    // After the declarations, which the threads need too
+    if forks {
+        writer.code.push_str("\tif (bf_start) goto bf_resume;\n");
+        writer.c_line += 1;
+    }
+    writer.block(code, 1, 0, end);
+    if procedures {
+        writer.dispatch();
+    }
+    if forks {
+        writer.resume();
+    }
+    // Where `@` jumps to end the program
+    if procedures || forks || ir::contains(code, |node| *node == Node::End) {
+        writer.line(0, "bf_end:;");
+    }
+    // The same status as the interpreter exits with
+    let exit_code = match options.exit_cell {
+        Some(ExitCell::Current) => "*ptr".to_owned(),
+        Some(ExitCell::Cell(cell)) => format!("mem[{}]", cell),
+        None => "0".to_owned(),
+    };
+    if let Some(template) = template {
+        return template
+            .replace("{{BODY}}", &writer.code)
+            .replace("{{MEM_SIZE}}", &mem.to_string())
+            .replace("{{OFFSET}}", &offset.to_string())
+            .replace("{{EXIT_CODE}}", &exit_code);
+    }
+    cpp_code += &writer.code;
+    if forks {
+        cpp_code += &format!(
+            "\treturn {};\n}}\n\nint main() {{\n{}\n{}\tbf_done(bf_code(mem, ptr, 0));\n\tbf_join();\n",
+            exit_code, tape, buffering
+        );
+        cpp_code.push_str("\tfflush(stdout);\n\treturn bf_exit;\n}\n");
+        return cpp_code;
+    }
+    if !flags.freestanding && !lib {
+        cpp_code.push_str("\tfflush(stdout);\n");
+    }
+    cpp_code += &format!("\treturn {};\n}}\n", exit_code);
+    if arduino {
+        cpp_code += &arduino_sketch(options);
+    }
+    if flags.emit == Some(Emit::CDylib) {
+        cpp_code += &format!(
+            "
+static void bf_stdout_put(void* data, unsigned char c) {{
+    (void)data;
+    putchar(c);
+}}
+
+/* The end of the input reads as 0, like in the interpreter */
+static int bf_stdin_get(void* data) {{
+    int ch;
+    (void)data;
+    fflush(stdout);
+    ch = getchar();
+    return ch == EOF ? 0 : ch;
+}}
+
+int bf_main(void) {{
+    bf_io io = {{NULL, bf_stdout_put, bf_stdin_get}};
+    bf_cell* tape = calloc({}, sizeof(bf_cell));
+    int code;
+    if (!tape) return -1;
+    code = bf_program(tape, {}, &io);
+    fflush(stdout);
+    free(tape);
+    return code;
+}}
+",
+            mem, mem
+        );
+    }
+    cpp_code
+}
+
+/// The rest of the sketch of `--emit=arduino`, after the C code, which runs it once the serial
+/// port is open
+fn arduino_sketch(options: &CompileOptions) -> String {
+    format!(
+        "
+/* The boards with the size of their SRAM in avr-libc stop here, when the memory cannot fit */
+#if defined(RAMSTART) && defined(RAMEND)
+#if {} > RAMEND - RAMSTART + 1
+#error \"The memory of the Brainfuck program does not fit in the SRAM of this board, pass a smaller -m\"
+#endif
+#endif
+
+#define BF_BAUD_RATE 9600
+
+void bf_putc(unsigned char c) {{
+    Serial.write(c);
+}}
+
+/* The serial port has no end, so this waits for a byte */
+int bf_getc(void) {{
+    while (Serial.available() <= 0) {{
+    }}
+    return Serial.read();
+}}
+
+void setup() {{
+    Serial.begin(BF_BAUD_RATE);
+    while (!Serial) {{
+    }}
+    bf_main();
+    Serial.flush();
+}}
+
+void loop() {{
+}}
+",
+        options.mem_size * options.cell_size as usize / 8
+    )
+}
+
+/// Writes the C code for the nodes
+struct CWriter<'a> {
+    code: String,
+    debug: bool,
+    /// The number of cells, as a C expression
+    len: String,
+    /// Whether the memory grows instead of the checks failing when the pointer goes past it
+    grow: bool,
+    /// Whether to use the `bf_` functions of `--freestanding` instead of the libc
+    freestanding: bool,
+    /// Whether the debug symbols only print when `bf_debug` is set
+    debug_env: bool,
+    /// Whether the output goes through the `bf_io` of `--emit=c-lib`
+    lib: bool,
+    /// The C expression reading a byte for `,`
+    getch: &'a str,
+    profile: Option<&'a [LoopCount]>,
+    /// The bits of a cell, the scans and clears only use the byte functions of `string.h` on 8
+    cell_size: u8,
+    /// Whether to check the pointer before every node that moves it or reaches away from it
+    checked: bool,
+    /// Whether the nodes of copy loops are in an `if (*ptr)`, see [`ir::needs_guard`]
+    guard: bool,
+    /// How many nodes were written, which numbers them like `--emit=ir` does for the checks
+    count: usize,
+    /// Where the nodes came from, to write `#line` before every line
+    source: Option<&'a mut Source>,
+    /// The line of the C file the next line goes on
+    c_line: usize,
+    /// How far the pointer is from where the innermost loop, or the code, started, if it is
+    /// always the same
+    offset: Option<isize>,
+    /// How many procedures of pbrain were written, which are `bf_proc_N` from 1
+    procs: usize,
+    /// How many calls were written, which return to `bf_return_N` from 1
+    calls: usize,
+    /// How many tapes there are, if the code goes to another one
+    tapes: Option<usize>,
+    /// How many forks were written, which threads start from at `bf_fork_N` from 1
+    forks: usize,
+}
+
+/// Where the code came from in the source, for `--line-directives` and `--annotate`
+pub struct Source {
+    /// The source file, as a C string literal
+    file: String,
+    /// The source itself
+    contents: String,
+    debug: bool,
+    /// Whether to write `#line` before every line
+    directives: bool,
+    /// Whether to write a comment before every piece of the source
+    annotate: bool,
+    /// Where every loop is, by id
+    loops: Vec<LoopLines>,
+    /// The line of the source the next line of C comes from
+    line: usize,
+    /// Every loop with where it is in the source and in the C file, one per line
+    pub map: String,
+}
+
+/// Where a loop is in the source, as lines and columns from 1
+#[derive(Clone, Copy)]
+struct LoopLines {
+    span: ast::Span,
+    open: (usize, usize),
+    close: (usize, usize),
+    /// The line of the first instruction of the body
+    body: usize,
+    /// The line of the first instruction after the loop
+    after: usize,
+}
+
+impl Source {
+    /// Where the code of `contents` in `file` came from, when the source starts on the line
+    /// after `first_line`
+    pub fn new(
+        file: &str,
+        contents: &str,
+        config: &Config,
+        first_line: usize,
+        directives: bool,
+        annotate: bool,
+    ) -> Result<Self, BfError> {
+        /// The offsets of the `[`, the `]`, the first instruction of the body and the first one
+        /// after every loop in `ast`, where `end` is the offset after the last node
+        fn walk(ast: &[ast::Ast], end: usize, loops: &mut Vec<[usize; 4]>) {
+            for (i, node) in ast.iter().enumerate() {
+                if let ast::Ast::Loop { span, body, .. } = node {
+                    // The ids are the order of the `[`, which is the order they are found in
+                    let body_start = body.first().map_or(span.end, |node| node.span().start);
+                    let after = ast.get(i + 1).map_or(end, |node| node.span().start);
+                    loops.push([span.start, span.end, body_start, after]);
+                    walk(body, span.end, loops);
+                }
+            }
+        }
+        let tree = ast::parse(contents, config)?;
+        let mut offsets = Vec::new();
+        walk(&tree, contents.len(), &mut offsets);
+        let position = |at| {
+            let (line, column) = ast::position(contents, at);
+            (first_line + line, column)
+        };
+        Ok(Self {
+            file: c_string(file.as_bytes()),
+            contents: contents.to_owned(),
+            debug: config.debug,
+            directives,
+            annotate,
+            loops: offsets
+                .iter()
+                .map(|&[open, close, body, after]| LoopLines {
+                    span: ast::Span {
+                        start: open,
+                        end: close,
+                    },
+                    open: position(open),
+                    close: position(close),
+                    body: position(body).0,
+                    after: position(after).0,
+                })
+                .collect(),
+            line: tree
+                .first()
+                .map_or(first_line + 1, |node| position(node.span().start).0),
+            map: String::new(),
+        })
+    }
+
+    /// The instructions of the source from the byte offset `start` up to `end`, shortened to
+    /// fit in a comment
+    fn snippet(&self, start: usize, end: usize) -> String {
+        let mut code = self.contents[start..end]
+            .chars()
+            .filter(|&ch| "<>+-.,".contains(ch) || (self.debug && "#|".contains(ch)));
+        let snippet: String = code.by_ref().take(SNIPPET_LEN).collect();
+        match code.next() {
+            Some(_) => snippet + "...",
+            None => snippet,
+        }
+    }
+}
+
+impl CWriter<'_> {
+    /// Writes a line indented by `depth` tabs, after the `#line` of where it came from
+    fn line(&mut self, depth: usize, text: &str) {
+        if let Some(source) = self.source.as_deref().filter(|source| source.directives) {
+            self.code += &format!("#line {} {}\n", source.line, source.file);
+            self.c_line += 1;
+        }
+        self.code += &format!("{}{}\n", "\t".repeat(depth), text);
+        self.c_line += 1;
+    }
+
+    /// Writes the `--annotate` comment for the source from the byte offset `start` up to the
+    /// first loop left in `nodes`, or up to `end` if there are none
+    fn annotate(&mut self, nodes: &[Node], depth: usize, start: usize, end: usize) {
+        let Some(source) = self.source.as_deref().filter(|source| source.annotate) else {
+            return;
+        };
+        let next = nodes.iter().find_map(|node| match *node {
+            Node::Loop { id, .. } => Some(source.loops[id].span.start),
+            _ => None,
+        });
+        let snippet = source.snippet(start, next.unwrap_or(end));
+        if snippet.is_empty() {
+            return;
+        }
+        let offset = match self.offset {
+            Some(offset) => format!("offset {:+}", offset),
+            None => "offset unknown".to_owned(),
+        };
+        if !self.code.is_empty() && !self.code.ends_with("\n\n") {
+            self.code.push('\n');
+            self.c_line += 1;
+        }
+        let comment = format!("/* {} | depth {}, {} */", snippet, depth - 1, offset);
+        self.line(depth, &comment);
+    }
+
+    /// Writes `nodes` indented by `depth` tabs, where they came from the source between the
+    /// byte offsets `start` and `end`
+    fn block(&mut self, nodes: &[Node], depth: usize, start: usize, end: usize) {
+        self.annotate(nodes, depth, start, end);
+        let mut rest = nodes;
+        while let Some(node) = rest.first() {
+            // Outputs of known values that follow each other are written all at once, and so
+            // are the clears of cells next to each other
+            let bytes: Vec<u8> = rest
+                .iter()
+                .map_while(|node| match *node {
+                    Node::Print(value) => Some(value),
+                    _ => None,
+                })
+                .collect();
+            let (cells, len) = clears(rest);
+            if bytes.len() > 1 {
+                let text = match self.freestanding {
+                    _ if self.lib => {
+                        format!("bf_write(io, {}, {});", c_string(&bytes), bytes.len())
+                    }
+                    true => format!("bf_write({}, {});", c_string(&bytes), bytes.len()),
+                    false => format!("fwrite({}, 1, {}, stdout);", c_string(&bytes), bytes.len()),
+                };
+                self.line(depth, &text);
+                self.count += bytes.len();
+                rest = &rest[bytes.len()..];
+            } else if cells > 1 {
+                let bytes = match self.cell_size {
+                    8 => cells.to_string(),
+                    _ => format!("{} * sizeof(*ptr)", cells),
+                };
+                self.count += 1;
+                self.check(cells as isize - 1, depth);
+                self.count += len - 1;
+                let memset = match self.freestanding {
+                    true => "bf_memset",
+                    false => "memset",
+                };
+                self.line(depth, &format!("{}(ptr, 0, {});", memset, bytes));
+                self.line(depth, &format!("{};", step(cells as isize - 1)));
+                self.offset = self.offset.map(|offset| offset + cells as isize - 1);
+                rest = &rest[len..];
+            } else if self.guard && matches!(node, Node::MulAdd { .. }) {
+                let len = rest
+                    .iter()
+                    .take_while(|node| matches!(node, Node::MulAdd { .. }))
+                    .count();
+                self.line(depth, "if (*ptr) {");
+                for node in &rest[..len] {
+                    self.node(node, depth + 1);
+                }
+                self.line(depth, "}");
+                rest = &rest[len..];
+            } else {
+                self.node(node, depth);
+                rest = &rest[1..];
+                if let (Node::Loop { id, .. }, Some(source)) = (node, &self.source) {
+                    let after = source.loops[*id].span.end + 1;
+                    self.annotate(rest, depth, after, end);
+                }
+            }
+        }
+    }
+
+    /// Writes the check that the cell `reach` away from the pointer is in the memory, for the
+    /// last node counted
+    fn check(&mut self, reach: isize, depth: usize) {
+        if self.checked && reach != 0 {
+            self.line(depth, &format!("BF_CHECK({}, {});", reach, self.count));
+        }
+    }
+
+    /// Like [`CWriter::check`], but only when the current cell is not 0
+    fn check_nonzero(&mut self, reach: isize, depth: usize) {
+        if self.checked && reach != 0 {
+            self.line(
+                depth,
+                &format!("if (*ptr) BF_CHECK({}, {});", reach, self.count),
+            );
+        }
+    }
+
+    /// Writes a single node indented by `depth` tabs
+    fn node(&mut self, node: &Node, depth: usize) {
+        self.count += 1;
+        match *node {
+            Node::Move(amount) => {
+                self.offset = self.offset.map(|offset| offset + amount);
+                self.check(amount, depth)
+            }
+            Node::Scan(_) => self.offset = None,
+            Node::MulAdd { offset, .. } if self.guard => self.check(offset, depth),
+            // It only reaches the other cell when the loop it came from would have run
+            Node::MulAdd { offset, .. } => self.check_nonzero(offset, depth),
+            Node::Add { offset, .. } | Node::Output { offset } | Node::Input { offset } => {
+                self.check(offset, depth)
+            }
+            _ => (),
+        }
+        match *node {
+            Node::Proc { ref body } => return self.procedure(body, depth),
+            Node::Call => return self.call(depth),
+            Node::Fork => return self.fork(depth),
+            _ => (),
+        }
+        let count = self.count;
+        let (putchar, memchr) = match self.freestanding {
+            true => ("bf_putc", "bf_memchr"),
+            false => ("putchar", "memchr"),
+        };
+        let line = match *node {
+                Node::Move(amount) => format!("{};", step(amount)),
+                Node::Add { offset, amount } if (amount as i8) < 0 => {
+                    format!("{} -= {};", cell(offset), (amount as i8).unsigned_abs())
+                }
+                Node::Add { offset, amount } => format!("{} += {};", cell(offset), amount),
+                Node::SetZero => "*ptr = 0;".to_owned(),
+                Node::Scan(1) if self.cell_size == 8 && self.grow => format!("ptr = (bf_cell*)memchr(ptr, 0, {} - (ptr - mem));if (!ptr) ptr = bf_grow(mem + mem_len - 1, mem_len) + 1;", self.len),
+                Node::Scan(1) if self.cell_size == 8 && self.checked => format!("ptr = (bf_cell*)memchr(ptr, 0, {} - (ptr - mem));if (!ptr) bf_fail(\"overflow\", {});", self.len, count),
+                Node::Scan(-1) if self.cell_size == 8 && self.checked => format!("ptr = (bf_cell*)bf_memrchr(mem, 0, ptr - mem + 1);if (!ptr) bf_fail(\"underflow\", {});", count),
+                Node::Scan(1) if self.cell_size == 8 => format!("ptr = (bf_cell*){}(ptr, 0, {} - (ptr - mem));", memchr, self.len),
+                Node::Scan(-1) if self.cell_size == 8 => "ptr = (bf_cell*)bf_memrchr(mem, 0, ptr - mem + 1);".to_owned(),
+                Node::Scan(amount) if self.checked => format!("while (*ptr) {{BF_CHECK({}, {});{};}}", amount, count, step(amount)),
+                Node::Scan(amount) => format!("while (*ptr) {};", step(amount)),
+                Node::MulAdd { offset, factor: 1 } => format!("{} += *ptr;", cell(offset)),
+                Node::MulAdd { offset, factor: 255 } => format!("{} -= *ptr;", cell(offset)),
+                Node::MulAdd { offset, factor } if (factor as i8) < 0 => {
+                    format!("{} -= *ptr * {};", cell(offset), (factor as i8).unsigned_abs())
+                }
+                Node::MulAdd { offset, factor } => {
+                    format!("{} += *ptr * {};", cell(offset), factor)
+                }
+                Node::Output { offset } if self.lib => format!("io->put(io->data, {});", cell(offset)),
+                Node::Output { offset } => format!("{}({});", putchar, cell(offset)),
+                Node::Input { offset } => format!("{} = {};", cell(offset), self.getch),
+                Node::Print(value) if self.lib => format!("io->put(io->data, {});", value),
+                Node::Print(value) => format!("{}({});", putchar, value),
+                Node::Loop { id, ref body } => {
+                    if let Some(source) = self.source.as_deref_mut() {
+                        source.line = source.loops[id].open.0;
+                    }
+                    let count = self.profile.and_then(|profile| profile.get(id));
+                    match count {
+                        Some(count) if count.iterations >= count.entries.max(1) * HOT_LOOP => {
+                            self.line(depth, "#pragma GCC unroll 4");
+                            self.line(depth, "while (*ptr) {");
+                        }
+                        Some(count) if count.entries > 0 && count.iterations == 0 => {
+                            self.line(depth, "while (__builtin_expect(*ptr, 0)) {");
+                        }
+                        _ => self.line(depth, "while (*ptr) {"),
+                    }
+                    let start = self.c_line - 1;
+                    if let Some(source) = self.source.as_deref_mut() {
+                        source.line = source.loops[id].body;
+                    }
+                    let span = self.source.as_ref().map(|source| source.loops[id].span);
+                    let outer = self.offset.replace(0);
+                    self.block(
+                        body,
+                        depth + 1,
+                        span.map_or(0, |span| span.start + 1),
+                        span.map_or(0, |span| span.end),
+                    );
+                    // The pointer is only where it was if the loop always ends where it started
+                    self.offset = outer.filter(|_| ir::analyze(body).balanced());
+                    if let Some(source) = self.source.as_deref_mut() {
+                        source.line = source.loops[id].close.0;
+                    }
+                    self.line(depth, "}");
+                    if let Some(source) = self.source.as_deref_mut() {
+                        let LoopLines { open, close, .. } = source.loops[id];
+                        source.line = source.loops[id].after;
+                        source.map += &format!(
+                            "loop {} {}:{}-{}:{} -> {}-{}\n",
+                            id, open.0, open.1, close.0, close.1, start, self.c_line - 1
+                        );
+                    }
+                    return;
+                }
+                Node::DebugCell if self.debug && self.tapes.is_some() => {
+                    "debug_count += 1;printf(\"\\ndebug flag %d : %c, %\" BF_CELL \", %ld on tape %u\\n\", debug_count, (unsigned char)*ptr, *ptr, ptr-mem, bf_tape);".to_owned()
+                }
+                Node::DebugCell if self.debug => {
+                    "debug_count += 1;printf(\"\\ndebug flag %d : %c, %\" BF_CELL \", %ld\\n\", debug_count, (unsigned char)*ptr, *ptr, ptr-mem);".to_owned()
+                }
+                Node::DebugMem if self.debug => {
+                    let end = match self.tapes {
+                        Some(_) => "printf(\"on tape %u\\n\", bf_tape);",
+                        None => "printf(\"\\n\");",
+                    };
+                    format!("printf(\"\\n\");for (bf_cell* i = ptr - 15; i < ptr + 15; i++) {{if (i < mem || i >= mem + {}) continue; if (i == ptr) printf(\"|%\" BF_CELL \"| \", *i); else printf(\"%\" BF_CELL \" \", *i); }}{}", self.len, end)
+                }
+                Node::Store => "bf_storage = *ptr;".to_owned(),
+                Node::Retrieve => "*ptr = bf_storage;".to_owned(),
+                Node::Bits(Bits::ShiftLeft) => "*ptr <<= 1;".to_owned(),
+                Node::Bits(Bits::ShiftRight) => "*ptr >>= 1;".to_owned(),
+                Node::Bits(Bits::Not) => "*ptr = ~*ptr;".to_owned(),
+                Node::Bits(Bits::Xor) => "*ptr ^= bf_storage;".to_owned(),
+                Node::Bits(Bits::And) => "*ptr &= bf_storage;".to_owned(),
+                Node::Bits(Bits::Or) => "*ptr |= bf_storage;".to_owned(),
+                Node::End => "goto bf_end;".to_owned(),
+                Node::FileOpen { write } => {
+                    format!("*ptr = bf_open(ptr, {} - (ptr - mem), {});", self.len, write as u8)
+                }
+                Node::FileRead => "*ptr = bf_read_file();".to_owned(),
+                Node::FileWrite => "if (bf_files[1]) putc((unsigned char)*ptr, bf_files[1]);".to_owned(),
+                Node::FileStatus => "*ptr = bf_file_status;".to_owned(),
+                // SplitMix64, like in the interpreter
+                Node::Random => "{uint64_t bf_z = bf_rng += 0x9e3779b97f4a7c15ULL; bf_z = (bf_z ^ (bf_z >> 30)) * 0xbf58476d1ce4e5b9ULL; bf_z = (bf_z ^ (bf_z >> 27)) * 0x94d049bb133111ebULL; *ptr = (bf_cell)((bf_z ^ (bf_z >> 31)) >> 56);}".to_owned(),
+                Node::Tape(amount) => {
+                    let tapes = self.tapes.unwrap_or(1);
+                    let next = amount.rem_euclid(tapes as isize);
+                    self.offset = None;
+                    format!("bf_ptrs[bf_tape] = ptr;bf_tape = (bf_tape + {}) % {};mem = bf_tapes[bf_tape];ptr = bf_ptrs[bf_tape];", next, tapes)
+                }
+                Node::DebugCell
+                | Node::DebugMem
+                | Node::Proc { .. }
+                | Node::Call
+                | Node::Fork => return,
+            };
+        let line = match *node {
+            Node::DebugCell | Node::DebugMem if self.debug_env => {
+                format!("if (bf_debug) {{{}}}", line)
+            }
+            _ => line,
+        };
+        self.line(depth, &line);
+    }
+}
+
+impl CWriter<'_> {
+    /// Writes the procedure of pbrain with `body`, which is only run by jumping into it. The
+    /// procedures and the calls are labels in the function, so that they share its pointer
+    fn procedure(&mut self, body: &[Node], depth: usize) {
+        self.procs += 1;
+        let proc = self.procs;
+        self.line(depth, &format!("bf_procs[(unsigned char)*ptr] = {};", proc));
+        self.line(depth, "if (0) {");
+        self.line(depth, &format!("bf_proc_{}:", proc));
+        // It runs wherever it is called from
+        let outer = self.offset.take();
+        self.block(body, depth + 1, 0, 0);
+        self.offset = outer;
+        self.line(depth + 1, "goto bf_return;");
+        self.line(depth, "}");
+    }
+
+    /// Writes the call of the procedure of the current cell, which returns to after it
+    fn call(&mut self, depth: usize) {
+        self.calls += 1;
+        let overflow = self.fail("Call stack overflow\\n", "");
+        self.line(
+            depth,
+            &format!("if (bf_depth == {}) {}", CALL_DEPTH, overflow),
+        );
+        self.line(depth, &format!("bf_calls[bf_depth++] = {};", self.calls));
+        self.line(depth, "bf_proc = bf_procs[(unsigned char)*ptr];");
+        self.line(depth, "goto bf_call;");
+        self.line(depth, &format!("bf_return_{}:;", self.calls));
+        self.offset = None;
+    }
+
+    /// Writes the `Y` of Brainfork, which starts a thread that goes on from the label after it
+    fn fork(&mut self, depth: usize) {
+        self.forks += 1;
+        self.line(depth, &format!("bf_fork(mem, ptr, {});", self.forks));
+        self.line(depth, "*ptr = 0;");
+        self.line(depth, &format!("bf_fork_{}:;", self.forks));
+        // The thread is on the next cell
+        self.offset = None;
+    }
+
+    /// Writes where the threads jump to the fork they start from, after the rest of the code
+    fn resume(&mut self) {
+        self.line(1, "goto bf_end;");
+        self.line(0, "bf_resume:");
+        self.line(1, "switch (bf_start) {");
+        for fork in 1..=self.forks {
+            self.line(1, &format!("case {}: goto bf_fork_{};", fork, fork));
+        }
+        self.line(1, "}");
+    }
+
+    /// Writes where the calls and the returns jump to the label they go to, after the rest of
+    /// the code and before `bf_end`
+    fn dispatch(&mut self) {
+        self.line(1, "goto bf_end;");
+        self.line(0, "bf_call:");
+        self.line(1, "switch (bf_proc) {");
+        for proc in 1..=self.procs {
+            self.line(1, &format!("case {}: goto bf_proc_{};", proc, proc));
+        }
+        let undefined = self.fail("No procedure for %d\\n", ", (unsigned char)*ptr");
+        self.line(1, &format!("default: {}", undefined));
+        self.line(1, "}");
+        self.line(0, "bf_return:");
+        self.line(1, "switch (bf_calls[--bf_depth]) {");
+        for call in 1..=self.calls {
+            self.line(1, &format!("case {}: goto bf_return_{};", call, call));
+        }
+        self.line(1, "}");
+    }
+
+    /// The statement ending the program with the error `format` of `fprintf`, followed by its
+    /// `args`. Without the libc, the function returns -1 instead
+    fn fail(&self, format: &str, args: &str) -> String {
+        match self.freestanding || self.lib {
+            true => "return -1;".to_owned(),
+            false => format!(
+                "{{fflush(stdout); fprintf(stderr, \"{}\"{}); exit(1);}}",
+                format, args
+            ),
+        }
+    }
+}
+
+/// How many cells next to each other the clears at the start of `nodes` set to 0, with how many
+/// nodes they take, ending on the last cell
+fn clears(nodes: &[Node]) -> (usize, usize) {
+    let (mut cells, mut len) = (0, 0);
+    while nodes.get(len) == Some(&Node::SetZero) {
+        cells += 1;
+        len += 1;
+        if nodes.get(len) != Some(&Node::Move(1)) || nodes.get(len + 1) != Some(&Node::SetZero) {
+            break;
+        }
+        len += 1;
+    }
+    (cells, len)
+}
+
+/// A C string literal holding `bytes`
+fn c_string(bytes: &[u8]) -> String {
+    let mut string = "\"".to_owned();
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => string += &format!("\\{}", byte as char),
+            // Octal escapes, so that a digit after them is not taken as part of them, and `?`
+            // so that it cannot make a trigraph
+            b' '..=b'~' if byte != b'?' => string.push(byte as char),
+            _ => string += &format!("\\{:03o}", byte),
+        }
+    }
+    string + "\""
+}
+
+/// The C statement moving the pointer by `amount`, without its semicolon
+fn step(amount: isize) -> String {
+    match amount {
+        1 => "ptr++".to_owned(),
+        -1 => "ptr--".to_owned(),
+        _ if amount < 0 => format!("ptr -= {}", amount.unsigned_abs()),
+        _ => format!("ptr += {}", amount),
+    }
+}
+
+/// The C expression for the cell `offset` away from the pointer
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "*ptr".to_owned(),
+        _ => format!("ptr[{}]", offset),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options() -> CompileOptions<'static> {
+        CompileOptions {
+            mem_size: 30000,
+            offset: 0,
+            cell_size: 8,
+            input: &[],
+            exit_cell: None,
+            debug: false,
+            debug_env: false,
+            unbuffered: false,
+        }
+    }
+
+    /// The C code of `contents`, optimized like the compiled programs are
+    fn c(contents: &str, options: &CompileOptions, flags: CFlags) -> String {
+        let config = Config {
+            debug: options.debug,
+            ..Config::default()
+        };
+        let code = ir::optimize(ir::parse(contents, &config).unwrap(), &config);
+        translate(&code, options, flags)
+    }
+
+    #[test]
+    fn runs_are_single_statements() {
+        let code = c(",+++++>>>-----<.", &options(), CFlags::default());
+        assert!(code.contains("*ptr += 5;"));
+        assert!(code.contains("ptr[3] -= 5;"));
+        assert!(code.contains("ptr += 2;"));
+    }
+
+    #[test]
+    fn clear_and_copy_loops_are_assignments() {
+        let code = c(",[->+>++<<]>.>[-]>[-]>[-]<.", &options(), CFlags::default());
+        assert!(code.contains("ptr[1] += *ptr;"));
+        assert!(code.contains("ptr[2] += *ptr * 2;"));
+        assert!(code.contains("*ptr = 0;"));
+        assert!(code.contains("memset(ptr, 0, 3);"));
+        assert!(!code.contains("while (*ptr)"));
+    }
+
+    #[test]
+    fn windows_has_its_own_input_and_memrchr() {
+        let code = c(",[<]", &options(), CFlags::default());
+        assert!(code.contains("#ifdef _WIN32\n#include <conio.h>"));
+        assert!(code.contains("return _getch();"));
+        // glibc is the only one with memrchr
+        assert!(code.contains("#ifdef __GLIBC__\n#define bf_memrchr memrchr"));
+        assert!(code.contains("ptr = (bf_cell*)bf_memrchr(mem, 0, ptr - mem + 1);"));
+        // conio.h has a getch of its own
+        assert!(code.contains("int bf_getch() {"));
+    }
+
+    #[test]
+    fn getchar_reads_without_the_terminal() {
+        let flags = CFlags {
+            getchar: true,
+            ..CFlags::default()
+        };
+        let code = c(",.", &options(), flags);
+        assert!(code.contains("    fflush(stdout);\n    return bf_getchar();"));
+        assert!(!code.contains("termios"));
+        assert!(!code.contains("conio.h"));
+    }
+
+    #[test]
+    fn output_is_buffered_until_it_is_flushed() {
+        let code = c("+.,.", &options(), CFlags::default());
+        assert!(code.contains("setvbuf(stdout, NULL, _IOFBF, 1 << 16);"));
+        assert!(code.contains("fflush(stdout);\n#ifdef _WIN32"));
+        assert!(code.ends_with("\tfflush(stdout);\n\treturn 0;\n}\n"));
+        let unbuffered = CompileOptions {
+            unbuffered: true,
+            ..options()
+        };
+        let code = c("+.,.", &unbuffered, CFlags::default());
+        assert!(code.contains("setvbuf(stdout, NULL, _IONBF, 0);"));
+    }
+
+    #[test]
+    fn cells_are_as_wide_as_asked() {
+        let wide = CompileOptions {
+            cell_size: 16,
+            ..options()
+        };
+        let code = c(",[>]>[-]>[-].", &wide, CFlags::default());
+        assert!(code.contains("typedef uint16_t bf_cell;\n#define BF_CELL PRIu16"));
+        // memchr and memset only work on bytes
+        assert!(code.contains("while (*ptr) ptr++;"));
+        assert!(code.contains("memset(ptr, 0, 2 * sizeof(*ptr));"));
+    }
+
+    #[test]
+    fn checked_code_checks_the_moves_it_cannot_prove() {
+        let checked = || CFlags {
+            checked: true,
+            ..CFlags::default()
+        };
+        let code = c(",[>,]<<.", &options(), checked());
+        assert!(code.contains("#define BF_CHECK(reach, node)"));
+        assert!(code.contains("if (bf_at >= 30000) bf_fail(\"overflow\", node);"));
+        assert!(code.contains("BF_CHECK(1, "));
+        assert!(code.contains("BF_CHECK(-2, "));
+        // Nothing is left to check when the pointer stays in the memory
+        let code = c(",>,[-<+>]<.", &options(), checked());
+        assert!(!code.contains("BF_CHECK"));
+    }
+
+    #[test]
+    fn grown_tape_is_on_the_heap() {
+        let flags = CFlags {
+            grow_tape: true,
+            ..CFlags::default()
+        };
+        let code = c(",[>,]<.", &options(), flags);
+        assert!(code.contains("static size_t mem_len = 30000;"));
+        assert!(code.contains("mem = calloc(mem_len, sizeof(*mem));"));
+        assert!(code.contains("if (bf_at >= (long)mem_len) ptr = bf_grow(ptr, bf_at);"));
+        assert!(!code.contains("static bf_cell mem["));
+    }
+
+    #[test]
+    fn memory_is_static_and_starts_at_the_offset() {
+        let offset = CompileOptions {
+            mem_size: 100,
+            offset: 10,
+            ..options()
+        };
+        let code = c(",.", &offset, CFlags::default());
+        // Zeroed like the memory of the interpreter, and not on the stack
+        assert!(code.contains("    static bf_cell mem[100];\n    bf_cell* ptr = mem + 10;"));
+        assert!(!code.contains("memset(mem"));
+    }
+
+    /// Where the code of `contents` in `prog.bf` came from, after a shebang line
+    fn source(contents: &str, directives: bool, annotate: bool) -> Source {
+        Source::new(
+            "prog.bf",
+            contents,
+            &Config::default(),
+            1,
+            directives,
+            annotate,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn lines_point_at_the_source() {
+        let contents = ",\n[\n>,\n]\n.";
+        let mut source = source(contents, true, false);
+        let flags = CFlags {
+            source: Some(&mut source),
+            ..CFlags::default()
+        };
+        let code = c(contents, &options(), flags);
+        assert!(code.contains("#line 2 \"prog.bf\"\n\t*ptr = bf_getch();"));
+        assert!(code.contains("#line 3 \"prog.bf\"\n\twhile (*ptr) {"));
+        assert!(code.contains("#line 5 \"prog.bf\"\n\t}"));
+        // The loop goes from the `[` to the `]`, and to the lines of the C file around its body
+        let lines: Vec<&str> = code.lines().collect();
+        let map = source.map.strip_prefix("loop 0 3:1-5:1 -> ").unwrap();
+        let (start, end) = map.trim_end().split_once('-').unwrap();
+        let (start, end): (usize, usize) = (start.parse().unwrap(), end.parse().unwrap());
+        assert_eq!(lines[start - 1], "\twhile (*ptr) {");
+        assert_eq!(lines[end - 1], "\t}");
+    }
+
+    #[test]
+    fn annotations_show_the_source() {
+        let contents = ",>+<[>,]++.";
+        let mut source = source(contents, false, true);
+        let flags = CFlags {
+            source: Some(&mut source),
+            ..CFlags::default()
+        };
+        let code = c(contents, &options(), flags);
+        assert!(code.contains("\t/* ,>+< | depth 0, offset +0 */\n"));
+        assert!(code.contains("\t\t/* >, | depth 1, offset +0 */\n"));
+        // The loop does not end where it started
+        assert!(code.contains("\t/* ++. | depth 0, offset unknown */\n"));
+    }
+
+    #[test]
+    fn template_gets_the_code() {
+        let template = "int main() {\n\tbf_cell mem[{{MEM_SIZE}}];\n\tbf_cell* ptr = mem + {{OFFSET}};\n{{BODY}}\treturn {{EXIT_CODE}};\n}\n";
+        let options = CompileOptions {
+            exit_cell: Some(ExitCell::Current),
+            ..options()
+        };
+        let flags = CFlags {
+            template: Some(template),
+            ..CFlags::default()
+        };
+        let code = c(",+.", &options, flags);
+        assert_eq!(
+            code,
+            "int main() {\n\tbf_cell mem[30000];\n\tbf_cell* ptr = mem + 0;\n\t*ptr = bf_getch();\n\t*ptr += 1;\n\tputchar(*ptr);\n\treturn *ptr;\n}\n"
+        );
+    }
+
+    #[test]
+    fn freestanding_code_needs_no_libc() {
+        let flags = CFlags {
+            freestanding: true,
+            ..CFlags::default()
+        };
+        let code = c(",[>],.>[-]>[-]<..", &options(), flags);
+        assert!(code.contains("extern void bf_putc(unsigned char c);"));
+        assert!(code.contains("    return bf_getc();"));
+        assert!(code.contains("bf_memchr(ptr, 0, "));
+        assert!(code.contains("bf_memset(ptr, 0, 2);"));
+        assert!(code.contains("bf_putc(*ptr);"));
+        assert!(code.contains("bf_write(\"\\000\\000\", 2);"));
+        for libc in [
+            "stdio.h", "stdlib.h", "string.h", "putchar", "fflush", "setvbuf",
+        ] {
+            assert!(!code.contains(libc), "{}", libc);
+        }
+    }
+
+    #[test]
+    fn exit_code_comes_from_the_cell() {
+        let exit = |exit_cell| {
+            let options = CompileOptions {
+                exit_cell,
+                ..options()
+            };
+            c(",>,", &options, CFlags::default())
+        };
+        assert!(exit(None).ends_with("\treturn 0;\n}\n"));
+        assert!(exit(Some(ExitCell::Current)).ends_with("\treturn *ptr;\n}\n"));
+        assert!(exit(Some(ExitCell::Cell(3))).ends_with("\treturn mem[3];\n}\n"));
+    }
+
+    #[test]
+    fn piped_input_is_read_as_it_is() {
+        let code = c(",.", &options(), CFlags::default());
+        assert!(code.contains("    if (!isatty(0)) return bf_getchar();\n    tcgetattr(0, &old);"));
+        assert!(code.contains(
+            "    if (!_isatty(_fileno(stdin))) return bf_getchar();\n    return _getch();"
+        ));
+        // The end of the input reads as 0
+        assert!(code.contains("return ch == EOF ? 0 : ch;"));
+    }
+
+    #[test]
+    fn debug_symbols_wait_for_bf_debug() {
+        let debug = |debug_env| {
+            let options = CompileOptions {
+                debug: true,
+                debug_env,
+                ..options()
+            };
+            c("+#>|", &options, CFlags::default())
+        };
+        let code = debug(true);
+        assert!(code.contains("\tconst char* bf_env = getenv(\"BF_DEBUG\");\n"));
+        assert!(code.contains("\tif (bf_debug) {debug_count += 1;printf("));
+        assert!(code.contains("\tif (bf_debug) {printf(\"\\n\");for ("));
+        let code = debug(false);
+        assert!(!code.contains("BF_DEBUG"));
+        assert!(code.contains("\tdebug_count += 1;printf("));
+    }
+
+    #[test]
+    fn library_is_a_function_with_a_header() {
+        let lib = |emit| CFlags {
+            emit: Some(emit),
+            output: "build/prog",
+            ..CFlags::default()
+        };
+        let code = c(",+.", &options(), lib(Emit::CLib));
+        assert!(code.contains("#include \"prog.h\""));
+        assert!(code.contains("int bf_program(bf_cell* tape, size_t len, bf_io* io) {"));
+        assert!(code.contains("    if (len < 30000) return -1;"));
+        assert!(code.contains("\t*ptr = io->get(io->data);"));
+        assert!(code.contains("\tio->put(io->data, *ptr);"));
+        assert!(!code.contains("int main"));
+        let header = lib_header(&options(), &lib(Emit::CLib));
+        assert!(header.contains("typedef uint8_t bf_cell;"));
+        assert!(header.contains("BF_EXPORT int bf_program(bf_cell* tape, size_t len, bf_io* io);"));
+        assert!(!header.contains("bf_main"));
+        // The shared library can also run on stdin and stdout
+        let code = c(",+.", &options(), lib(Emit::CDylib));
+        assert!(code.contains("int bf_main(void) {"));
+        let header = lib_header(&options(), &lib(Emit::CDylib));
+        assert!(header.contains("BF_EXPORT int bf_main(void);"));
+    }
+
+    #[test]
+    fn input_is_built_in() {
+        let options = CompileOptions {
+            input: b"hi",
+            ..options()
+        };
+        let code = c(",.", &options, CFlags::default());
+        assert!(code.contains("static const bf_cell input[] = {104, 105};"));
+        // Then the terminal
+        assert!(code.contains("    return bf_term_getch();"));
+        let flags = CFlags {
+            emit: Some(Emit::CLib),
+            ..CFlags::default()
+        };
+        let code = c(",.", &options, flags);
+        assert!(code.contains("\t*ptr = bf_getch(io, &input_pos);"));
+        assert!(code.contains("    return io->get(io->data);"));
+    }
+}
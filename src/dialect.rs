@@ -0,0 +1,211 @@
+//! The dialects of Brainfuck: the ones with instructions of their own, for `--dialect`, and the
+//! ones that only write the instructions of Brainfuck with other tokens, for `--dialect-map`.
+//!
+//! A map has a line for every token, with the instruction first and the token after it, like
+//! `+ pipi` for Pikalang or `> Blub. Blub?` for Blub. The words of a token can be apart by any
+//! whitespace in the source. Everything that is not a token is a comment, like in Brainfuck
+
+use alloc::{format, string::String, vec::Vec};
+
+/// The instructions the source is read with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Dialect {
+    /// Only the instructions of Brainfuck
+    #[default]
+    Brainfuck,
+    /// pbrain, where `(...)` makes its body the procedure of the value of the current cell, and
+    /// `:` calls the procedure of the value of the current cell
+    Pbrain,
+    /// Extended Brainfuck Type I, where `@` ends the program, `$` stores the current cell and `!`
+    /// sets it to the stored one, `{` and `}` shift it by a bit, `~` flips its bits, and `^`, `&`
+    /// and `|` combine it with the stored one
+    Extended1,
+    /// Several tapes with a pointer each, where `{` and `}` go to the previous and the next
+    /// tape, wrapping around
+    Multitape,
+    /// Brainfork, where `Y` forks the program: the current cell of the parent becomes 0, and
+    /// the child goes on with a copy of the tape, one cell to the right, which becomes 1
+    Brainfork,
+    /// `?` sets the current cell to a random byte
+    Random,
+    /// Files, where `/` and `\` open the file named by the bytes from the current cell up to
+    /// the next 0 cell, for reading and for writing, and set the current cell to 1 if it was
+    /// opened or else 0. `;` reads a byte of the file open for reading into the current cell,
+    /// `:` writes the current cell to the file open for writing, and `=` sets the current cell
+    /// to 1 if the last `;` read a byte or else 0, when it was at the end of the file
+    Files,
+}
+
+impl Dialect {
+    /// Every dialect, in the order `--help` shows them
+    pub const ALL: &'static [Dialect] = &[
+        Dialect::Brainfuck,
+        Dialect::Pbrain,
+        Dialect::Extended1,
+        Dialect::Multitape,
+        Dialect::Brainfork,
+        Dialect::Random,
+        Dialect::Files,
+    ];
+
+    /// The name `--dialect` takes
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Brainfuck => "brainfuck",
+            Dialect::Pbrain => "pbrain",
+            Dialect::Extended1 => "extended1",
+            Dialect::Multitape => "multitape",
+            Dialect::Brainfork => "brainfork",
+            Dialect::Random => "random",
+            Dialect::Files => "files",
+        }
+    }
+
+    /// The dialect called `name`
+    pub fn from_name(name: &str) -> Option<Dialect> {
+        Dialect::ALL
+            .iter()
+            .copied()
+            .find(|dialect| dialect.name() == name)
+    }
+
+    /// The instructions it has besides the ones of Brainfuck
+    pub fn instructions(self) -> &'static str {
+        match self {
+            Dialect::Brainfuck => "",
+            Dialect::Pbrain => "():",
+            Dialect::Extended1 => "@$!{}~^&|",
+            Dialect::Multitape => "{}",
+            Dialect::Brainfork => "Y",
+            Dialect::Random => "?",
+            Dialect::Files => "/\\;:=",
+        }
+    }
+}
+
+/// The instructions a token can stand for
+const INSTRUCTIONS: &str = "<>[]+-.,#|";
+
+/// The tokens of a dialect, with the instructions they stand for
+pub struct DialectMap {
+    /// The words of every token, the longest first so that a token is not read as one it
+    /// starts with
+    tokens: Vec<(Vec<String>, char)>,
+}
+
+impl DialectMap {
+    /// Reads the map in `text`. Empty lines are skipped
+    pub fn parse(text: &str) -> Result<DialectMap, String> {
+        let mut tokens: Vec<(Vec<String>, char)> = Vec::new();
+        for (line, text) in text.lines().enumerate() {
+            let text = text.trim();
+            let Some(instr) = text.chars().next() else {
+                continue;
+            };
+            let words: Vec<String> = text[instr.len_utf8()..]
+                .split_whitespace()
+                .map(String::from)
+                .collect();
+            if !INSTRUCTIONS.contains(instr) {
+                return Err(format!(
+                    "Invalid instruction '{}' in the dialect map at line {}, expected one of {}",
+                    instr,
+                    line + 1,
+                    INSTRUCTIONS
+                ));
+            }
+            if words.is_empty() {
+                return Err(format!(
+                    "No token for '{}' in the dialect map at line {}",
+                    instr,
+                    line + 1
+                ));
+            }
+            if tokens.iter().any(|(other, _)| *other == words) {
+                return Err(format!(
+                    "Token '{}' mapped twice in the dialect map at line {}",
+                    words.join(" "),
+                    line + 1
+                ));
+            }
+            tokens.push((words, instr));
+        }
+        tokens.sort_by_key(|(words, _)| {
+            core::cmp::Reverse(words.iter().map(String::len).sum::<usize>())
+        });
+        Ok(DialectMap { tokens })
+    }
+
+    /// The Brainfuck code of `source`. The lines stay where they were, so the positions of the
+    /// errors and of `--line-directives` are the ones of the source
+    pub fn translate(&self, source: &str) -> String {
+        let mut code = String::new();
+        let mut pos = 0;
+        while let Some(ch) = source[pos..].chars().next() {
+            let token = self
+                .tokens
+                .iter()
+                .find_map(|(words, instr)| Some((matches(source, pos, words)?, *instr)));
+            match token {
+                Some((end, instr)) => {
+                    code.push(instr);
+                    code.extend(source[pos..end].matches('\n').map(|_| '\n'));
+                    pos = end;
+                }
+                None => {
+                    if ch == '\n' {
+                        code.push('\n');
+                    }
+                    pos += ch.len_utf8();
+                }
+            }
+        }
+        code
+    }
+}
+
+/// Where the token with `words` ends if it is at `pos` in `source`
+fn matches(source: &str, mut pos: usize, words: &[String]) -> Option<usize> {
+    for (i, word) in words.iter().enumerate() {
+        if i > 0 {
+            let rest = &source[pos..];
+            let space = rest.len() - rest.trim_start().len();
+            if space == 0 {
+                return None;
+            }
+            pos += space;
+        }
+        if !source[pos..].starts_with(word.as_str()) {
+            return None;
+        }
+        pos += word.len();
+    }
+    Some(pos)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_turn_tokens_into_instructions() {
+        let map = DialectMap::parse("+ pi\n- pipi\n> Blub. Blub?\n\n. out").unwrap();
+        // The longest token wins, and the words of one can be on different lines
+        assert_eq!(map.translate("pipi pi x Blub.\n  Blub? out"), "-+>\n.");
+        assert_eq!(map.translate("Blub.Blub?"), "");
+        assert_eq!(
+            DialectMap::parse("+ a\n* b").err().as_deref(),
+            Some(
+                "Invalid instruction '*' in the dialect map at line 2, expected one of <>[]+-.,#|"
+            )
+        );
+        assert_eq!(
+            DialectMap::parse("+").err().as_deref(),
+            Some("No token for '+' in the dialect map at line 1")
+        );
+        assert_eq!(
+            DialectMap::parse("+ a  b\n- a b").err().as_deref(),
+            Some("Token 'a b' mapped twice in the dialect map at line 2")
+        );
+    }
+}
@@ -0,0 +1,3439 @@
+use crate::{
+    asm::{Syntax, Target},
+    ast,
+    backend::{CompileOptions, Registry},
+    bytecode,
+    c::{self, CFlags, Source},
+    dialect::{Dialect, DialectMap},
+    interpreter::*,
+    ir::{self, Node},
+    macros, ook,
+};
+use getch::Getch;
+use std::{
+    env,
+    error::Error,
+    fmt,
+    fs::{self, File},
+    io::{self, IsTerminal, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Child, Command, ExitStatus, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// What `--emit` prints, or what the code is compiled into
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Emit {
+    /// The nodes after the optimization passes
+    Ir,
+    /// The loops of the source, with where they are in it
+    Ast,
+    /// The C code of the program, without compiling it
+    C,
+    /// The assembly the C compiler makes of the program
+    Asm,
+    /// The program, which is what compiling makes without `--emit`
+    Exe,
+    /// A C function and a header to link the code into other programs, instead of a program
+    CLib,
+    /// The function of [`Emit::CLib`] built into an object file
+    Obj,
+    /// The function of [`Emit::CLib`] built into a static library
+    StaticLib,
+    /// The function of [`Emit::CLib`] built into a shared library, with `bf_main` to run it on
+    /// stdin and stdout
+    CDylib,
+    /// The code as a Rust program, which `--compiler=rustc` builds instead of the C code
+    Rust,
+    /// The code as LLVM IR, which `--llvm` builds instead of the C code
+    LlvmIr,
+    /// The program as a WebAssembly module for WASI, written without a compiler
+    Wasm,
+    /// The code after the optimizations turned back into Brainfuck
+    Bf,
+    /// The code as a Java class, which `--compiler=javac` builds into a jar instead of the C code
+    Java,
+    /// The C code of `--freestanding` in an Arduino sketch, doing its input and output through
+    /// the serial port
+    Arduino,
+}
+
+impl Emit {
+    /// Whether it is printed instead of running the code, rather than compiled
+    pub fn is_printed(self) -> bool {
+        matches!(self, Emit::Ir | Emit::Ast)
+    }
+
+    /// Whether it is the code as a function to link into other programs, with a header for it
+    pub fn is_lib(self) -> bool {
+        matches!(
+            self,
+            Emit::CLib | Emit::Obj | Emit::StaticLib | Emit::CDylib
+        )
+    }
+
+    /// Whether it is the C code itself, which the C compiler is not run on
+    fn is_c(self) -> bool {
+        matches!(self, Emit::C | Emit::CLib | Emit::Arduino)
+    }
+
+    /// The flags that cannot be passed with it
+    fn invalid_flags(self) -> u64 {
+        // What only a program of its own uses, and the library takes its memory from the caller
+        let program = ArgFlags::DEBUG
+            | ArgFlags::DEBUG_ENV
+            | ArgFlags::CHECKED
+            | ArgFlags::GROW_TAPE
+            | ArgFlags::GETCHAR
+            | ArgFlags::UNBUFFERED
+            | ArgFlags::RUN
+            | ArgFlags::TEMPLATE
+            | ArgFlags::FREESTANDING;
+        // What is only for the C compiler and the linker
+        let build = ArgFlags::CFLAGS | ArgFlags::TARGET;
+        let link = ArgFlags::LDFLAGS | ArgFlags::LIBS | ArgFlags::STATIC;
+        match self {
+            Emit::Ir | Emit::Ast => {
+                ArgFlags::COMPILE_ONLY | ArgFlags::INTERPRET_ONLY | ArgFlags::INTERPRET
+            }
+            Emit::Exe => 0,
+            Emit::C => ArgFlags::RUN | build | link,
+            Emit::Asm => ArgFlags::RUN | link,
+            Emit::CLib => program | build | link,
+            Emit::Obj | Emit::StaticLib => program | link,
+            Emit::CDylib => program | ArgFlags::STATIC,
+            Emit::Rust | Emit::Java => ArgFlags::RUN | ArgFlags::C_ONLY | build | link,
+            // Nothing checks the pointer in the IR
+            Emit::LlvmIr => ArgFlags::RUN | ArgFlags::C_ONLY | ArgFlags::CHECKED | build | link,
+            // The module is all there is, with no debug mode or checks
+            Emit::Wasm => {
+                ArgFlags::RUN
+                    | ArgFlags::C_ONLY
+                    | ArgFlags::CHECKED
+                    | ArgFlags::DEBUG
+                    | ArgFlags::DEBUG_ENV
+                    | ArgFlags::KEEP
+                    | ArgFlags::COMPILER
+                    | build
+                    | link
+            }
+            // The board runs the sketch, which has its own `main`
+            Emit::Arduino => program | build | link,
+            Emit::Bf => {
+                ArgFlags::RUN
+                    | ArgFlags::C_ONLY
+                    | ArgFlags::CHECKED
+                    | ArgFlags::DEBUG_ENV
+                    | ArgFlags::GETCHAR
+                    | ArgFlags::UNBUFFERED
+                    | ArgFlags::KEEP
+                    | ArgFlags::COMPILER
+                    | build
+                    | link
+            }
+        }
+    }
+}
+
+/// What the code is translated to before it is built
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Language {
+    C,
+    Rust,
+    LlvmIr,
+    Asm(Syntax),
+    Java,
+}
+
+impl Language {
+    fn name(self) -> &'static str {
+        match self {
+            Language::C => "C",
+            Language::Rust => "Rust",
+            Language::LlvmIr => "LLVM IR",
+            Language::Asm(_) => "assembly",
+            Language::Java => "Java",
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Language::C => ".c",
+            Language::Rust => ".rs",
+            Language::LlvmIr => ".ll",
+            Language::Asm(Syntax::Gas) => ".s",
+            Language::Asm(Syntax::Nasm) => ".asm",
+            Language::Java => ".java",
+        }
+    }
+}
+
+/// The build file `--build-file` writes next to the C code of `--emit=c`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BuildFile {
+    Make,
+    Ninja,
+}
+
+pub struct Args<'a> {
+    pub console: bool,
+    pub interpret: bool,
+    pub file: &'a str,
+    pub debug: bool,
+    pub mem_size: usize,
+    pub offset: usize,
+    pub release: bool,
+    pub exit_cell: Option<ExitCell>,
+    pub strict: bool,
+    pub record_input: Option<&'a str>,
+    /// Input for `,` after the one given after the `!` in the code, before stdin
+    pub input_str: Option<&'a str>,
+    pub replay_input: Option<&'a str>,
+    pub step_delay: Option<Duration>,
+    pub jit: bool,
+    /// Keep the instructions in a `.bfc` file next to the source
+    pub cache: bool,
+    /// Where to write how often every loop ran
+    pub profile_out: Option<&'a str>,
+    /// A profile written with `profile_out` to guide the C code
+    pub profile_use: Option<&'a str>,
+    /// Which optimization passes run, from 0 to 3. No level has the `eval` pass, see `eval`
+    pub opt_level: u8,
+    /// The passes to run instead of the ones of `opt_level`, as a mask of [`ir::PASSES`]
+    pub passes: Option<u32>,
+    /// Passes that never run, as a mask
+    pub disabled_passes: u32,
+    pub emit: Option<Emit>,
+    /// Print what the optimization passes changed before running the code
+    pub opt_report: bool,
+    /// Run programs that never read input at compile time, see the `eval` pass
+    pub eval: bool,
+    /// Check the code after every optimization pass
+    pub verify_ir: bool,
+    verbose: bool,
+    /// Read input with a plain `getchar()` in the C code instead of raw terminal mode
+    getchar: bool,
+    /// Write every output of the C code right away instead of when it reads or ends
+    unbuffered: bool,
+    /// How many bits the cells of the C code have, 8, 16 or 32
+    cell_size: u8,
+    /// Stop the C code with an error when the pointer leaves the memory
+    checked: bool,
+    /// Make the memory of the C code bigger when the pointer goes past its end
+    grow_tape: bool,
+    /// Point the lines of the C code back to the source with `#line`
+    line_directives: bool,
+    /// Comment the C code with the source it came from
+    annotate: bool,
+    /// A C file to put the code in instead of the default one
+    template: Option<&'a str>,
+    /// Write C that needs no libc, doing its input and output through hooks
+    freestanding: bool,
+    /// Only print the debug symbols of the C code when `BF_DEBUG` is set
+    debug_env: bool,
+    run: bool,
+    output: &'a str,
+    keep: bool,
+    /// The C compiler, or `None` for the one in `CC`, or the first of gcc, clang and cc
+    compiler: Option<&'a str>,
+    /// More flags for the C compiler, separated by whitespace
+    cflags: &'a str,
+    /// More flags for the linker, separated by whitespace
+    ldflags: &'a str,
+    /// The libraries to link with, separated by commas
+    libs: &'a str,
+    /// The target triple to build the program for, instead of this machine
+    target: Option<&'a str>,
+    /// Link the libc and everything else into the program
+    static_link: bool,
+    /// `brainfuck run`, which builds the program in a temporary directory and runs it there
+    script: bool,
+    /// The build file to write with the C code, to build it later
+    build_file: Option<BuildFile>,
+    /// Build the program from the LLVM IR instead of the C code
+    llvm: bool,
+    /// Build the program from assembly of its own instead of the C code, for this assembler
+    asm: Option<Syntax>,
+    /// Write the x86-64 Linux executable directly, without a compiler, an assembler or a linker
+    elf: bool,
+    /// Write the machine code of `--elf` as an object file and link it with ld
+    aot: bool,
+    /// The name of the backend to write the code with instead of building it
+    backend: Option<&'a str>,
+    /// A file mapping the tokens of a dialect to the instructions, to read the source with
+    dialect_map: Option<&'a str>,
+    /// The instructions the source has besides the ones of Brainfuck
+    dialect: Dialect,
+    /// How many tapes the multitape dialect has
+    tapes: usize,
+    /// Run the forks of Brainfork in the C code as POSIX threads
+    pthreads: bool,
+    /// The seed of `?` in the random dialect, or `None` for a different one every run
+    seed: Option<u64>,
+    /// Expand the macros of the source before reading it
+    macros: bool,
+}
+
+struct ArgFlags(u64);
+
+impl ArgFlags {
+    const HELP: u64 = 1;
+    const FILE: u64 = 2;
+    const OUTPUT: u64 = 4; //I
+    const KEEP: u64 = 8; //I
+    const COMPILER: u64 = 16; //I
+    const RUN: u64 = 32; //I
+    const INTERPRET: u64 = 64;
+    const DEBUG: u64 = 128;
+    const MEM_SIZE: u64 = 256;
+    const OFFSET: u64 = 512;
+    const RELEASE: u64 = 1024; //I
+    const VERBOSE: u64 = 2048;
+    const EXIT_CELL: u64 = 4096;
+    const STRICT: u64 = 8192;
+    const RECORD_INPUT: u64 = 16384;
+    const REPLAY_INPUT: u64 = 32768;
+    const STEP_DELAY: u64 = 65536;
+    const JIT: u64 = 131072;
+    const CACHE: u64 = 262144;
+    const PROFILE_OUT: u64 = 524288;
+    const PROFILE_USE: u64 = 1048576; //I
+    const OPT_LEVEL: u64 = 2097152;
+    const EMIT: u64 = 4194304;
+    const PASSES: u64 = 8388608;
+    const DISABLE_PASS: u64 = 16777216;
+    const OPT_REPORT: u64 = 33554432;
+    const EVAL: u64 = 67108864;
+    const VERIFY_IR: u64 = 134217728;
+    const GETCHAR: u64 = 268435456; //I
+    const UNBUFFERED: u64 = 536870912; //I
+    const CELL_SIZE: u64 = 1073741824; //I
+    const CHECKED: u64 = 2147483648; //I
+    const GROW_TAPE: u64 = 4294967296; //I
+    const LINE_DIRECTIVES: u64 = 8589934592; //I
+    const ANNOTATE: u64 = 17179869184; //I
+    const TEMPLATE: u64 = 34359738368; //I
+    const FREESTANDING: u64 = 68719476736; //I
+    const DEBUG_ENV: u64 = 137438953472; //I
+    const BUILD_EMIT: u64 = 274877906944; //I
+    const INPUT_STR: u64 = 549755813888;
+    const CFLAGS: u64 = 1099511627776; //I
+    const LDFLAGS: u64 = 2199023255552; //I
+    const LIBS: u64 = 4398046511104; //I
+    const TARGET: u64 = 8796093022208; //I
+    const STATIC: u64 = 17592186044416; //I
+    const SCRIPT: u64 = 35184372088832; //I
+    const BUILD_FILE: u64 = 70368744177664; //I
+    const LLVM: u64 = 140737488355328; //I
+    const ASM: u64 = 281474976710656; //I
+    const ELF: u64 = 562949953421312; //I
+    const BACKEND: u64 = 1125899906842624; //I
+    const DIALECT_MAP: u64 = 2251799813685248;
+    const DIALECT: u64 = 4503599627370496;
+    const TAPES: u64 = 9007199254740992;
+    const PTHREADS: u64 = 18014398509481984; //I
+    const SEED: u64 = 36028797018963968;
+    const MACROS: u64 = 72057594037927936;
+    const AOT: u64 = 144115188075855872; //I
+
+    /// Flags for what only the C code has, which the Rust code of `--emit=rust` does not
+    const C_ONLY: u64 = Self::PROFILE_USE
+        | Self::GROW_TAPE
+        | Self::LINE_DIRECTIVES
+        | Self::ANNOTATE
+        | Self::TEMPLATE
+        | Self::FREESTANDING
+        | Self::CFLAGS
+        | Self::LDFLAGS
+        | Self::LIBS
+        | Self::BUILD_FILE
+        | Self::PTHREADS;
+
+    /// Flags that only make sense when compiling
+    const COMPILE_ONLY: u64 = Self::OUTPUT
+        | Self::KEEP
+        | Self::COMPILER
+        | Self::RUN
+        | Self::RELEASE
+        | Self::PROFILE_USE
+        | Self::GETCHAR
+        | Self::UNBUFFERED
+        | Self::CELL_SIZE
+        | Self::CHECKED
+        | Self::GROW_TAPE
+        | Self::LINE_DIRECTIVES
+        | Self::ANNOTATE
+        | Self::TEMPLATE
+        | Self::FREESTANDING
+        | Self::DEBUG_ENV
+        | Self::BUILD_EMIT
+        | Self::CFLAGS
+        | Self::LDFLAGS
+        | Self::LIBS
+        | Self::TARGET
+        | Self::STATIC
+        | Self::BUILD_FILE
+        | Self::LLVM
+        | Self::ASM
+        | Self::ELF
+        | Self::AOT
+        | Self::BACKEND
+        | Self::PTHREADS;
+    /// Flags that only make sense when interpreting
+    const INTERPRET_ONLY: u64 = Self::RECORD_INPUT
+        | Self::REPLAY_INPUT
+        | Self::STEP_DELAY
+        | Self::JIT
+        | Self::CACHE
+        | Self::PROFILE_OUT;
+    /// Flags that can be passed to the console interpreter
+    const CONSOLE: u64 = Self::DEBUG
+        | Self::MEM_SIZE
+        | Self::OFFSET
+        | Self::VERBOSE
+        | Self::OPT_LEVEL
+        | Self::PASSES
+        | Self::DISABLE_PASS
+        | Self::EVAL
+        | Self::VERIFY_IR
+        | Self::DIALECT
+        | Self::TAPES
+        | Self::SEED
+        | Self::INTERPRET_ONLY;
+    /// Flags that can be passed without a file
+    const NO_FILE: u64 = Self::COMPILE_ONLY | Self::INTERPRET;
+}
+
+impl Default for Args<'_> {
+    fn default() -> Self {
+        Self {
+            release: false,
+            offset: 0,
+            mem_size: 30000,
+            console: false,
+            run: false,
+            file: "",
+            output: "output",
+            keep: false,
+            compiler: None,
+            cflags: "",
+            ldflags: "",
+            libs: "",
+            target: None,
+            static_link: false,
+            script: false,
+            build_file: None,
+            llvm: false,
+            asm: None,
+            elf: false,
+            aot: false,
+            backend: None,
+            dialect_map: None,
+            dialect: Dialect::Brainfuck,
+            tapes: 2,
+            pthreads: false,
+            seed: None,
+            macros: false,
+            interpret: false,
+            debug: false,
+            verbose: false,
+            exit_cell: None,
+            strict: false,
+            record_input: None,
+            input_str: None,
+            replay_input: None,
+            step_delay: None,
+            jit: false,
+            cache: false,
+            profile_out: None,
+            profile_use: None,
+            opt_level: 3,
+            passes: None,
+            disabled_passes: 0,
+            emit: None,
+            opt_report: false,
+            eval: false,
+            verify_ir: false,
+            getchar: false,
+            unbuffered: false,
+            cell_size: 8,
+            checked: false,
+            grow_tape: false,
+            line_directives: false,
+            annotate: false,
+            template: None,
+            freestanding: false,
+            debug_env: false,
+        }
+    }
+}
+
+impl Args<'_> {
+    /// The settings used by the interpreter
+    pub fn config(&self) -> Config {
+        let eval = match self.eval {
+            true => ir::pass_mask("eval").unwrap_or(0),
+            false => 0,
+        };
+        Config {
+            mem_size: self.mem_size,
+            offset: self.offset,
+            debug: self.debug,
+            verbose: self.verbose,
+            exit_cell: self.exit_cell,
+            passes: (self.passes.unwrap_or(ir::level_passes(self.opt_level)) | eval)
+                & !self.disabled_passes,
+            verify_ir: self.verify_ir,
+            dialect: self.dialect,
+            tapes: self.tapes,
+            seed: self.seed.unwrap_or_else(random_seed),
+        }
+    }
+}
+
+/// A seed for `?` that is different every run, for when `--seed` does not give one
+pub(crate) fn random_seed() -> u64 {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH);
+    time.map_or(0, |time| time.as_nanos() as u64) ^ ((process::id() as u64) << 32)
+}
+
+pub fn parse_args(args: &[String]) -> Result<Args<'_>, String> {
+    let mut parsed_args = Args::default();
+    let mut flags = ArgFlags(0);
+    // `brainfuck run file` is compiling it with --run, without leaving anything behind
+    if args.get(1).is_some_and(|arg| arg == "run") {
+        flags.0 |= ArgFlags::SCRIPT | ArgFlags::RUN;
+        parsed_args.script = true;
+        parsed_args.run = true;
+    }
+
+    for arg in args.iter().skip(1 + parsed_args.script as usize) {
+        match arg.as_str() {
+            "--help" | "-h" => {
+                if flags.0 & ArgFlags::HELP != 0 {
+                    continue;
+                }
+                flags.0 |= ArgFlags::HELP;
+                println!("\nUsage:\n  brainfuck [options] file\n  brainfuck run [options] file    Build the program in a temporary directory, run it and\n\t\t\t\t  remove it, like a script\n\nThe file is Brainfuck, Ook! when it ends with .ook, or a Brainloller image when it\nends with .png, which needs the brainloller feature\n");
+                println!("Options:");
+                println!("  --help | -h            Show this message");
+                println!("  --keep | -k            Keep the generated C file next to the output. It is built in a temporary\n\t\t\t directory otherwise, and only kept when the compiler fails");
+                println!(
+                    "  --output | -o          Set the name of the output file, default is 'output'"
+                );
+                println!("  --compiler | -c        Select the compiler to compile the C generated file, default is the one\n\t\t\t in CC, or else gcc, clang or cc, whichever is installed. libtcc builds it\n\t\t\t without a compiler when built with the tcc feature. The flags in CFLAGS and\n\t\t\t LDFLAGS are passed to it");
+                println!("  --cflags=FLAGS         Pass the whitespace separated FLAGS to the C compiler, like\n\t\t\t --cflags=\"-O3 -march=native -fsanitize=address\"");
+                println!("  --ldflags=FLAGS        Pass the whitespace separated FLAGS to the linker, after the C file");
+                println!("  --libs=LIST            Link with the libraries in the comma separated LIST, like --libs=m,pthread\n\t\t\t for -lm -lpthread");
+                println!("  --target=TRIPLE        Build the program for the target TRIPLE, like aarch64-linux-gnu. gcc\n\t\t\t becomes the TRIPLE-gcc cross compiler, clang and zig are told the\n\t\t\t target. Programs for Windows get a .exe at the end");
+                println!("  --run | -r             Runs the program after compiling");
+                println!("  --interpret | -i       Interprets the program instead of compiling it");
+                println!("  --debug | -d           Activates the debug mode.\n\t\t\t In the debug mode, any # or | will be considered as a debug symbol");
+                println!("  --mem_size | -m        Set the memory, default is 30000");
+                println!("  --release | -rl        Compiles in release mode, leaving out the debug symbols and building the\n\t\t\t C code with -O2 -s instead of -g");
+                println!("  -O0 | -O1 | -O2 | -O3  Set the optimization level, default is -O3.\n\t\t\t -O1 merges runs of the same instruction, -O2 also turns clear, scan and copy loops\n\t\t\t into single instructions and removes dead code, -O3 also addresses cells by offset\n\t\t\t instead of moving and folds constants. The eval pass is in no level, it\n\t\t\t only runs with --eval or --passes=eval, and there is no -O4");
+                println!("  --verbose | -v         Compiles VerboseFuck");
+                println!("  --strict | -s          Any character that is not an instruction or whitespace is an error");
+                println!("  --macros               Expand the macros of the source first. A line '!define NAME BODY' makes\n\t\t\t @NAME stand for BODY, which can use other macros, like '!define zero [-]'\n\t\t\t and then @zero. Not with extended1, where ! and @ are instructions");
+                println!("  --dialect=NAME         Read the source with the instructions of the dialect NAME, one of {}.\n\t\t\t pbrain has procedures, where (...) makes its body the procedure of the\n\t\t\t value of the current cell and : calls it. extended1 is Extended Type I, where\n\t\t\t @ ends the program, $ and ! store and retrieve the cell, and {{ }} ~ ^ & |\n\t\t\t are bitwise; ! does not start the input then. multitape has several tapes,\n\t\t\t with {{ and }} going to the previous and the next one. brainfork has Y,\n\t\t\t which forks a thread on a copy of the memory one cell to the right, setting\n\t\t\t its cell to 1 and the one of the parent to 0. random has ?, which sets the\n\t\t\t cell to a random byte. files has / and \\, which open a file for reading and\n\t\t\t one for writing, named by the bytes from the cell up to the next 0, setting\n\t\t\t the cell to 1 if it opened, ; and : which read and write a byte of them,\n\t\t\t and =, which sets the cell to 1 if the last ; read a byte and to 0 at the end\n\t\t\t of the file. The dialects are only in the interpreter and the C code", dialect_names());
+                println!("  --pthreads             Run the forks of brainfork as threads in the C code, linked with -pthread");
+                println!("  --seed=N               Start the random bytes of ? in the random dialect from N, the same in the\n\t\t\t interpreter and the C code. Without it, they are different every run, except\n\t\t\t in the C libraries, the templates and the C code without the libc, which get\n\t\t\t a seed when they are written");
+                println!("  --tapes=N              Give the multitape dialect N tapes of --mem-size cells, default is 2");
+                println!("  --dialect-map=FILE     Read the source with the tokens of FILE instead of the instructions. Every\n\t\t\t line of FILE is an instruction and its token, like '+ pipi', and the file\n\t\t\t can then have any extension");
+                println!("  --record-input=FILE    Save every byte read from the terminal to FILE");
+                println!("  --replay-input=FILE    Read input from FILE before reading from the terminal,\n\t\t\t also in the program run by --run");
+                println!("  --step-delay=MS        Wait MS milliseconds between interpreted instructions.\n\t\t\t In the debug mode, every instruction is also printed");
+                println!("  --jit | -j             Compile the code to native code before interpreting it.\n\t\t\t Needs the jit feature, and does nothing in the debug mode");
+                println!("  --cache                Keep the instructions in a .bfc file next to the source and reuse them\n\t\t\t while the source does not change");
+                println!("  --profile-out=FILE     Count how often every loop runs while interpreting and save it to FILE");
+                println!("  --profile-use=FILE     Unroll the loops that ran the most and move the ones that never ran\n\t\t\t out of the way in the C code, using a profile from --profile-out");
+                println!("  --passes=LIST          Run only the optimization passes in the comma separated LIST, out of\n\t\t\t {}", pass_names());
+                println!("  --disable-pass=LIST    Never run the optimization passes in the comma separated LIST");
+                println!("  --opt-report           Print what every optimization pass changed");
+                println!("  --verify-ir            Check the code after every optimization pass, to find the pass that\n\t\t\t breaks it. Always done in debug builds");
+                println!("  --eval                 Run programs that never read input while compiling them, so that\n\t\t\t only their output is left. This adds the eval pass to the ones of -O or --passes");
+                println!("  --emit=ir              Print the code after the optimizations instead of running it");
+                println!("  --emit=ast             Print the loops of the code with where they are in the source, instead\n\t\t\t of running it");
+                println!(
+                    "  --emit=c               Write the C code to OUTPUT.c without compiling it"
+                );
+                println!(
+                    "  --emit=asm             Write the assembly the C compiler makes to OUTPUT.s"
+                );
+                println!("  --emit=exe             Compile the code into a program, which is the default");
+                println!("  --emit=c-lib           Write the code as the function int bf_program(bf_cell* tape, size_t len,\n\t\t\t bf_io* io) in OUTPUT.c, declared in OUTPUT.h, to link into other C\n\t\t\t programs. The bf_io holds the functions , and . go through. OUTPUT\n\t\t\t is bf_program unless --output says otherwise");
+                println!("  --emit=obj             Build the function of --emit=c-lib into the object file OUTPUT.o,\n\t\t\t next to OUTPUT.h");
+                println!("  --emit=staticlib       Build the function of --emit=c-lib into the static library libOUTPUT.a,\n\t\t\t next to OUTPUT.h, with the ar in AR, or else ar");
+                println!("  --emit=cdylib          Build the function of --emit=c-lib into the shared library\n\t\t\t libOUTPUT.so, libOUTPUT.dylib or OUTPUT.dll, next to OUTPUT.h. It also\n\t\t\t exports int bf_main(void), which runs the code on stdin and stdout");
+                println!("  --emit=rust            Write the code as a Rust program to OUTPUT.rs, which only needs std.\n\t\t\t --compiler=rustc builds it into the program instead of the C code");
+                println!("  --emit=llvm-ir         Write the code as LLVM IR to OUTPUT.ll, for LLVM 15 and later");
+                println!("  --emit=wasm            Write the program as a WebAssembly module for WASI to OUTPUT.wasm,\n\t\t\t which runs under wasmtime or wasmer with its input and output piped");
+                println!("  --emit=java            Write the code as the Java class BfProgram to OUTPUT.java, which only\n\t\t\t needs java.io. --compiler=javac builds it into OUTPUT.jar instead of the\n\t\t\t C code, which runs with java -jar");
+                println!("  --emit=arduino         Write the C code of --freestanding as the Arduino sketch OUTPUT.ino,\n\t\t\t doing its output and input through the serial port. The memory is\n\t\t\t {} cells unless -m says otherwise, and the sketch does not\n\t\t\t build for a board without the SRAM for it", ARDUINO_MEM_SIZE);
+                println!("  --emit=bf              Write the code after the optimizations back as Brainfuck to OUTPUT.bf,\n\t\t\t without what cancels out or never runs");
+                println!("  --llvm                 Build the program from the LLVM IR instead of the C code, with clang,\n\t\t\t or else with llc and the C compiler to link it");
+                println!("  --asm[=SYNTAX]         Build the program from assembly that needs no libc, for x86-64 Linux,\n\t\t\t or AArch64 Linux and macOS with --target or on those, with as and ld,\n\t\t\t or nasm for --asm=nasm on x86-64, or cc on macOS. With --emit=asm,\n\t\t\t writes that assembly to OUTPUT.s or OUTPUT.asm instead of the C\n\t\t\t compiler's");
+                println!("  --backend=NAME         Write the code with the backend NAME to OUTPUT with its extension\n\t\t\t instead of building it, one of {}", Registry::new().names().collect::<Vec<_>>().join(", "));
+                println!("  --elf                  Write the program as an x86-64 Linux executable directly, with no\n\t\t\t compiler, assembler or linker");
+                println!("  --aot                  Write the code of --elf as an object file and link it into the program\n\t\t\t with ld, with no compiler or assembler. --keep keeps it in OUTPUT.o.\n\t\t\t Needs the aot feature");
+                println!("  --build-file=KIND      With --emit=c, also write OUTPUT.mk for make or OUTPUT.ninja for ninja,\n\t\t\t to build the C code later with the compiler and flags given now");
+                println!("  --getchar              Read input with getchar in the compiled program instead of reading\n\t\t\t keys from the terminal, for when the input is piped or redirected");
+                println!("  --unbuffered           Write every output of the compiled program right away. By default\n\t\t\t it is only written before reading input and at the end");
+                println!("  --cell-size=BITS       Set the size of the cells of the compiled program to 8, 16 or 32 bits,\n\t\t\t default is 8");
+                println!("  --checked              Stop the compiled program with an error when the pointer leaves the\n\t\t\t memory, giving the node of --emit=ir where it did. Left out when the\n\t\t\t pointer provably stays in it");
+                println!("  --grow-tape            Allocate the memory of the compiled program on the heap and double it\n\t\t\t whenever the pointer goes past its end, so that --mem_size is only\n\t\t\t where it starts");
+                println!("  --line-directives      Point the compiler, the debugger and the sanitizers at the lines of the\n\t\t\t source instead of the C file. With --keep, also writes where every loop\n\t\t\t went in the C file to OUTPUT.map");
+                println!("  --annotate             Split the C code into the pieces of the source they came from, with a\n\t\t\t comment showing the piece, its loop depth and how far the pointer is\n\t\t\t from where the loop started. Best with --keep");
+                println!("  --template=FILE        Put the C code in FILE where it says {{{{BODY}}}}, instead of in the default\n\t\t\t main. {{{{MEM_SIZE}}}} and {{{{OFFSET}}}} become the memory size and the pointer\n\t\t\t offset, and {{{{EXIT_CODE}}}} what --exit-cell says to return. FILE has to\n\t\t\t define the bf_cell type, the mem and ptr variables and the bf_getch and\n\t\t\t bf_memrchr functions the code uses");
+                println!("  --freestanding         Write C that needs no libc, for microcontrollers and kernels. Output and\n\t\t\t input go through the functions void bf_putc(unsigned char) and\n\t\t\t int bf_getc(void), which have to be linked in");
+                println!("  --debug-env            Like --debug, but the compiled program only prints the debug symbols\n\t\t\t when it runs with the BF_DEBUG environment variable set to something\n\t\t\t other than 0");
+                println!("  --input-str=TEXT       Read TEXT with , before reading stdin, after any input given after a !\n\t\t\t in the code. The compiled program has it built in");
+                println!("  --static               Link the libc into the program, so that it runs without any libraries,\n\t\t\t like in containers. zig builds it with musl");
+                println!("  --exit-cell[=N] | -e   Use the value of cell N (default: the current cell) at the end as the exit code");
+                println!("  --ptr-offset | -po     Set the pointer offset from the start of the memory, default is 0\n");
+            }
+            "--keep" | "-k" => {
+                if flags.0 & ArgFlags::KEEP != 0 {
+                    return Err("More than 1 build flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::KEEP;
+                parsed_args.keep = true;
+            }
+            "--verbose" | "-v" => {
+                if flags.0 & ArgFlags::VERBOSE != 0 {
+                    return Err("Compile in VerboseFuck".to_owned());
+                }
+                flags.0 |= ArgFlags::VERBOSE;
+                parsed_args.verbose = true;
+            }
+            "--release" | "-rl" => {
+                if flags.0 & ArgFlags::RELEASE != 0 {
+                    return Err("More than 1 release flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::RELEASE;
+                parsed_args.release = true;
+            }
+            "--debug" | "-d" => {
+                if flags.0 & ArgFlags::DEBUG != 0 {
+                    return Err("More than 1 debug flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::DEBUG;
+                parsed_args.debug = true;
+            }
+            "--interpret" | "-i" => {
+                if flags.0 & ArgFlags::INTERPRET != 0 {
+                    return Err("More than 1 interpret flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::INTERPRET;
+                parsed_args.interpret = true;
+            }
+            "--run" | "-r" => {
+                if flags.0 & ArgFlags::RUN != 0 {
+                    return Err("More than 1 run flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::RUN;
+                parsed_args.run = true;
+            }
+            "--strict" | "-s" => {
+                if flags.0 & ArgFlags::STRICT != 0 {
+                    return Err("More than 1 strict flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::STRICT;
+                parsed_args.strict = true;
+            }
+            "--macros" => {
+                if flags.0 & ArgFlags::MACROS != 0 {
+                    return Err("More than 1 macros flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::MACROS;
+                parsed_args.macros = true;
+            }
+            "-O0" | "-O1" | "-O2" | "-O3" => {
+                if flags.0 & ArgFlags::OPT_LEVEL != 0 {
+                    return Err("More than 1 optimization level passed".to_owned());
+                }
+                flags.0 |= ArgFlags::OPT_LEVEL;
+                parsed_args.opt_level = arg.as_bytes()[2] - b'0';
+            }
+            level if level.starts_with("-O") => {
+                return Err(format!(
+                    "Invalid optimization level {}, expected -O0 to -O3. The eval pass runs with --eval or --passes=eval",
+                    level
+                ));
+            }
+            "--verify-ir" => {
+                if flags.0 & ArgFlags::VERIFY_IR != 0 {
+                    return Err("More than 1 verify ir flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::VERIFY_IR;
+                parsed_args.verify_ir = true;
+            }
+            "--eval" => {
+                if flags.0 & ArgFlags::EVAL != 0 {
+                    return Err("More than 1 eval flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::EVAL;
+                parsed_args.eval = true;
+            }
+            "--opt-report" => {
+                if flags.0 & ArgFlags::OPT_REPORT != 0 {
+                    return Err("More than 1 opt report flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::OPT_REPORT;
+                parsed_args.opt_report = true;
+            }
+            "--jit" | "-j" => {
+                if !cfg!(feature = "jit") {
+                    return Err("Built without the jit feature".to_owned());
+                }
+                if flags.0 & ArgFlags::JIT != 0 {
+                    return Err("More than 1 jit flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::JIT;
+                parsed_args.jit = true;
+            }
+            "--cache" => {
+                if flags.0 & ArgFlags::CACHE != 0 {
+                    return Err("More than 1 cache flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::CACHE;
+                parsed_args.cache = true;
+            }
+            "--getchar" => {
+                if flags.0 & ArgFlags::GETCHAR != 0 {
+                    return Err("More than 1 getchar flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::GETCHAR;
+                parsed_args.getchar = true;
+            }
+            "--unbuffered" => {
+                if flags.0 & ArgFlags::UNBUFFERED != 0 {
+                    return Err("More than 1 unbuffered flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::UNBUFFERED;
+                parsed_args.unbuffered = true;
+            }
+            "--checked" => {
+                if flags.0 & ArgFlags::CHECKED != 0 {
+                    return Err("More than 1 checked flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::CHECKED;
+                parsed_args.checked = true;
+            }
+            "--grow-tape" => {
+                if flags.0 & ArgFlags::GROW_TAPE != 0 {
+                    return Err("More than 1 grow tape flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::GROW_TAPE;
+                parsed_args.grow_tape = true;
+            }
+            "--line-directives" => {
+                if flags.0 & ArgFlags::LINE_DIRECTIVES != 0 {
+                    return Err("More than 1 line directives flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::LINE_DIRECTIVES;
+                parsed_args.line_directives = true;
+            }
+            "--annotate" => {
+                if flags.0 & ArgFlags::ANNOTATE != 0 {
+                    return Err("More than 1 annotate flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::ANNOTATE;
+                parsed_args.annotate = true;
+            }
+            "--pthreads" => {
+                if flags.0 & ArgFlags::PTHREADS != 0 {
+                    return Err("More than 1 pthreads flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::PTHREADS;
+                parsed_args.pthreads = true;
+            }
+            "--freestanding" => {
+                if flags.0 & ArgFlags::FREESTANDING != 0 {
+                    return Err("More than 1 freestanding flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::FREESTANDING;
+                parsed_args.freestanding = true;
+            }
+            "--debug-env" => {
+                if flags.0 & ArgFlags::DEBUG_ENV != 0 {
+                    return Err("More than 1 debug env flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::DEBUG_ENV;
+                parsed_args.debug = true;
+                parsed_args.debug_env = true;
+            }
+            "--asm" => {
+                if flags.0 & ArgFlags::ASM != 0 {
+                    return Err("More than 1 asm flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::ASM;
+                parsed_args.asm = Some(Syntax::Gas);
+            }
+            "--elf" => {
+                if flags.0 & ArgFlags::ELF != 0 {
+                    return Err("More than 1 elf flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::ELF;
+                parsed_args.elf = true;
+            }
+            "--aot" => {
+                if !cfg!(feature = "aot") {
+                    return Err("Built without the aot feature".to_owned());
+                }
+                if flags.0 & ArgFlags::AOT != 0 {
+                    return Err("More than 1 aot flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::AOT;
+                parsed_args.aot = true;
+            }
+            "--llvm" => {
+                if flags.0 & ArgFlags::LLVM != 0 {
+                    return Err("More than 1 llvm flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::LLVM;
+                parsed_args.llvm = true;
+            }
+            "--static" => {
+                if flags.0 & ArgFlags::STATIC != 0 {
+                    return Err("More than 1 static flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::STATIC;
+                parsed_args.static_link = true;
+            }
+            "--exit-cell" | "-e" => {
+                if flags.0 & ArgFlags::EXIT_CELL != 0 {
+                    return Err("More than 1 exit cell flag passed".to_owned());
+                }
+                flags.0 |= ArgFlags::EXIT_CELL;
+                parsed_args.exit_cell = Some(ExitCell::Current);
+            }
+            other => match other.split_once('=') {
+                Some(("--input-str", var)) => {
+                    if flags.0 & ArgFlags::INPUT_STR != 0 {
+                        return Err("More than 1 input str flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::INPUT_STR;
+                    parsed_args.input_str = Some(var)
+                }
+                Some(("--output" | "-o", var)) => {
+                    if flags.0 & ArgFlags::OUTPUT != 0 {
+                        return Err("More than 1 output flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::OUTPUT;
+                    parsed_args.output = var
+                }
+                Some(("--mem-size" | "-m", var)) => {
+                    if flags.0 & ArgFlags::MEM_SIZE != 0 {
+                        return Err("More than 1 output flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::MEM_SIZE;
+                    parsed_args.mem_size = match var.parse::<usize>() {
+                        Ok(res) => res,
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--ptr-offset" | "-po", var)) => {
+                    if flags.0 & ArgFlags::OFFSET != 0 {
+                        return Err("More than 1 output flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::OFFSET;
+                    parsed_args.offset = match var.parse::<usize>() {
+                        Ok(res) => res,
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--exit-cell" | "-e", var)) => {
+                    if flags.0 & ArgFlags::EXIT_CELL != 0 {
+                        return Err("More than 1 exit cell flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::EXIT_CELL;
+                    parsed_args.exit_cell = match var.parse::<usize>() {
+                        Ok(res) => Some(ExitCell::Cell(res)),
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--record-input", var)) => {
+                    if flags.0 & ArgFlags::RECORD_INPUT != 0 {
+                        return Err("More than 1 record input flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::RECORD_INPUT;
+                    parsed_args.record_input = Some(var)
+                }
+                Some(("--replay-input", var)) => {
+                    if flags.0 & ArgFlags::REPLAY_INPUT != 0 {
+                        return Err("More than 1 replay input flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::REPLAY_INPUT;
+                    parsed_args.replay_input = Some(var)
+                }
+                Some(("--step-delay", var)) => {
+                    if flags.0 & ArgFlags::STEP_DELAY != 0 {
+                        return Err("More than 1 step delay flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::STEP_DELAY;
+                    parsed_args.step_delay = match var.parse::<u64>() {
+                        Ok(res) => Some(Duration::from_millis(res)),
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--profile-out", var)) => {
+                    if flags.0 & ArgFlags::PROFILE_OUT != 0 {
+                        return Err("More than 1 profile out flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::PROFILE_OUT;
+                    parsed_args.profile_out = Some(var)
+                }
+                Some(("--profile-use", var)) => {
+                    if flags.0 & ArgFlags::PROFILE_USE != 0 {
+                        return Err("More than 1 profile use flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::PROFILE_USE;
+                    parsed_args.profile_use = Some(var)
+                }
+                Some(("--passes", var)) => {
+                    if flags.0 & ArgFlags::PASSES != 0 {
+                        return Err("More than 1 passes flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::PASSES;
+                    parsed_args.passes = Some(parse_passes(var)?);
+                }
+                Some(("--disable-pass", var)) => {
+                    if flags.0 & ArgFlags::DISABLE_PASS != 0 {
+                        return Err("More than 1 disable pass flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::DISABLE_PASS;
+                    parsed_args.disabled_passes = parse_passes(var)?;
+                }
+                Some(("--emit", var)) => {
+                    if flags.0 & (ArgFlags::EMIT | ArgFlags::BUILD_EMIT) != 0 {
+                        return Err("More than 1 emit flag passed".to_owned());
+                    }
+                    parsed_args.emit = match var {
+                        "ir" => Some(Emit::Ir),
+                        "ast" => Some(Emit::Ast),
+                        "c" => Some(Emit::C),
+                        "asm" => Some(Emit::Asm),
+                        "exe" => Some(Emit::Exe),
+                        "c-lib" => Some(Emit::CLib),
+                        "obj" => Some(Emit::Obj),
+                        "staticlib" => Some(Emit::StaticLib),
+                        "cdylib" => Some(Emit::CDylib),
+                        "rust" => Some(Emit::Rust),
+                        "llvm-ir" => Some(Emit::LlvmIr),
+                        "wasm" => Some(Emit::Wasm),
+                        "bf" => Some(Emit::Bf),
+                        "arduino" => Some(Emit::Arduino),
+                        "java" => Some(Emit::Java),
+                        _ => return Err(format!(
+                            "Invalid emit '{}', expected ir, ast, c, asm, exe, c-lib, obj, staticlib, cdylib, rust, llvm-ir, wasm, bf, arduino or java",
+                            var
+                        )),
+                    };
+                    // The printed ones are instead of running the code, the others are compiled
+                    flags.0 |= match parsed_args.emit.is_some_and(Emit::is_printed) {
+                        true => ArgFlags::EMIT,
+                        false => ArgFlags::BUILD_EMIT,
+                    };
+                }
+                Some(("--cell-size", var)) => {
+                    if flags.0 & ArgFlags::CELL_SIZE != 0 {
+                        return Err("More than 1 cell size flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::CELL_SIZE;
+                    parsed_args.cell_size = match var {
+                        "8" => 8,
+                        "16" => 16,
+                        "32" => 32,
+                        _ => {
+                            return Err(format!(
+                                "Invalid cell size '{}', expected 8, 16 or 32",
+                                var
+                            ))
+                        }
+                    }
+                }
+                Some(("--template", var)) => {
+                    if flags.0 & ArgFlags::TEMPLATE != 0 {
+                        return Err("More than 1 template flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::TEMPLATE;
+                    parsed_args.template = Some(var)
+                }
+                Some(("--compiler" | "-c", var)) => {
+                    if flags.0 & ArgFlags::COMPILER != 0 {
+                        return Err("More than 1 compiler flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::COMPILER;
+                    parsed_args.compiler = Some(var)
+                }
+                Some(("--cflags", var)) => {
+                    if flags.0 & ArgFlags::CFLAGS != 0 {
+                        return Err("More than 1 cflags flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::CFLAGS;
+                    parsed_args.cflags = var
+                }
+                Some(("--ldflags", var)) => {
+                    if flags.0 & ArgFlags::LDFLAGS != 0 {
+                        return Err("More than 1 ldflags flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::LDFLAGS;
+                    parsed_args.ldflags = var
+                }
+                Some(("--libs", var)) => {
+                    if flags.0 & ArgFlags::LIBS != 0 {
+                        return Err("More than 1 libs flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::LIBS;
+                    parsed_args.libs = var
+                }
+                Some(("--asm", var)) => {
+                    if flags.0 & ArgFlags::ASM != 0 {
+                        return Err("More than 1 asm flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::ASM;
+                    parsed_args.asm = match var {
+                        "gas" => Some(Syntax::Gas),
+                        "nasm" => Some(Syntax::Nasm),
+                        _ => {
+                            return Err(format!(
+                                "Invalid assembler '{}', expected gas or nasm",
+                                var
+                            ))
+                        }
+                    };
+                }
+                Some(("--dialect", var)) => {
+                    if flags.0 & ArgFlags::DIALECT != 0 {
+                        return Err("More than 1 dialect flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::DIALECT;
+                    parsed_args.dialect = Dialect::from_name(var).ok_or_else(|| {
+                        format!(
+                            "Invalid dialect '{}', expected one of {}",
+                            var,
+                            dialect_names()
+                        )
+                    })?;
+                }
+                Some(("--tapes", var)) => {
+                    if flags.0 & ArgFlags::TAPES != 0 {
+                        return Err("More than 1 tapes flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::TAPES;
+                    parsed_args.tapes = match var.parse::<usize>() {
+                        Ok(0) => return Err("There must be at least 1 tape".to_owned()),
+                        Ok(res) => res,
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--seed", var)) => {
+                    if flags.0 & ArgFlags::SEED != 0 {
+                        return Err("More than 1 seed flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::SEED;
+                    parsed_args.seed = match var.parse::<u64>() {
+                        Ok(res) => Some(res),
+                        Err(err) => return Err(err.to_string()),
+                    }
+                }
+                Some(("--dialect-map", var)) => {
+                    if flags.0 & ArgFlags::DIALECT_MAP != 0 {
+                        return Err("More than 1 dialect map flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::DIALECT_MAP;
+                    parsed_args.dialect_map = Some(var);
+                }
+                Some(("--backend", var)) => {
+                    if flags.0 & ArgFlags::BACKEND != 0 {
+                        return Err("More than 1 backend flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::BACKEND;
+                    parsed_args.backend = Some(var);
+                }
+                Some(("--build-file", var)) => {
+                    if flags.0 & ArgFlags::BUILD_FILE != 0 {
+                        return Err("More than 1 build file flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::BUILD_FILE;
+                    parsed_args.build_file = match var {
+                        "make" => Some(BuildFile::Make),
+                        "ninja" => Some(BuildFile::Ninja),
+                        _ => {
+                            return Err(format!(
+                                "Invalid build file '{}', expected make or ninja",
+                                var
+                            ))
+                        }
+                    };
+                }
+                Some(("--target", var)) => {
+                    if flags.0 & ArgFlags::TARGET != 0 {
+                        return Err("More than 1 target flag passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::TARGET;
+                    parsed_args.target = Some(var)
+                }
+                None => {
+                    if flags.0 & ArgFlags::FILE != 0 {
+                        return Err("More than 1 file passed".to_owned());
+                    }
+                    flags.0 |= ArgFlags::FILE;
+                    parsed_args.file = other;
+                }
+                Some(_) => {
+                    return Err(format!("Invalid argument {}", other));
+                }
+            },
+        }
+    }
+    validate_args(flags, parsed_args, args.len())
+}
+
+fn validate_args(flags: ArgFlags, mut args: Args, len: usize) -> Result<Args, String> {
+    // A dialect map says how to read any file, and the other files are read by their extension
+    if flags.0 & ArgFlags::FILE != 0
+        && args.dialect_map.is_none()
+        && !args.file.ends_with(".bf")
+        && !args.file.ends_with(".ook")
+        && !args.file.ends_with(".png")
+    {
+        return Err(format!(
+            "Invalid file name: '{}'. File must end with .bf, .ook or .png",
+            args.file
+        ));
+    }
+    if args.file.ends_with(".png") && !cfg!(feature = "brainloller") {
+        return Err("Built without the brainloller feature".to_owned());
+    }
+    if (args.dialect_map.is_some() && args.file.ends_with(".ook"))
+        || (args.file.ends_with(".png")
+            && (args.dialect_map.is_some() || args.line_directives || args.annotate))
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // A sketch is the C code without the libc, for a board with a few kilobytes of memory
+    if args.emit == Some(Emit::Arduino) {
+        args.freestanding = true;
+        if flags.0 & ArgFlags::MEM_SIZE == 0 {
+            args.mem_size = ARDUINO_MEM_SIZE;
+        }
+    }
+    if args.offset > args.mem_size {
+        return Err("pointer offset cannot be greater than memory size".to_owned());
+    }
+    if let Some(ExitCell::Cell(cell)) = args.exit_cell {
+        if cell >= args.mem_size {
+            return Err("exit cell must be less than memory size".to_owned());
+        }
+    }
+    // The program that is run is given the input of `--replay-input` too
+    let interpret_only = match flags.0 & ArgFlags::RUN {
+        0 => ArgFlags::INTERPRET_ONLY,
+        _ => ArgFlags::INTERPRET_ONLY & !ArgFlags::REPLAY_INPUT,
+    };
+    if flags.0 & interpret_only != 0 && flags.0 & ArgFlags::COMPILE_ONLY != 0 {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if flags.0 & ArgFlags::COMPILE_ONLY != 0 && flags.0 & ArgFlags::INTERPRET != 0 {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if flags.0 & ArgFlags::OPT_LEVEL != 0 && flags.0 & ArgFlags::PASSES != 0 {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // These need the libc, or a program that is linked and can be run on its own
+    if flags.0 & ArgFlags::FREESTANDING != 0
+        && flags.0
+            & (ArgFlags::DEBUG
+                | ArgFlags::DEBUG_ENV
+                | ArgFlags::CHECKED
+                | ArgFlags::GROW_TAPE
+                | ArgFlags::GETCHAR
+                | ArgFlags::UNBUFFERED
+                | ArgFlags::RUN
+                | ArgFlags::LDFLAGS
+                | ArgFlags::LIBS
+                | ArgFlags::STATIC)
+            != 0
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if let Some(emit) = args.emit {
+        let mut invalid = emit.invalid_flags();
+        // The flags for the compiler go in the build file
+        if args.build_file.is_some() {
+            invalid &= !(ArgFlags::CFLAGS
+                | ArgFlags::TARGET
+                | ArgFlags::LDFLAGS
+                | ArgFlags::LIBS
+                | ArgFlags::STATIC);
+        }
+        if flags.0 & invalid != 0 {
+            return Err("Invalid argument combination".to_owned());
+        }
+    }
+    // The other dialects only have their instructions in the interpreter and the C code
+    if args.dialect != Dialect::Brainfuck
+        && (matches!(
+            args.emit,
+            Some(Emit::Rust | Emit::LlvmIr | Emit::Wasm | Emit::Java)
+        ) || args
+            .compiler
+            .is_some_and(|compiler| is_rustc(compiler) || is_javac(compiler))
+            || flags.0 & (ArgFlags::LLVM | ArgFlags::ASM | ArgFlags::ELF | ArgFlags::AOT) != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The tapes are in the memory of the C code, which the library and the templates give it
+    if (flags.0 & ArgFlags::TAPES != 0 && args.dialect != Dialect::Multitape)
+        || (args.dialect == Dialect::Multitape
+            && (args.emit.is_some_and(Emit::is_lib)
+                || flags.0 & (ArgFlags::GROW_TAPE | ArgFlags::TEMPLATE) != 0))
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The files are opened with the libc, by functions next to the ones of the program
+    if args.dialect == Dialect::Files
+        && (args.freestanding
+            || args.emit.is_some_and(Emit::is_lib)
+            || flags.0 & ArgFlags::TEMPLATE != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The macros are text, which an image does not have
+    if args.macros && (args.dialect == Dialect::Extended1 || args.file.ends_with(".png")) {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if flags.0 & ArgFlags::SEED != 0 && args.dialect != Dialect::Random {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The threads start from the `main` of the C code, and copy the memory it has
+    if (flags.0 & ArgFlags::PTHREADS != 0 && args.dialect != Dialect::Brainfork)
+        || (args.pthreads
+            && (args.freestanding
+                || args.emit.is_some_and(Emit::is_lib)
+                || flags.0 & (ArgFlags::GROW_TAPE | ArgFlags::TEMPLATE) != 0))
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if args.build_file.is_some() && args.emit != Some(Emit::C) {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // rustc only builds programs, from the Rust code
+    if args.compiler.is_some_and(is_rustc)
+        && (args.emit.is_some_and(|emit| emit != Emit::Exe)
+            || flags.0 & (ArgFlags::C_ONLY | ArgFlags::LLVM) != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // And javac, which builds for the JVM
+    if args.compiler.is_some_and(is_javac)
+        && (args.emit.is_some_and(|emit| emit != Emit::Exe)
+            || flags.0 & (ArgFlags::C_ONLY | ArgFlags::LLVM | ArgFlags::TARGET | ArgFlags::STATIC)
+                != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // So do clang and llc from the LLVM IR
+    if args.llvm
+        && (args.emit.is_some_and(|emit| emit != Emit::Exe)
+            || flags.0 & (ArgFlags::C_ONLY | ArgFlags::CHECKED) != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The assembly is the program or what `--emit=asm` writes, and has no debug mode or checks
+    if args.asm.is_some()
+        && (args
+            .emit
+            .is_some_and(|emit| !matches!(emit, Emit::Exe | Emit::Asm))
+            || flags.0
+                & (ArgFlags::C_ONLY
+                    | ArgFlags::CHECKED
+                    | ArgFlags::DEBUG
+                    | ArgFlags::DEBUG_ENV
+                    | ArgFlags::LLVM
+                    | ArgFlags::COMPILER)
+                != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if args.asm.is_some() {
+        asm_target(&args)?;
+    }
+    // The executable is all there is, there is no code to keep or to build differently
+    if args.elf
+        && (args.emit.is_some_and(|emit| emit != Emit::Exe)
+            || flags.0
+                & (ArgFlags::C_ONLY
+                    | ArgFlags::CHECKED
+                    | ArgFlags::DEBUG
+                    | ArgFlags::DEBUG_ENV
+                    | ArgFlags::LLVM
+                    | ArgFlags::ASM
+                    | ArgFlags::COMPILER
+                    | ArgFlags::KEEP)
+                != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // The object file is the code of `--elf`, which is only linked, and kept with --keep
+    if args.aot
+        && (args.emit.is_some_and(|emit| emit != Emit::Exe)
+            || flags.0
+                & (ArgFlags::C_ONLY
+                    | ArgFlags::CHECKED
+                    | ArgFlags::DEBUG
+                    | ArgFlags::DEBUG_ENV
+                    | ArgFlags::LLVM
+                    | ArgFlags::ASM
+                    | ArgFlags::ELF
+                    | ArgFlags::COMPILER)
+                != 0)
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if args.elf || args.aot {
+        elf_target(&args)?;
+    }
+    // The backend only writes the code, with the settings every backend has
+    if flags.0 & ArgFlags::BACKEND != 0
+        && flags.0
+            & (ArgFlags::EMIT
+                | ArgFlags::BUILD_EMIT
+                | ArgFlags::RUN
+                | ArgFlags::KEEP
+                | ArgFlags::COMPILER
+                | ArgFlags::C_ONLY
+                | ArgFlags::CHECKED
+                | ArgFlags::GETCHAR
+                | ArgFlags::LLVM
+                | ArgFlags::ASM
+                | ArgFlags::ELF
+                | ArgFlags::AOT
+                | ArgFlags::TARGET
+                | ArgFlags::STATIC)
+            != 0
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // A program for another machine cannot run on this one
+    if flags.0 & ArgFlags::TARGET != 0 && flags.0 & ArgFlags::RUN != 0 {
+        return Err("Invalid argument combination".to_owned());
+    }
+    // These write files next to the output
+    if flags.0 & ArgFlags::SCRIPT != 0
+        && flags.0
+            & (ArgFlags::OUTPUT
+                | ArgFlags::KEEP
+                | ArgFlags::EMIT
+                | ArgFlags::BUILD_EMIT
+                | ArgFlags::LINE_DIRECTIVES)
+            != 0
+    {
+        return Err("Invalid argument combination".to_owned());
+    }
+    if flags.0 & ArgFlags::SCRIPT != 0 && flags.0 & ArgFlags::FILE == 0 {
+        return Err("No File passed".to_owned());
+    }
+    if args.emit.is_some_and(Emit::is_lib) && flags.0 & ArgFlags::OUTPUT == 0 {
+        args.output = "bf_program";
+    }
+    if (flags.0 & !ArgFlags::CONSOLE == 0 && len <= 3) || flags.0 == 0 {
+        args.console = true;
+        return Ok(args);
+    }
+    if flags.0 & !ArgFlags::NO_FILE != 0 && flags.0 & ArgFlags::FILE == 0 {
+        return Err("No File passed".to_owned());
+    }
+    Ok(args)
+}
+
+/// The mask of the passes in a comma separated list of their names
+fn parse_passes(list: &str) -> Result<u32, String> {
+    let mut names = list.split(',').filter(|name| !name.is_empty());
+    names.try_fold(0, |passes, name| match ir::pass_mask(name) {
+        Some(mask) => Ok(passes | mask),
+        None => Err(format!(
+            "Unknown pass '{}', expected one of {}",
+            name,
+            pass_names()
+        )),
+    })
+}
+
+fn dialect_names() -> String {
+    let names = Dialect::ALL.iter().map(|dialect| dialect.name());
+    names.collect::<Vec<_>>().join(", ")
+}
+
+fn pass_names() -> String {
+    let names = ir::PASSES.iter().map(|pass| pass.name);
+    names.collect::<Vec<_>>().join(", ")
+}
+
+pub fn get_code(args: &Args) -> Result<String, String> {
+    let mut contents = read_source(args.file)?;
+    let shebang = split_shebang(&mut contents);
+    if args.macros {
+        contents = macros::expand(&contents, shebang.is_some() as usize)?;
+    }
+    match args.dialect_map {
+        Some(path) => {
+            let map = fs::read_to_string(path).map_err(|err| err.to_string())?;
+            contents = DialectMap::parse(&map)?.translate(&contents);
+        }
+        None => front_end(args.file, &mut contents)?,
+    }
+    if args.strict {
+        check_strict(
+            &contents,
+            args.debug,
+            args.dialect,
+            shebang.is_some() as usize,
+        )?;
+    }
+
+    let end = input_separator(&contents, args.dialect).unwrap_or(contents.len());
+    let code = &contents[..end];
+    if code.matches('[').count() != code.matches(']').count() {
+        return Err("Unbalanced Brackets".to_string());
+    }
+    Ok(contents)
+}
+
+/// The text of the file at `path`, or the Brainfuck code of a Brainloller image
+fn read_source(path: &str) -> Result<String, String> {
+    if path.ends_with(".png") {
+        #[cfg(feature = "brainloller")]
+        return crate::brainloller::translate(&fs::read(path).map_err(|err| err.to_string())?);
+        #[cfg(not(feature = "brainloller"))]
+        return Err("Built without the brainloller feature".to_owned());
+    }
+    fs::read_to_string(path).map_err(|err| err.to_string())
+}
+
+/// Turns the code of the languages that are not Brainfuck into Brainfuck, by the extension of
+/// `path`
+fn front_end(path: &str, contents: &mut String) -> Result<(), String> {
+    if path.ends_with(".ook") {
+        *contents = ook::translate(contents)?;
+    }
+    Ok(())
+}
+
+/// Rejects every character that is neither an instruction nor whitespace, reporting its line:column.
+/// `first_line` is the number of lines already removed from the start of the file
+pub fn check_strict(
+    contents: &str,
+    debug: bool,
+    dialect: Dialect,
+    first_line: usize,
+) -> Result<(), String> {
+    let end = input_separator(contents, dialect).unwrap_or(contents.len());
+    for (line, text) in contents[..end].lines().enumerate() {
+        for (column, ch) in text.chars().enumerate() {
+            if ch.is_whitespace()
+                || "<>[]+-.,".contains(ch)
+                || dialect.instructions().contains(ch)
+                || (debug && "#|".contains(ch))
+            {
+                continue;
+            }
+            return Err(format!(
+                "Invalid character '{}' at {}:{}",
+                ch.escape_default(),
+                first_line + line + 1,
+                column + 1
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// How many lines [`split_shebang`] took off the top of the file at `path`
+fn shebang_lines(path: &str) -> io::Result<usize> {
+    Ok(fs::read_to_string(path)?.starts_with("#!") as usize)
+}
+
+/// Removes a leading `#!...` line so that `.bf` files can be made executable
+pub fn split_shebang(contents: &mut String) -> Option<String> {
+    if !contents.starts_with("#!") {
+        return None;
+    }
+    let end = contents.find('\n').map_or(contents.len(), |pos| pos + 1);
+    Some(contents.drain(..end).collect())
+}
+
+/// Where the first top-level `!` is, if `!` is not an instruction of `dialect`
+fn input_separator(contents: &str, dialect: Dialect) -> Option<usize> {
+    if dialect.instructions().contains('!') {
+        return None;
+    }
+    let mut depth = 0usize;
+    for (pos, code) in contents.char_indices() {
+        match code {
+            '[' => depth += 1,
+            ']' => depth = depth.saturating_sub(1),
+            '!' if depth == 0 => return Some(pos),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Splits off everything after the first top-level `!`, which is used as the input for `,`.
+/// There is no input in the source of a dialect where `!` is an instruction
+pub fn split_input(contents: &mut String, dialect: Dialect) -> Option<Vec<u8>> {
+    let pos = input_separator(contents, dialect)?;
+    let input = contents.as_bytes()[pos + 1..].to_vec();
+    contents.truncate(pos);
+    Some(input)
+}
+
+/// Splits off the input after the `!` in `contents` like [`split_input`], followed by the one
+/// of `--input-str`
+fn given_input(contents: &mut String, args: &Args) -> Vec<u8> {
+    let mut input = split_input(contents, args.dialect).unwrap_or_default();
+    input.extend(args.input_str.unwrap_or_default().bytes());
+    input
+}
+
+/// Reads single keypresses from the raw terminal and writes straight to stdout
+pub struct Terminal {
+    getch: Getch,
+}
+
+impl Terminal {
+    pub fn new() -> io::Result<Self> {
+        Ok(Self {
+            getch: Getch::new()?,
+        })
+    }
+}
+
+impl BfIo for Terminal {
+    fn read(&mut self) -> Option<u8> {
+        self.getch.getch().ok()
+    }
+    fn write(&mut self, b: u8) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(&[b]).and_then(|_| stdout.flush());
+    }
+}
+
+/// Buffered stdin and stdout, for when the input does not come from a terminal
+pub struct StdIo {
+    stdout: io::BufWriter<io::Stdout>,
+}
+
+impl StdIo {
+    pub fn new() -> Self {
+        Self {
+            stdout: io::BufWriter::new(io::stdout()),
+        }
+    }
+}
+
+impl Default for StdIo {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BfIo for StdIo {
+    fn read(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        match io::stdin().read(&mut byte) {
+            Ok(1) => Some(byte[0]),
+            _ => None,
+        }
+    }
+    fn write(&mut self, b: u8) {
+        let _ = self.stdout.write_all(&[b]);
+    }
+    fn flush(&mut self) {
+        let _ = self.stdout.flush();
+    }
+}
+
+/// Adapts any reader and writer to [`BfIo`], keeping the first error that happens
+pub struct ReadWrite<R, W> {
+    pub input: R,
+    pub output: W,
+    pub error: Option<io::Error>,
+}
+
+impl<R: Read, W: Write> ReadWrite<R, W> {
+    pub fn new(input: R, output: W) -> Self {
+        Self {
+            input,
+            output,
+            error: None,
+        }
+    }
+}
+
+impl<R: Read, W: Write> BfIo for ReadWrite<R, W> {
+    fn read(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        match self.input.read(&mut byte) {
+            Ok(0) => None,
+            Ok(_) => Some(byte[0]),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+    fn write(&mut self, b: u8) {
+        if let Err(err) = self.output.write_all(&[b]) {
+            self.error.get_or_insert(err);
+        }
+    }
+    fn flush(&mut self) {
+        if let Err(err) = self.output.flush() {
+            self.error.get_or_insert(err);
+        }
+    }
+}
+
+/// Replays the input from `--replay-input` and records what is read afterwards to
+/// `--record-input`. It has the files of the files dialect too
+struct Session<T> {
+    io: T,
+    replay: std::vec::IntoIter<u8>,
+    record: Option<File>,
+    /// The files `;` and `:` of the files dialect read and write
+    reading: Option<io::BufReader<File>>,
+    writing: Option<io::BufWriter<File>>,
+    error: Option<io::Error>,
+}
+
+impl Session<Box<dyn BfIo>> {
+    /// Uses the raw terminal if there is one and buffered stdio otherwise
+    fn new(args: &Args) -> Result<Self, String> {
+        let io: Box<dyn BfIo> = if io::stdin().is_terminal() {
+            Box::new(Terminal::new().map_err(|err| err.to_string())?)
+        } else {
+            Box::new(StdIo::new())
+        };
+        let replay = match args.replay_input {
+            Some(file) => fs::read(file).map_err(|err| format!("{}: {}", file, err))?,
+            None => Vec::new(),
+        };
+        let record = match args.record_input {
+            Some(file) => Some(File::create(file).map_err(|err| format!("{}: {}", file, err))?),
+            None => None,
+        };
+        Ok(Self {
+            io,
+            replay: replay.into_iter(),
+            record,
+            reading: None,
+            writing: None,
+            error: None,
+        })
+    }
+}
+
+impl<T: BfIo> BfIo for Session<T> {
+    fn read(&mut self) -> Option<u8> {
+        if let Some(byte) = self.replay.next() {
+            return Some(byte);
+        }
+        let byte = self.io.read()?;
+        if let Some(record) = &mut self.record {
+            if let Err(err) = record.write_all(&[byte]) {
+                self.error.get_or_insert(err);
+            }
+        }
+        Some(byte)
+    }
+    fn write(&mut self, b: u8) {
+        self.io.write(b)
+    }
+    fn flush(&mut self) {
+        self.io.flush();
+        if let Some(file) = &mut self.writing {
+            if let Err(err) = file.flush() {
+                self.error.get_or_insert(err);
+            }
+        }
+    }
+    fn open(&mut self, name: &[u8], write: bool) -> bool {
+        if let (true, Some(mut file)) = (write, self.writing.take()) {
+            if let Err(err) = file.flush() {
+                self.error.get_or_insert(err);
+            }
+        }
+        if !write {
+            self.reading = None;
+        }
+        if name.is_empty() {
+            return false;
+        }
+        // A file that can't be opened is a 0 for the program to handle, not an error
+        let path = String::from_utf8_lossy(name).into_owned();
+        match write {
+            true => {
+                self.writing = File::create(path).ok().map(io::BufWriter::new);
+                self.writing.is_some()
+            }
+            false => {
+                self.reading = File::open(path).ok().map(io::BufReader::new);
+                self.reading.is_some()
+            }
+        }
+    }
+    fn read_file(&mut self) -> Option<u8> {
+        let mut byte = [0];
+        match self.reading.as_mut()?.read(&mut byte) {
+            Ok(0) => None,
+            Ok(_) => Some(byte[0]),
+            Err(err) => {
+                self.error.get_or_insert(err);
+                None
+            }
+        }
+    }
+    fn write_file(&mut self, b: u8) {
+        if let Some(file) = &mut self.writing {
+            if let Err(err) = file.write_all(&[b]) {
+                self.error.get_or_insert(err);
+            }
+        }
+    }
+}
+
+pub fn interpret(contents: String, args: Args) -> Result<i32, String> {
+    let mut session = Session::new(&args)?;
+    println!("\n\x1b[90m--------------\x1b[0m\x1b[96mOUTPUT\x1b[0m\x1b[90m--------------\x1b[0m\n");
+    let result = interpret_with(&contents, &args, &mut session);
+    session.flush();
+    println!("\n\x1b[90m----------------------------------\x1b[0m");
+    if let Some(err) = session.error {
+        return Err(err.to_string());
+    }
+    Ok(result?)
+}
+
+/// Interprets `contents` using `io` for `,` and `.`, and returns the exit code.
+/// Once the input runs out `,` sets the cell to 0
+pub fn interpret_with(contents: &str, args: &Args, io: &mut impl BfIo) -> Result<i32, BfError> {
+    let mut contents = contents.to_owned();
+    let embedded = given_input(&mut contents, args);
+    let mut interpreter = if args.cache && !args.file.is_empty() {
+        cached_interpreter(&contents, args)?
+    } else {
+        Interpreter::new(&contents, args.config())?
+    };
+    interpreter.feed(&embedded);
+    if args.profile_out.is_some() {
+        interpreter.enable_profile();
+    }
+    run_to_end(&mut interpreter, io, args)?;
+    io.flush();
+    if let (Some(path), Some(profile)) = (args.profile_out, interpreter.profile()) {
+        write_profile(path, &profile)?;
+    }
+    Ok(interpreter.exit_code())
+}
+
+/// The first line of every profile file
+const PROFILE_HEADER: &str = "brainfuck profile 1";
+
+/// Saves a profile as one `bracket entries iterations` line per loop
+fn write_profile(path: &str, profile: &[LoopCount]) -> io::Result<()> {
+    let mut file = io::BufWriter::new(File::create(path)?);
+    writeln!(file, "{}", PROFILE_HEADER)?;
+    for (bracket, count) in profile.iter().enumerate() {
+        writeln!(file, "{} {} {}", bracket, count.entries, count.iterations)?;
+    }
+    file.flush()
+}
+
+fn read_profile(path: &str) -> Result<Vec<LoopCount>, String> {
+    let contents = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    let mut lines = contents.lines();
+    if lines.next() != Some(PROFILE_HEADER) {
+        return Err(format!("{} is not a profile", path));
+    }
+    let mut profile = Vec::new();
+    for (i, line) in lines.enumerate() {
+        let numbers = line
+            .split_whitespace()
+            .map(str::parse::<u64>)
+            .collect::<Result<Vec<_>, _>>();
+        match numbers.as_deref() {
+            Ok(&[bracket, entries, iterations]) if bracket == i as u64 => profile.push(LoopCount {
+                entries,
+                iterations,
+            }),
+            _ => return Err(format!("Invalid profile line {}: '{}'", i + 2, line)),
+        }
+    }
+    Ok(profile)
+}
+
+/// The cells of `--emit=arduino` without `-m`, which leave an Arduino Uno half of its 2 KiB of
+/// SRAM for the stack and the serial port
+const ARDUINO_MEM_SIZE: usize = 1024;
+
+/// Takes the instructions from the `.bfc` file next to the source when it was made from
+/// `contents`, otherwise lowers them again and writes that file
+fn cached_interpreter(contents: &str, args: &Args) -> Result<Interpreter, BfError> {
+    let path = format!("{}c", args.file);
+    let config = args.config();
+    let hash = bytecode::hash(contents, &config);
+    let cached = fs::read(&path)
+        .ok()
+        .and_then(|bytes| bytecode::decode(&bytes).ok());
+    if let Some((_, code)) = cached.filter(|&(cached, _)| cached == hash) {
+        return Ok(Interpreter::from_code(contents, code, config));
+    }
+    let interpreter = Interpreter::new(contents, config)?;
+    fs::write(&path, bytecode::encode(interpreter.code(), hash))?;
+    Ok(interpreter)
+}
+
+/// The text `--emit` prints for `contents`
+pub fn emit(mut contents: String, args: &Args) -> Result<String, String> {
+    split_input(&mut contents, args.dialect);
+    let config = args.config();
+    match args.emit {
+        Some(Emit::Ir) | None => {
+            let nodes = ir::optimize_with(ir::parse(&contents, &config)?, &config)?;
+            let text = ir::dump(&nodes).to_string();
+            Ok(text)
+        }
+        Some(Emit::Ast) => {
+            let tree = ast::parse(&contents, &config)?;
+            let text = ast::dump(&contents, &tree).to_string();
+            Ok(text)
+        }
+        Some(_) => Err("Only the IR and the AST are printed, the rest is compiled".to_owned()),
+    }
+}
+
+/// What the optimization passes change in `contents`, as text
+pub fn opt_report(mut contents: String, args: &Args) -> Result<String, String> {
+    split_input(&mut contents, args.dialect);
+    let config = args.config();
+    let (_, report) = ir::optimize_with_report(ir::parse(&contents, &config)?, &config)?;
+    Ok(report.to_string())
+}
+
+/// Prints the instructions of the file of `args`, lowered with its settings like `--cache` does,
+/// or of a `.bfc` file made with it, one per line after their index
+pub fn disasm(args: &Args) -> Result<(), String> {
+    let code = if args.file.ends_with(".bfc") {
+        let bytes = fs::read(args.file).map_err(|err| err.to_string())?;
+        bytecode::decode(&bytes)?.1
+    } else {
+        let mut contents = get_code(args)?;
+        split_input(&mut contents, args.dialect);
+        bytecode::lower(&contents, &args.config())?
+    };
+    for (i, instr) in code.iter().enumerate() {
+        println!("{:>6}  {}", i, instr);
+    }
+    Ok(())
+}
+
+/// How many instructions `equiv` runs each program for on every input
+const EQUIV_STEPS: usize = 1_000_000;
+
+/// Compares the programs of the files of `a` and `b` on many inputs, and prints the first one they
+/// differ on. Both run with the settings of `a`, which is parsed from the same options as `b`.
+/// Returns whether they agreed on all of them
+pub fn equiv(a: &Args, b: &Args) -> Result<bool, String> {
+    let read = |args: &Args| -> Result<String, String> {
+        let mut contents = get_code(args)?;
+        split_input(&mut contents, args.dialect);
+        Ok(contents)
+    };
+    let (first, second) = (read(a)?, read(b)?);
+    let config = a.config();
+    let (a, b) = (a.file, b.file);
+    match crate::equiv::check(&first, &second, config, EQUIV_STEPS)? {
+        None => {
+            println!("{} and {} agree on every input tried", a, b);
+            Ok(true)
+        }
+        Some(difference) => {
+            println!(
+                "{} and {} differ on the input \"{}\"",
+                a,
+                b,
+                difference.input.escape_ascii()
+            );
+            for (filename, outcome) in [a, b].iter().zip(&difference.outcomes) {
+                println!(
+                    "  {} wrote \"{}\" and {}",
+                    filename,
+                    outcome.output.escape_ascii(),
+                    outcome.end
+                );
+            }
+            Ok(false)
+        }
+    }
+}
+
+/// Interprets `contents`, reading `,` from `input` and writing `.` to `output`, and returns the
+/// exit code
+pub fn interpret_with_io(
+    contents: &str,
+    args: &Args,
+    input: impl Read,
+    output: impl Write,
+) -> Result<i32, BfError> {
+    let mut io = ReadWrite::new(input, output);
+    let result = interpret_with(contents, args, &mut io);
+    match io.error {
+        Some(err) => Err(err.into()),
+        None => result,
+    }
+}
+
+/// Interprets `contents` without touching the terminal, reading `,` from `input` and
+/// returning everything written by `.`
+pub fn interpret_to_vec(contents: &str, args: &Args, input: &[u8]) -> Result<Vec<u8>, BfError> {
+    let mut output = Vec::new();
+    interpret_with_io(contents, args, input, &mut output)?;
+    Ok(output)
+}
+
+/// Runs until the program ends, reading 0 once the input runs out. With `--step-delay`
+/// the interpreter is slowed down so that the execution can be followed
+fn run_to_end(
+    interpreter: &mut Interpreter,
+    io: &mut impl BfIo,
+    args: &Args,
+) -> Result<(), BfError> {
+    #[cfg(feature = "jit")]
+    if args.jit && args.step_delay.is_none() && args.profile_out.is_none() {
+        if let Some(result) = interpreter.run_jit(io) {
+            return result;
+        }
+    }
+    loop {
+        let state = match args.step_delay {
+            Some(delay) => {
+                if let (Some(code), true) = (interpreter.current(), args.debug) {
+                    let place = match args.dialect {
+                        Dialect::Multitape => format!(" of tape {}", interpreter.tape()),
+                        Dialect::Brainfork => format!(" in thread {}", interpreter.thread()),
+                        _ => String::new(),
+                    };
+                    eprintln!(
+                        "\x1b[90mstep {} : {:?} at cell {}{} ({})\x1b[0m",
+                        interpreter.codeptr(),
+                        code,
+                        interpreter.cellptr(),
+                        place,
+                        interpreter.mem()[interpreter.cellptr()]
+                    );
+                }
+                thread::sleep(delay);
+                interpreter.run_steps(io, 1)?
+            }
+            None => interpreter.run(io)?,
+        };
+        match state {
+            State::NeedsInput => interpreter.feed(&[0]),
+            State::Paused => (),
+            State::Halted => return Ok(()),
+        }
+    }
+}
+
+/// The C compiler failed to build the C file
+#[derive(Debug)]
+pub struct CompilerError {
+    pub compiler: String,
+    /// The exit code of the compiler, or `None` if a signal stopped it
+    pub code: Option<i32>,
+    /// The C file, or the Rust file for rustc, which is kept
+    pub c_file: String,
+}
+
+impl fmt::Display for CompilerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.code {
+            Some(code) => write!(f, "{} failed with exit code {}", self.compiler, code)?,
+            None => write!(f, "{} was stopped by a signal", self.compiler)?,
+        }
+        write!(f, ", the code is kept in {}", self.c_file)
+    }
+}
+
+impl Error for CompilerError {}
+
+/// The compiler that builds for `target`, with the flags it needs for it before the C file
+fn target_compiler(compiler: &str, args: &Args) -> Result<(String, Vec<String>), String> {
+    let name = Path::new(compiler)
+        .file_name()
+        .map_or(compiler.into(), |name| name.to_string_lossy());
+    // zig brings the musl libc along, which can be linked statically for this machine too
+    let musl = format!("{}-linux-musl", env::consts::ARCH);
+    let target = match args.target {
+        None if name == "zig" && args.static_link && cfg!(target_os = "linux") => Some(&*musl),
+        target => target,
+    };
+    let Some(target) = target else {
+        // zig is only a C compiler behind `zig cc`
+        return match &*name {
+            "zig" => Ok((compiler.to_owned(), vec!["cc".to_owned()])),
+            _ => Ok((compiler.to_owned(), Vec::new())),
+        };
+    };
+    match &*name {
+        _ if name.contains("clang") => {
+            Ok((compiler.to_owned(), vec![format!("--target={}", target)]))
+        }
+        "zig" => Ok((
+            compiler.to_owned(),
+            vec!["cc".to_owned(), "-target".to_owned(), target.to_owned()],
+        )),
+        // Every gcc only builds for one target, so the cross compilers are named after it
+        "gcc" | "cc" => Ok((format!("{}-gcc", target), Vec::new())),
+        _ if name.starts_with(target) => Ok((compiler.to_owned(), Vec::new())),
+        _ if name.contains("gcc") => Ok((format!("{}-{}", target, name), Vec::new())),
+        _ => Err(format!("Cannot tell {} to build for {}", compiler, target)),
+    }
+}
+
+/// The first compiler that is installed, with the flags of [`target_compiler`]. Without
+/// `--compiler`, it is the one in `CC` like for make, or else gcc, clang and cc, then cl on
+/// Windows and then the libtcc built in with the `tcc` feature
+fn find_compiler(args: &Args) -> Result<(String, Vec<String>), String> {
+    let cc = env::var("CC").unwrap_or_default();
+    // `CC` can have flags after the compiler, which go before the ones for the target
+    let mut words = cc.split_whitespace();
+    if let (None, Some(compiler)) = (args.compiler, words.next()) {
+        let (compiler, mut target_args) = target_compiler(compiler, args)?;
+        if !find_program(&compiler) {
+            return Err(format!("{}, the compiler in CC, was not found", compiler));
+        }
+        target_args.extend(words.map(str::to_owned));
+        return Ok((compiler, target_args));
+    }
+    let mut candidates = match args.compiler {
+        Some(compiler) => vec![compiler],
+        None => vec!["gcc", "clang", "cc"],
+    };
+    if args.compiler.is_none() && cfg!(windows) {
+        candidates.push("cl");
+    }
+    // The libtcc built in, which only builds for this machine
+    if args.compiler.is_none() && cfg!(feature = "tcc") && args.target.is_none() {
+        candidates.push("libtcc");
+    }
+    let mut tried: Vec<String> = Vec::new();
+    for candidate in candidates {
+        let (compiler, target_args) = target_compiler(candidate, args)?;
+        if find_program(&compiler) || (cfg!(feature = "tcc") && compiler == "libtcc") {
+            if !tried.is_empty() {
+                println!("\x1b[1mCould not find {}\x1b[0m", tried.join(", "));
+            }
+            return Ok((compiler, target_args));
+        }
+        if !tried.contains(&compiler) {
+            tried.push(compiler);
+        }
+    }
+    Err(format!(
+        "No C compiler was found, tried {}",
+        tried.join(", ")
+    ))
+}
+
+/// The flags in the environment variable `name`, like `CFLAGS` for make, followed by `given`
+fn env_flags(name: &str, given: &str) -> String {
+    let mut flags = env::var(name).unwrap_or_default();
+    flags.push(' ');
+    flags + given
+}
+
+/// Whether `program` is a file, looked for in the `PATH` like the shell does if it is only a name
+fn find_program(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| {
+            let file = dir.join(program);
+            file.is_file() || (cfg!(windows) && file.with_extension("exe").is_file())
+        })
+    })
+}
+
+/// Whether `compiler` is the `cl` of MSVC, which takes flags of its own
+fn is_msvc(compiler: &str) -> bool {
+    Path::new(compiler)
+        .file_stem()
+        .is_some_and(|name| name.eq_ignore_ascii_case("cl"))
+}
+
+/// Whether `compiler` is rustc, which builds the Rust code instead of the C code
+fn is_rustc(compiler: &str) -> bool {
+    Path::new(compiler)
+        .file_stem()
+        .is_some_and(|name| name == "rustc")
+}
+
+/// Whether `compiler` is javac, which builds the Java code instead of the C code
+fn is_javac(compiler: &str) -> bool {
+    Path::new(compiler)
+        .file_stem()
+        .is_some_and(|name| name == "javac")
+}
+
+/// Whether `compiler` is llc, which only builds the LLVM IR into an object file
+fn is_llc(compiler: &str) -> bool {
+    Path::new(compiler)
+        .file_stem()
+        .is_some_and(|name| name == "llc")
+}
+
+/// The flags for `cl` that the ones for gcc in [`compile`] are for the others
+fn msvc_args(command: &mut Command, args: &Args, c_file: &Path, binary: &str) {
+    command.arg("/nologo");
+    command.arg(c_file);
+    if let Some(dir) = include_dir(args) {
+        command.arg(format!("/I{}", dir));
+    }
+    match args.emit {
+        // cl writes the assembly next to the object file, which is left in the build directory
+        Some(Emit::Asm) => command.args([
+            "/c".to_owned(),
+            format!("/Fa{}", binary),
+            format!("/Fo{}", c_file.with_extension("obj").display()),
+        ]),
+        _ if object(args) => command.args(["/c".to_owned(), format!("/Fo{}", binary)]),
+        _ => command.arg(format!("/Fe{}", binary)),
+    };
+    if args.emit == Some(Emit::CDylib) {
+        command.arg("/LD");
+    }
+    // The C runtime is the only library MSVC links by default
+    if args.static_link {
+        command.arg("/MT");
+    }
+    match args.release {
+        true => command.arg("/O2"),
+        false => command.arg("/Zi"),
+    };
+    command.args(env_flags("CFLAGS", args.cflags).split_whitespace());
+    let libs = args.libs.split(',').filter(|lib| !lib.is_empty());
+    command.args(libs.map(|lib| format!("{}.lib", lib)));
+    // Everything after /link goes to the linker
+    let ldflags = env_flags("LDFLAGS", args.ldflags);
+    if !ldflags.trim().is_empty() {
+        command.arg("/link");
+        command.args(ldflags.split_whitespace());
+    }
+}
+
+/// Where the headers the C file includes are, which are not next to it in the directory it is
+/// built in: the one of the library, or the ones of the template
+fn include_dir(args: &Args) -> Option<String> {
+    match args.emit {
+        Some(emit) if emit.is_lib() => {
+            let dir = Path::new(args.output).parent().unwrap_or(Path::new(""));
+            match dir.as_os_str().is_empty() {
+                true => Some(".".to_owned()),
+                false => Some(dir.to_string_lossy().into_owned()),
+            }
+        }
+        _ => args.template.map(|_| ".".to_owned()),
+    }
+}
+
+/// Whether the compiler stops at an object file. Without the hooks of `--freestanding` the code
+/// cannot be linked, so it becomes an object file to link them with
+fn object(args: &Args) -> bool {
+    args.freestanding || matches!(args.emit, Some(Emit::Obj | Emit::StaticLib))
+}
+
+/// Puts `object` into the static library of `--emit=staticlib`, with `lib` for MSVC
+fn archive(args: &Args, object: &str, msvc: bool) -> Result<(), Box<dyn Error>> {
+    let path = Path::new(args.output);
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut command = match msvc {
+        true => {
+            let mut command = Command::new("lib");
+            let library = path.with_file_name(format!("{}.lib", name));
+            command
+                .arg("/nologo")
+                .arg(format!("/OUT:{}", library.display()));
+            command
+        }
+        false => {
+            let mut command = Command::new(env::var("AR").unwrap_or("ar".to_owned()));
+            command
+                .arg("rcs")
+                .arg(path.with_file_name(format!("lib{}.a", name)));
+            command
+        }
+    };
+    let output = command.arg(object).output()?;
+    io::stdout().write_all(&output.stdout)?;
+    io::stdout().write_all(&output.stderr)?;
+    match output.status.success() {
+        true => Ok(()),
+        false => Err(format!("Making the static library failed with {}", output.status).into()),
+    }
+}
+
+/// The file the compiler writes the program to
+fn binary_name(args: &Args) -> String {
+    let windows = match args.target {
+        Some(target) => target.contains("windows") || target.contains("mingw"),
+        None => cfg!(windows),
+    };
+    match args.emit {
+        Some(Emit::Asm) => [args.output, ".s"].concat(),
+        Some(Emit::Obj) => [args.output, ".o"].concat(),
+        Some(Emit::CDylib) => {
+            let path = Path::new(args.output);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            let apple = match args.target {
+                Some(target) => target.contains("apple") || target.contains("darwin"),
+                None => cfg!(target_os = "macos"),
+            };
+            let file = match (windows, apple) {
+                (true, _) => format!("{}.dll", name),
+                (false, true) => format!("lib{}.dylib", name),
+                (false, false) => format!("lib{}.so", name),
+            };
+            path.with_file_name(file).to_string_lossy().into_owned()
+        }
+        _ if args.compiler.is_some_and(is_javac) => [args.output, ".jar"].concat(),
+        _ if windows && !args.freestanding => [args.output, ".exe"].concat(),
+        _ => args.output.to_owned(),
+    }
+}
+
+/// Compiles `contents` into what `--emit` says, which is a program by default. Every step
+/// starts from the C code, or the Rust code or LLVM IR, and then stops at it, at the assembly or
+/// object file the C compiler makes of it, or at what is linked
+pub fn compile(contents: String, args: Args) -> Result<(), Box<dyn Error>> {
+    compile_with(contents, args, &Registry::new())
+}
+
+/// [`compile`], with the backends of `registry` for `--backend`
+pub fn compile_with(
+    contents: String,
+    args: Args,
+    registry: &Registry,
+) -> Result<(), Box<dyn Error>> {
+    if let Some(name) = args.backend {
+        return write_artifact(contents, &args, registry, name);
+    }
+    if args.elf {
+        return write_elf(contents, &args);
+    }
+    #[cfg(feature = "aot")]
+    if args.aot {
+        return write_object(contents, &args);
+    }
+    if args.emit == Some(Emit::Wasm) {
+        return write_wasm(contents, &args);
+    }
+    if args.emit == Some(Emit::Bf) {
+        return write_bf(contents, &args);
+    }
+    let emit = args.emit.unwrap_or(Emit::Exe);
+    // rustc builds the same program from the Rust code, and clang or llc from the LLVM IR
+    let language = match emit {
+        Emit::Rust => Language::Rust,
+        Emit::LlvmIr => Language::LlvmIr,
+        Emit::Java => Language::Java,
+        _ if args.compiler.is_some_and(is_rustc) => Language::Rust,
+        _ if args.compiler.is_some_and(is_javac) => Language::Java,
+        _ if args.llvm => Language::LlvmIr,
+        _ => match args.asm {
+            Some(syntax) => Language::Asm(syntax),
+            None => Language::C,
+        },
+    };
+    let c_code = match language {
+        Language::C => c_code(contents, &args)?,
+        _ => program_code(contents, &args, language)?,
+    };
+    let extension = match emit {
+        Emit::Arduino => ".ino",
+        _ => language.extension(),
+    };
+    if emit.is_lib() {
+        println!("\x1b[1mCreating the header...\x1b[0m");
+        let header = c::lib_header(&compile_options(&args, false, &[]), &c_flags(&args));
+        fs::write([args.output, ".h"].concat(), header)?;
+    }
+    // The code is all there is to these, the library is built with the program it goes in
+    let written = match language {
+        Language::C => emit.is_c(),
+        _ => emit != Emit::Exe,
+    };
+    if written {
+        println!("\x1b[1mCreating the {} file...\x1b[0m", language.name());
+        fs::write([args.output, extension].concat(), c_code)?;
+        if let Some(kind) = args.build_file {
+            println!("\x1b[1mCreating the build file...\x1b[0m");
+            write_build_file(&args, kind)?;
+        }
+        return Ok(());
+    }
+    // Only the program ends up next to the output, and builds of the same output at the same time
+    // do not write over each other's C file
+    let dir = BuildDir::new()?;
+    let name = Path::new(args.output).file_name().unwrap_or_default();
+    let c_file = dir.0.join(name).with_extension(&extension[1..]);
+    println!("\x1b[1mCreating the {} file...\x1b[0m", language.name());
+    let mut cpp_file = File::create(&c_file)?;
+    cpp_file.write_all(c_code.as_bytes())?;
+
+    // The object file only goes in the static library, and `brainfuck run` leaves nothing behind
+    let binary = match emit {
+        Emit::StaticLib => c_file.with_extension("o").to_string_lossy().into_owned(),
+        _ if args.script => dir
+            .0
+            .join(binary_name(&args))
+            .to_string_lossy()
+            .into_owned(),
+        _ => binary_name(&args),
+    };
+
+    let (compiler, target_args) = match (language, args.compiler) {
+        (Language::Rust | Language::Java, Some(compiler)) => match find_program(compiler) {
+            true => (compiler.to_owned(), Vec::new()),
+            false => return Err(format!("{} was not found", compiler).into()),
+        },
+        (Language::LlvmIr, _) => llvm_compiler(&args)?,
+        (Language::Asm(syntax), _) => (assembler(&args, syntax)?, Vec::new()),
+        _ => find_compiler(&args)?,
+    };
+    println!(
+        "\x1b[1mCompiling the {} file using {}...\x1b[0m",
+        language.name(),
+        compiler
+    );
+    let msvc = is_msvc(&compiler);
+    let built = match (&*compiler, language) {
+        #[cfg(feature = "tcc")]
+        ("libtcc", _) => build_with_libtcc(&c_code, &args, &binary).map_err(Into::into),
+        (_, Language::Rust) => build_with_rustc(&args, compiler, &c_file, &binary),
+        (_, Language::Java) => build_with_javac(&args, compiler, &c_file, &binary),
+        (_, Language::LlvmIr) => build_llvm_ir(&args, compiler, target_args, &c_file, &binary),
+        (_, Language::Asm(syntax)) => build_asm(&args, compiler, syntax, &c_file, &binary),
+        (_, Language::C) => build(&args, compiler, target_args, &c_file, &binary),
+    };
+    // The code is kept to see what the compiler did not like about it
+    if args.keep || (built.is_err() && !args.script) {
+        println!("\x1b[1mKeeping the {} file...\x1b[0m", language.name());
+        let kept = fs::copy(&c_file, [args.output, extension].concat());
+        built?;
+        kept?;
+    }
+    if emit == Emit::StaticLib {
+        println!("\x1b[1mMaking the static library...\x1b[0m");
+        archive(&args, &binary, msvc)?;
+    }
+
+    if args.run {
+        run(&binary, args.replay_input)?
+    }
+    drop(dir);
+    Ok(())
+}
+
+/// The settings the code is parsed and optimized with for the compiled program, which has no
+/// debug symbols in release builds
+fn compiled_config(args: &Args) -> Config {
+    let mut config = Config {
+        debug: args.debug && !args.release,
+        ..args.config()
+    };
+    // These passes work out the values of the cells modulo 256
+    if args.cell_size != 8 {
+        for name in ["unroll", "const-fold", "eval"] {
+            config.passes &= !ir::pass_mask(name).unwrap_or(0);
+        }
+    }
+    config
+}
+
+/// The Rust code or the LLVM IR of `contents`, which are made with the same settings
+fn program_code(
+    contents: String,
+    args: &Args,
+    language: Language,
+) -> Result<String, Box<dyn Error>> {
+    with_program(contents, args, |code, program| match language {
+        Language::LlvmIr => Ok(crate::llvm::translate(code, program)?),
+        Language::Asm(syntax) => Ok(crate::asm::translate(
+            code,
+            program,
+            syntax,
+            asm_target(args)?,
+        )?),
+        Language::Java => Ok(crate::java::translate(code, program)?),
+        _ => Ok(crate::rust::translate(code, program)?),
+    })
+}
+
+/// Calls `f` with the optimized nodes of `contents` and the settings of the program, which are
+/// the same for every backend but the C code
+fn with_program<T>(
+    mut contents: String,
+    args: &Args,
+    f: impl FnOnce(&[Node], &CompileOptions) -> Result<T, Box<dyn Error>>,
+) -> Result<T, Box<dyn Error>> {
+    let input = given_input(&mut contents, args);
+    let config = compiled_config(args);
+    let code = ir::optimize_with(ir::parse(&contents, &config)?, &config)?;
+    f(&code, &compile_options(args, config.debug, &input))
+}
+
+/// The settings every backend has, out of `args`
+fn compile_options<'a>(args: &Args, debug: bool, input: &'a [u8]) -> CompileOptions<'a> {
+    CompileOptions {
+        mem_size: args.mem_size,
+        offset: args.offset,
+        cell_size: args.cell_size,
+        input,
+        exit_cell: args.exit_cell,
+        debug,
+        debug_env: args.debug_env,
+        unbuffered: args.unbuffered,
+    }
+}
+
+/// The settings of the C code that come from its flags in `args`, without the files they name
+fn c_flags<'a>(args: &Args<'a>) -> CFlags<'a> {
+    CFlags {
+        emit: args.emit,
+        getchar: args.getchar,
+        freestanding: args.freestanding,
+        checked: args.checked,
+        grow_tape: args.grow_tape,
+        tapes: args.tapes,
+        seed: args.seed,
+        output: args.output,
+        ..CFlags::default()
+    }
+}
+
+/// Writes what the backend `name` of `registry` makes of `contents` to the output
+fn write_artifact(
+    contents: String,
+    args: &Args,
+    registry: &Registry,
+    name: &str,
+) -> Result<(), Box<dyn Error>> {
+    let backend = registry.get(name).ok_or_else(|| {
+        let names: Vec<_> = registry.names().collect();
+        format!(
+            "Unknown backend '{}', expected one of {}",
+            name,
+            names.join(", ")
+        )
+    })?;
+    let artifact = with_program(contents, args, |code, options| {
+        backend.emit(code, options).map_err(Into::into)
+    })?;
+    println!("\x1b[1mCreating the {} file...\x1b[0m", backend.name());
+    fs::write(
+        [args.output, artifact.extension].concat(),
+        artifact.contents,
+    )?;
+    Ok(())
+}
+
+/// Writes the source of `--emit=bf`, followed by the input after its `!`
+fn write_bf(mut contents: String, args: &Args) -> Result<(), Box<dyn Error>> {
+    let input = given_input(&mut contents, args);
+    let mut config = compiled_config(args);
+    // These write out what the code prints and unroll loops, which does not make it any shorter
+    for name in ["unroll", "const-fold", "eval"] {
+        config.passes &= !ir::pass_mask(name).unwrap_or(0);
+    }
+    let code = ir::optimize_with(ir::parse(&contents, &config)?, &config)?;
+    if !input.is_empty() && args.dialect.instructions().contains('!') {
+        return Err(format!(
+            "The input can't be written after the code of {}, where '!' is an instruction",
+            args.dialect.name()
+        )
+        .into());
+    }
+    let mut bf = ir::to_bf(&code);
+    match input.is_empty() {
+        true => bf.push('\n'),
+        false => bf += &format!("!{}", String::from_utf8_lossy(&input)),
+    }
+    println!("\x1b[1mCreating the Brainfuck file...\x1b[0m");
+    fs::write([args.output, ".bf"].concat(), bf)?;
+    Ok(())
+}
+
+/// Writes the module of `--emit=wasm`
+fn write_wasm(contents: String, args: &Args) -> Result<(), Box<dyn Error>> {
+    let wasm = with_program(contents, args, |code, program| {
+        crate::wasm::program(code, program).map_err(Into::into)
+    })?;
+    println!("\x1b[1mCreating the WebAssembly file...\x1b[0m");
+    fs::write([args.output, ".wasm"].concat(), wasm)?;
+    Ok(())
+}
+
+/// Writes the executable of `--elf`, which takes nothing but this to make
+fn write_elf(contents: String, args: &Args) -> Result<(), Box<dyn Error>> {
+    let elf = with_program(contents, args, |code, program| {
+        crate::elf::program(code, program).map_err(Into::into)
+    })?;
+    // `brainfuck run` leaves nothing behind
+    let dir = BuildDir::new()?;
+    let binary = match args.script {
+        true => dir.0.join(binary_name(args)).to_string_lossy().into_owned(),
+        false => binary_name(args),
+    };
+    println!("\x1b[1mWriting the executable...\x1b[0m");
+    fs::write(&binary, elf)?;
+    // What the linker would have done
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755))?;
+    }
+    if args.run {
+        run(&binary, args.replay_input)?
+    }
+    drop(dir);
+    Ok(())
+}
+
+/// Writes the object file of `--aot` and links it into the program with ld, which needs
+/// nothing else since the code makes its own syscalls
+#[cfg(feature = "aot")]
+fn write_object(contents: String, args: &Args) -> Result<(), Box<dyn Error>> {
+    let object = with_program(contents, args, |code, program| {
+        crate::elf::object(code, program).map_err(Into::into)
+    })?;
+    let dir = BuildDir::new()?;
+    let binary = match args.script {
+        true => dir.0.join(binary_name(args)).to_string_lossy().into_owned(),
+        false => binary_name(args),
+    };
+    let object_file = dir.0.join("program.o");
+    println!("\x1b[1mWriting the object file...\x1b[0m");
+    fs::write(&object_file, object)?;
+
+    let kept = [args.output, ".o"].concat();
+    let linker = linker(args);
+    println!("\x1b[1mLinking the program using {}...\x1b[0m", linker);
+    let mut command = Command::new(&linker);
+    command.arg(&object_file);
+    command.args(["-o", &binary]);
+    if args.release {
+        command.arg("-s");
+    }
+    let linked = run_compiler(command, linker, kept.clone());
+    // The object file is kept to see what the linker did not like about it
+    if args.keep || (linked.is_err() && !args.script) {
+        println!("\x1b[1mKeeping the object file...\x1b[0m");
+        let copied = fs::copy(&object_file, kept);
+        linked?;
+        copied?;
+    }
+    if args.run {
+        run(&binary, args.replay_input)?
+    }
+    drop(dir);
+    Ok(())
+}
+
+/// The C code of `contents`, with the `.map` of `--line-directives` written next to the output
+fn c_code(mut contents: String, args: &Args) -> Result<String, Box<dyn Error>> {
+    let input = given_input(&mut contents, args);
+    let config = compiled_config(args);
+    let debug = config.debug;
+    let code = ir::optimize_with(ir::parse(&contents, &config)?, &config)?;
+    if !args.pthreads && ir::contains(&code, |node| *node == Node::Fork) {
+        return Err("The forks of brainfork need --pthreads in the C code".into());
+    }
+    let profile = match args.profile_use {
+        Some(path) => Some(read_profile(path)?),
+        None => None,
+    };
+    if profile
+        .as_ref()
+        .is_some_and(|profile| profile.len() != contents.matches('[').count())
+    {
+        return Err("The profile does not match the code".into());
+    }
+    let template = match args.template {
+        Some(path) => Some(fs::read_to_string(path)?),
+        None => None,
+    };
+    if template
+        .as_ref()
+        .is_some_and(|template| !template.contains("{{BODY}}"))
+    {
+        return Err("The template has no {{BODY}} to put the code in".into());
+    }
+    let mut source = match args.line_directives || args.annotate {
+        true => Some(Source::new(
+            args.file,
+            &contents,
+            &config,
+            shebang_lines(args.file)?,
+            args.line_directives,
+            args.annotate,
+        )?),
+        false => None,
+    };
+    let c_code = c::translate(
+        &code,
+        &compile_options(args, debug, &input),
+        CFlags {
+            profile: profile.as_deref(),
+            template: template.as_deref(),
+            source: source.as_mut(),
+            ..c_flags(args)
+        },
+    );
+    if let Some(source) = source.filter(|_| args.keep && args.line_directives) {
+        fs::write([args.output, ".map"].concat(), source.map)?;
+    }
+    Ok(c_code)
+}
+
+/// A directory of its own for the files of a build, removed with them when the build is done
+struct BuildDir(PathBuf);
+
+impl BuildDir {
+    fn new() -> io::Result<Self> {
+        let time = SystemTime::now().duration_since(UNIX_EPOCH);
+        let name = format!(
+            "brainfuck-{}-{}",
+            process::id(),
+            time.map_or(0, |time| time.subsec_nanos())
+        );
+        let path = env::temp_dir().join(name);
+        fs::create_dir(&path)?;
+        Ok(Self(path))
+    }
+}
+
+impl Drop for BuildDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// The flags for gcc and the compilers like it to build what `args` asks for, before the ones
+/// of the user
+fn gcc_flags(args: &Args) -> Vec<String> {
+    let mut flags = Vec::new();
+    if let Some(dir) = include_dir(args) {
+        flags.push(format!("-I{}", dir));
+    }
+    if args.freestanding {
+        flags.push("-ffreestanding".to_owned());
+    }
+    if object(args) {
+        flags.push("-c".to_owned());
+    }
+    if args.emit == Some(Emit::Asm) {
+        flags.push("-S".to_owned());
+    }
+    if args.emit == Some(Emit::CDylib) {
+        flags.extend(["-shared".to_owned(), "-fPIC".to_owned()]);
+    }
+    if args.static_link {
+        flags.push("-static".to_owned());
+    }
+    if args.pthreads {
+        flags.push("-pthread".to_owned());
+    }
+    // Release builds are optimized and stripped, the others can be stepped through in gdb
+    match args.release {
+        true => flags.extend(["-O2".to_owned(), "-s".to_owned()]),
+        false => flags.push("-g".to_owned()),
+    }
+    flags
+}
+
+/// Writes the build file of `--build-file` next to the C code of `--emit=c`, which builds the
+/// program like [`compile`] would, with the compiler given now or else the one in `CC`
+fn write_build_file(args: &Args, kind: BuildFile) -> Result<(), Box<dyn Error>> {
+    let cc = env::var("CC").unwrap_or_default();
+    let mut words = cc.split_whitespace();
+    let (compiler, mut target_args) = match args.compiler {
+        Some(compiler) => target_compiler(compiler, args)?,
+        None => target_compiler(words.next().unwrap_or("cc"), args)?,
+    };
+    if args.compiler.is_none() {
+        target_args.extend(words.map(str::to_owned));
+    }
+    if is_msvc(&compiler) || compiler == "libtcc" {
+        return Err(format!("Cannot write a build file for {}", compiler).into());
+    }
+    // The paths are from the directory of the build file, which the C file is in too
+    let file_name = |path: &str| {
+        let name = Path::new(path).file_name().unwrap_or_default();
+        name.to_string_lossy().into_owned()
+    };
+    let name = file_name(args.output);
+    let c_file = file_name(&[args.output, ".c"].concat());
+    let binary = file_name(&binary_name(args));
+    // `$` starts a variable in both
+    let join = |flags: Vec<String>| flags.join(" ").replace('$', "$$");
+    let words = |flags: &str| {
+        flags
+            .split_whitespace()
+            .map(str::to_owned)
+            .collect::<Vec<_>>()
+    };
+    // make still adds the flags in CFLAGS and LDFLAGS when it runs, ninja does not read them
+    let (cflags, ldflags) = match kind {
+        BuildFile::Make => (args.cflags.to_owned(), args.ldflags.to_owned()),
+        BuildFile::Ninja => (
+            env_flags("CFLAGS", args.cflags),
+            env_flags("LDFLAGS", args.ldflags),
+        ),
+    };
+    let cflags = join([gcc_flags(args), words(&cflags)].concat());
+    let ldflags = join(words(&ldflags));
+    let libs = args.libs.split(',').filter(|lib| !lib.is_empty());
+    let libs = join(libs.map(|lib| format!("-l{}", lib)).collect());
+    let compiler = compiler.replace('$', "$$");
+    let target = join(target_args);
+    let (extension, text) = match kind {
+        BuildFile::Make => (
+            ".mk",
+            format!(
+                "\
+# Builds {} from {}, with make -f {}.mk
+CC = {}
+TARGET_ARCH = {}
+CFLAGS += {}
+LDFLAGS += {}
+LDLIBS += {}
+
+{}: {}
+\t$(CC) $(TARGET_ARCH) {} -o {} $(CFLAGS) $(LDFLAGS) $(LDLIBS)
+
+clean:
+\trm -f {}
+
+.PHONY: clean
+",
+                binary,
+                c_file,
+                name,
+                compiler,
+                target,
+                cflags,
+                ldflags,
+                libs,
+                binary,
+                c_file,
+                c_file,
+                binary,
+                binary
+            ),
+        ),
+        BuildFile::Ninja => (
+            ".ninja",
+            format!(
+                "\
+# Builds {} from {}, with ninja -f {}.ninja
+cc = {}
+target = {}
+cflags = {}
+ldflags = {}
+libs = {}
+
+rule cc
+  command = $cc $target $in -o $out $cflags $ldflags $libs
+  description = CC $out
+
+build {}: cc {}
+default {}
+",
+                binary,
+                c_file,
+                name,
+                compiler,
+                target,
+                cflags,
+                ldflags,
+                libs,
+                binary,
+                c_file,
+                binary
+            ),
+        ),
+    };
+    fs::write([args.output, extension].concat(), text)?;
+    Ok(())
+}
+
+/// Runs `compiler` on `c_file`, the C file of [`compile`], to build `binary`
+fn build(
+    args: &Args,
+    compiler: String,
+    target_args: Vec<String>,
+    c_file: &Path,
+    binary: &str,
+) -> Result<(), Box<dyn Error>> {
+    let msvc = is_msvc(&compiler);
+    let mut command = Command::new(&compiler);
+    command.args(target_args);
+    if msvc {
+        msvc_args(&mut command, args, c_file, binary);
+    } else {
+        command.arg(c_file);
+        command.args(["-o", binary]);
+        command.args(gcc_flags(args));
+        command.args(env_flags("CFLAGS", args.cflags).split_whitespace());
+        // The linker looks for the symbols of a library in the files before it, so these go last
+        command.args(env_flags("LDFLAGS", args.ldflags).split_whitespace());
+        let libs = args.libs.split(',').filter(|lib| !lib.is_empty());
+        command.args(libs.map(|lib| format!("-l{}", lib)));
+    }
+    let output = command.output()?;
+
+    // cl writes its errors to stdout
+    if msvc {
+        for x in output.stdout.iter() {
+            print!("{}", *x as char)
+        }
+    }
+    for x in output.stderr.iter() {
+        print!("{}", *x as char)
+    }
+    if !output.status.success() {
+        return Err(CompilerError {
+            compiler,
+            code: output.status.code(),
+            c_file: [args.output, ".c"].concat(),
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Runs rustc on `rust_file`, the Rust file of [`compile`], to build the program `binary`
+fn build_with_rustc(
+    args: &Args,
+    compiler: String,
+    rust_file: &Path,
+    binary: &str,
+) -> Result<(), Box<dyn Error>> {
+    let mut command = Command::new(&compiler);
+    command.args(["--edition", "2021", "--crate-name", "bf_program"]);
+    command.arg(rust_file);
+    command.args(["-o", binary]);
+    if let Some(target) = args.target {
+        command.args(["--target", target]);
+    }
+    if args.static_link {
+        command.args(["-C", "target-feature=+crt-static"]);
+    }
+    // Like the C code, release builds are optimized and stripped
+    match args.release {
+        true => command.args(["-O", "-C", "strip=symbols"]),
+        false => command.arg("-g"),
+    };
+    run_compiler(command, compiler, [args.output, ".rs"].concat())
+}
+
+/// Builds the Java code into a jar that `java -jar` runs, with the jar tool next to javac
+fn build_with_javac(
+    args: &Args,
+    compiler: String,
+    java_file: &Path,
+    binary: &str,
+) -> Result<(), Box<dyn Error>> {
+    let classes = java_file.with_file_name("classes");
+    let mut command = Command::new(&compiler);
+    command.arg("-d").arg(&classes).arg(java_file);
+    if !args.release {
+        command.arg("-g");
+    }
+    run_compiler(command, compiler.clone(), [args.output, ".java"].concat())?;
+    let jar = Path::new(&compiler).with_file_name("jar");
+    let mut command = Command::new(&jar);
+    command.args([
+        "--create",
+        "--file",
+        binary,
+        "--main-class",
+        "BfProgram",
+        "-C",
+    ]);
+    command.arg(&classes).arg(".");
+    run_compiler(
+        command,
+        jar.to_string_lossy().into_owned(),
+        [args.output, ".java"].concat(),
+    )
+}
+
+/// clang for `--llvm`, with the flags of [`target_compiler`], or else llc. `--compiler` can
+/// name either
+fn llvm_compiler(args: &Args) -> Result<(String, Vec<String>), String> {
+    let found = match args.compiler {
+        Some(compiler) => find_program(compiler).then_some(compiler),
+        None => ["clang", "llc"]
+            .into_iter()
+            .find(|&tool| find_program(tool)),
+    };
+    match found {
+        Some(llc) if is_llc(llc) => {
+            let target = args.target.map(|target| format!("-mtriple={}", target));
+            Ok((llc.to_owned(), target.into_iter().collect()))
+        }
+        Some(clang) => target_compiler(clang, args),
+        None => Err(match args.compiler {
+            Some(compiler) => format!("{} was not found", compiler),
+            None => "Neither clang nor llc was found to build the LLVM IR".to_owned(),
+        }),
+    }
+}
+
+/// Builds the LLVM IR in `ll_file` into the program `binary` with clang, or with llc and then
+/// the C compiler to link the object file it makes
+fn build_llvm_ir(
+    args: &Args,
+    compiler: String,
+    target_args: Vec<String>,
+    ll_file: &Path,
+    binary: &str,
+) -> Result<(), Box<dyn Error>> {
+    let kept = [args.output, ".ll"].concat();
+    let (compiler, target_args, input) = match is_llc(&compiler) {
+        true => {
+            let object = ll_file.with_extension("o");
+            let mut command = Command::new(&compiler);
+            command.args(target_args);
+            command.arg(ll_file);
+            command.arg("-o").arg(&object);
+            // The object file goes in a program, which is position independent by default
+            command.args(["-filetype=obj", "-relocation-model=pic"]);
+            match args.release {
+                true => command.arg("-O2"),
+                false => command.arg("-O0"),
+            };
+            run_compiler(command, compiler, kept.clone())?;
+            let (compiler, target_args) = find_compiler(args)?;
+            println!("\x1b[1mLinking the program using {}...\x1b[0m", compiler);
+            (compiler, target_args, object)
+        }
+        false => (compiler, target_args, ll_file.to_owned()),
+    };
+    let mut command = Command::new(&compiler);
+    command.args(target_args);
+    command.arg(input);
+    command.args(["-o", binary]);
+    command.args(gcc_flags(args));
+    run_compiler(command, compiler, kept)
+}
+
+/// What the assembly of `--asm` is written for, which is `--target` or else this machine
+fn asm_target(args: &Args) -> Result<Target, String> {
+    let target = match args.target {
+        Some(triple) => Target::of(triple),
+        None => Target::host(),
+    };
+    match target {
+        Some(Target::X86_64Linux) => Ok(Target::X86_64Linux),
+        Some(_) if args.asm == Some(Syntax::Nasm) => {
+            Err("nasm only assembles x86-64 assembly".to_owned())
+        }
+        Some(target) => Ok(target),
+        None => Err(
+            "The assembly of --asm is only for x86-64 Linux, and for AArch64 Linux and macOS"
+                .to_owned(),
+        ),
+    }
+}
+
+/// Checks that the executable of `--elf` or `--aot` is for x86-64 Linux, the machine its code is
+/// for
+fn elf_target(args: &Args) -> Result<(), String> {
+    let target = match args.target {
+        Some(target) => Target::of(target),
+        None => Target::host(),
+    };
+    match target {
+        Some(Target::X86_64Linux) => Ok(()),
+        _ => Err("The executables of --elf and --aot are only for x86-64 Linux".to_owned()),
+    }
+}
+
+/// The assembler for `syntax`, which is the one for `--target` for the GNU assembler. On macOS,
+/// `cc` assembles the code and links it with the system library, which is always linked there
+fn assembler(args: &Args, syntax: Syntax) -> Result<String, String> {
+    let target = asm_target(args)?;
+    if target == Target::Aarch64MacOs && env::consts::OS != "macos" {
+        return Err("The assembly for macOS only builds on macOS".to_owned());
+    }
+    let assembler = match (target, syntax, args.target) {
+        (Target::Aarch64MacOs, _, _) => "cc".to_owned(),
+        (_, Syntax::Gas, Some(target)) => format!("{}-as", target),
+        (_, Syntax::Gas, None) => "as".to_owned(),
+        (_, Syntax::Nasm, _) => "nasm".to_owned(),
+    };
+    match find_program(&assembler) {
+        true => Ok(assembler),
+        false => Err(format!("{} was not found", assembler)),
+    }
+}
+
+/// Assembles `asm_file` with `assembler` and links it into the program `binary` with ld, which
+/// needs nothing else since the assembly makes its own syscalls
+fn build_asm(
+    args: &Args,
+    assembler: String,
+    syntax: Syntax,
+    asm_file: &Path,
+    binary: &str,
+) -> Result<(), Box<dyn Error>> {
+    let kept = [args.output, Language::Asm(syntax).extension()].concat();
+    if asm_target(args)? == Target::Aarch64MacOs {
+        let mut command = Command::new(&assembler);
+        command.arg(asm_file);
+        command.args(["-o", binary]);
+        if !args.release {
+            command.arg("-g");
+        }
+        return run_compiler(command, assembler, kept);
+    }
+    let object = asm_file.with_extension("o");
+    let mut command = Command::new(&assembler);
+    if syntax == Syntax::Nasm {
+        command.args(["-f", "elf64"]);
+    }
+    command.arg(asm_file);
+    command.arg("-o").arg(&object);
+    if !args.release {
+        match syntax {
+            Syntax::Gas => command.arg("-g"),
+            Syntax::Nasm => command.args(["-g", "-F", "dwarf"]),
+        };
+    }
+    run_compiler(command, assembler, kept.clone())?;
+
+    let linker = linker(args);
+    println!("\x1b[1mLinking the program using {}...\x1b[0m", linker);
+    let mut command = Command::new(&linker);
+    command.arg(&object);
+    command.args(["-o", binary]);
+    if args.release {
+        command.arg("-s");
+    }
+    run_compiler(command, linker, kept)
+}
+
+/// The ld that links for `--target`
+fn linker(args: &Args) -> String {
+    args.target
+        .map_or("ld".to_owned(), |target| format!("{}-ld", target))
+}
+
+/// Runs `command`, the build with `compiler`, where the code is kept in `kept` when it fails
+fn run_compiler(
+    mut command: Command,
+    compiler: String,
+    kept: String,
+) -> Result<(), Box<dyn Error>> {
+    let status = command.status()?;
+    if !status.success() {
+        return Err(CompilerError {
+            compiler,
+            code: status.code(),
+            c_file: kept,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+/// Builds `c_code` like [`build`] does, with the libtcc in this process
+#[cfg(feature = "tcc")]
+fn build_with_libtcc(c_code: &str, args: &Args, binary: &str) -> Result<(), String> {
+    if args.emit == Some(Emit::Asm) {
+        return Err("libtcc does not write assembly".to_owned());
+    }
+    let debug = match args.release {
+        true => "",
+        false => "-g",
+    };
+    let include = include_dir(args).map_or(String::new(), |dir| format!("-I{}", dir));
+    let options = [
+        debug,
+        match args.static_link {
+            true => "-static",
+            false => "",
+        },
+        &include,
+        &env_flags("CFLAGS", args.cflags),
+        &env_flags("LDFLAGS", args.ldflags),
+    ]
+    .join(" ");
+    let libs: Vec<&str> = args.libs.split(',').filter(|lib| !lib.is_empty()).collect();
+    let kind = match args.emit {
+        _ if object(args) => crate::tcc::Output::Object,
+        Some(Emit::CDylib) => crate::tcc::Output::SharedLibrary,
+        _ => crate::tcc::Output::Program,
+    };
+    crate::tcc::build(c_code, binary, kind, &options, &libs)
+}
+
+fn run(filename: &str, replay: Option<&str>) -> Result<(), Box<dyn Error>> {
+    println!("\x1b[1mRunning the program...\x1b[0m");
+    let replay = match replay {
+        Some(file) => Some(fs::read(file).map_err(|err| format!("{}: {}", file, err))?),
+        None => None,
+    };
+    println!("\n\x1b[90m--------------\x1b[0m\x1b[96mOUTPUT\x1b[0m\x1b[90m--------------\x1b[0m\n");
+    // The program reads and writes the terminal itself, so it is as buffered as when it runs on its
+    // own, and what it writes to stderr is passed on in red as it comes
+    // Joined to the current directory, which is not searched for programs on Unix, and the jar of
+    // javac is run by java
+    let jar = Path::new(filename)
+        .extension()
+        .is_some_and(|extension| extension == "jar");
+    let mut command = match jar {
+        true => Command::new("java"),
+        false => Command::new(Path::new(".").join(filename)),
+    };
+    if jar {
+        command.args(["-jar", filename]);
+    }
+    let mut program = command
+        .stdin(match replay {
+            Some(_) => Stdio::piped(),
+            None => Stdio::inherit(),
+        })
+        .stderr(Stdio::piped())
+        .spawn()?;
+    if let (Some(replay), Some(mut stdin)) = (replay, program.stdin.take()) {
+        // Like the interpreter, the program reads the replayed input and then the terminal. Writing
+        // stops when the program exits, or else when this one does
+        thread::spawn(move || {
+            if stdin.write_all(&replay).is_ok() {
+                let _ = io::copy(&mut io::stdin(), &mut stdin);
+            }
+        });
+    }
+    let start = Instant::now();
+    let stderr = program.stderr.take().expect("stderr is piped");
+    let result = forward_in_red(stderr, io::stderr());
+    let (status, usage) = wait_with_usage(&mut program)?;
+    let time = start.elapsed();
+    result?;
+
+    println!("\n\x1b[90m----------------------------------\x1b[0m");
+    if status.success() {
+        println!("\x1b[1mProgram ended with \x1b[0m\x1b[92m{}\x1b[0m", status);
+    } else {
+        println!(
+            "\x1b[1mProgram ended with \x1b[90m\x1b[91m{}\x1b[0m",
+            status
+        );
+    }
+    match usage {
+        Some(usage) => println!(
+            "\x1b[1mTook\x1b[0m {:.3}s \x1b[90m(user {:.3}s, system {:.3}s)\x1b[0m\x1b[1m, at most\x1b[0m {:.1} MiB \x1b[1mof memory\x1b[0m",
+            time.as_secs_f64(),
+            usage.user.as_secs_f64(),
+            usage.system.as_secs_f64(),
+            usage.max_rss as f64 / (1024.0 * 1024.0)
+        ),
+        None => println!("\x1b[1mTook\x1b[0m {:.3}s", time.as_secs_f64()),
+    }
+    Ok(())
+}
+
+/// What the program that `--run` ran took besides the wall-clock time
+struct Usage {
+    user: Duration,
+    system: Duration,
+    /// The peak resident set size, in bytes
+    max_rss: u64,
+}
+
+/// Waits for `program` to end, with what it used where the OS tells that
+#[cfg(unix)]
+fn wait_with_usage(program: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    use std::os::unix::process::ExitStatusExt;
+    let mut status = 0;
+    // SAFETY: an all zero `rusage` is valid, and `wait4` only writes to it and `status`
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    // Unlike `getrusage(RUSAGE_CHILDREN)`, this leaves out the C compiler
+    // SAFETY: the pointers are to the locals above, and the child has not been waited for yet
+    while unsafe { libc::wait4(program.id() as libc::pid_t, &mut status, 0, &mut usage) } == -1 {
+        let err = io::Error::last_os_error();
+        if err.kind() != io::ErrorKind::Interrupted {
+            return Err(err);
+        }
+    }
+    let duration = |time: libc::timeval| {
+        Duration::from_secs(time.tv_sec as u64) + Duration::from_micros(time.tv_usec as u64)
+    };
+    // Linux counts the peak in kilobytes, macOS in bytes
+    let max_rss = match cfg!(target_os = "macos") {
+        true => usage.ru_maxrss as u64,
+        false => usage.ru_maxrss as u64 * 1024,
+    };
+    let usage = Usage {
+        user: duration(usage.ru_utime),
+        system: duration(usage.ru_stime),
+        max_rss,
+    };
+    Ok((ExitStatus::from_raw(status), Some(usage)))
+}
+
+#[cfg(not(unix))]
+fn wait_with_usage(program: &mut Child) -> io::Result<(ExitStatus, Option<Usage>)> {
+    Ok((program.wait()?, None))
+}
+
+/// Writes everything read from `from` to `to` in red as soon as it comes
+fn forward_in_red(mut from: impl Read, mut to: impl Write) -> io::Result<()> {
+    let mut buffer = [0; 4096];
+    loop {
+        let read = match from.read(&mut buffer) {
+            Ok(0) => return Ok(()),
+            Ok(read) => read,
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+            Err(error) => return Err(error),
+        };
+        write!(to, "\x1b[91m{}\x1b[0m", console_text(&buffer[..read]))?;
+        to.flush()?;
+    }
+}
+
+/// The output of a program as text, with the `\r\n` the C runtime of Windows ends lines with
+/// back to `\n`
+fn console_text(output: &[u8]) -> String {
+    let text: String = output.iter().map(|&byte| byte as char).collect();
+    match cfg!(windows) {
+        true => text.replace("\r\n", "\n"),
+        false => text,
+    }
+}
+
+pub fn run_in_terminal(args: Args) -> Result<(), String> {
+    let mut session = Session::new(&args)?;
+    let result = run_repl(&args, &mut session);
+    if let Some(err) = session.error {
+        return Err(err.to_string());
+    }
+    result
+}
+
+/// Reads code line by line from stdin and runs it on the same memory, with `io` behind `,` and `.`
+pub fn run_repl(args: &Args, io: &mut impl BfIo) -> Result<(), String> {
+    let mut interpreter = Interpreter::new("", args.config())?;
+    let mut contents = String::new();
+    loop {
+        print!(">>> ");
+        io::stdout().flush().map_err(|err| err.to_string())?;
+        if io::stdin()
+            .read_line(&mut contents)
+            .map_err(|err| err.to_string())?
+            == 0
+        {
+            break Ok(());
+        }
+        contents.pop();
+        println!("{}", contents);
+        if contents.eq("quit") {
+            break Ok(());
+        }
+        let result = match contents.split_once(' ') {
+            Some(("save", file)) => File::create(file)
+                .and_then(|file| interpreter.save(file))
+                .map_err(BfError::from),
+            Some(("load", file)) => File::open(file)
+                .map_err(BfError::from)
+                .and_then(Interpreter::restore)
+                .map(|restored| interpreter = restored),
+            _ => {
+                interpreter.load(&contents)?;
+                run_to_end(&mut interpreter, io, args)
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("\x1b[91mError\x1b[0m: {}", e);
+        }
+        io.flush();
+        println!();
+        contents.clear();
+    }
+}
+
+pub fn verbosify(filename: &str) -> Result<(), String> {
+    println!("\x1b[1mOpening {}...\x1b[0m", filename);
+    let mut contents = match fs::read_to_string(filename) {
+        Ok(contents) => contents,
+        Err(err) => return Err(err.to_string()),
+    };
+    println!("\x1b[1mVerbosifying...\x1b[0m");
+    let shebang = split_shebang(&mut contents);
+    let input = split_input(&mut contents, Dialect::Brainfuck);
+    contents.retain(|c| "<>[]+-.,".contains(c));
+    if let Some(shebang) = shebang {
+        contents.insert_str(0, &shebang);
+    }
+    if let Some(input) = input {
+        contents.push('!');
+        contents.push_str(&String::from_utf8_lossy(&input));
+    }
+    let mut cpp_file = match File::create(filename) {
+        Ok(x) => x,
+        Err(err) => return Err(err.to_string()),
+    };
+    println!("\x1b[1mSaving {}...\x1b[0m", filename);
+    match cpp_file.write_all(contents.as_bytes()) {
+        Ok(()) => Ok(()),
+        Err(err) => Err(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn input_starts_after_the_first_bang() {
+        let mut contents = "+[!],.!a!b".to_owned();
+        assert_eq!(
+            split_input(&mut contents, Dialect::Brainfuck).as_deref(),
+            Some(&b"a!b"[..])
+        );
+        assert_eq!(contents, "+[!],.");
+        let mut contents = ",.".to_owned();
+        assert_eq!(split_input(&mut contents, Dialect::Brainfuck), None);
+        // Where `!` is an instruction, there is no input
+        let mut contents = "+!.!a".to_owned();
+        assert_eq!(split_input(&mut contents, Dialect::Extended1), None);
+        assert_eq!(contents, "+!.!a");
+    }
+
+    #[test]
+    fn shebang_line_is_taken_off() {
+        let mut contents = "#!/usr/bin/env -S brainfuck run\n+.\n".to_owned();
+        assert_eq!(
+            split_shebang(&mut contents).as_deref(),
+            Some("#!/usr/bin/env -S brainfuck run\n")
+        );
+        assert_eq!(contents, "+.\n");
+        let mut contents = "+#!.".to_owned();
+        assert_eq!(split_shebang(&mut contents), None);
+        assert_eq!(contents, "+#!.");
+    }
+
+    #[test]
+    fn strict_points_at_the_character() {
+        assert_eq!(
+            check_strict("+.\n -x", false, Dialect::Brainfuck, 0),
+            Err("Invalid character 'x' at 2:3".to_owned())
+        );
+        // After a shebang line that was taken off
+        assert_eq!(
+            check_strict("+.\n -x", false, Dialect::Brainfuck, 1),
+            Err("Invalid character 'x' at 3:3".to_owned())
+        );
+        assert_eq!(
+            check_strict("+\t#", false, Dialect::Brainfuck, 0),
+            Err("Invalid character '#' at 1:3".to_owned())
+        );
+        assert_eq!(check_strict("+ #|\n", true, Dialect::Brainfuck, 0), Ok(()));
+        assert_eq!(check_strict("(+):", false, Dialect::Pbrain, 0), Ok(()));
+        // The input is not code
+        assert_eq!(
+            check_strict(",.!any input", false, Dialect::Brainfuck, 0),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn replayed_input_comes_first_and_the_rest_is_recorded() {
+        let path = env::temp_dir().join(format!("brainfuck-record-{}", process::id()));
+        let mut session = Session {
+            io: ReadWrite::new(&b"cd"[..], Vec::new()),
+            replay: b"ab".to_vec().into_iter(),
+            record: Some(File::create(&path).unwrap()),
+            reading: None,
+            writing: None,
+            error: None,
+        };
+        let read: Vec<_> = (0..5).map(|_| session.read()).collect();
+        assert_eq!(read, [Some(b'a'), Some(b'b'), Some(b'c'), Some(b'd'), None]);
+        drop(session);
+        assert_eq!(fs::read(&path).unwrap(), b"cd");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn step_delay_slows_the_code_down() {
+        let mut args = Args::default();
+        let delay = Duration::from_millis(20);
+        args.step_delay = Some(delay);
+        let start = Instant::now();
+        assert_eq!(interpret_to_vec(",+.", &args, b"A").unwrap(), b"B");
+        assert!(start.elapsed() >= delay);
+    }
+
+    #[test]
+    fn output_is_captured() {
+        let args = Args::default();
+        // The input after the `!` comes before the one given, and the end of it reads as 0
+        let output = interpret_to_vec("++++++++[>++++++++<-]>+.,.,.,.!B", &args, b"C").unwrap();
+        assert_eq!(output, b"ABC\0");
+        assert!(matches!(
+            interpret_to_vec("<", &args, b""),
+            Err(BfError::MemoryOutOfBounds)
+        ));
+    }
+
+    /// A writer that takes nothing
+    struct Full;
+
+    impl Write for Full {
+        fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+            Err(io::ErrorKind::WriteZero.into())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn io_goes_through_readers_and_writers() {
+        let args = Args {
+            exit_cell: Some(ExitCell::Current),
+            ..Args::default()
+        };
+        let mut output = Vec::new();
+        let code = interpret_with_io(",[.,]+++", &args, &b"abc"[..], &mut output).unwrap();
+        assert_eq!((code, &*output), (3, &b"abc"[..]));
+        // The first error of the writer is the one given back
+        assert!(matches!(
+            interpret_with_io("+.", &args, io::empty(), Full),
+            Err(BfError::Io(err)) if err.kind() == io::ErrorKind::WriteZero
+        ));
+    }
+}
@@ -0,0 +1,581 @@
+//! Writes the nodes straight into a static x86-64 Linux executable, for `--elf`.
+//!
+//! The file is an ELF header, the program headers and the machine code, with nothing for an
+//! assembler or a linker to do. The code is the one `--asm` writes for x86-64: `rbx` holds the
+//! address of the current cell, and `.` and `,` make their own syscalls. `r12` holds the start
+//! of the memory that is not in the file, which holds the output buffer and then the cells.
+//!
+//! With the aot feature, the same code is also written as an object file for `--aot`, where the
+//! linker puts the memory and writes its address into the code
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+
+/// Where the file is loaded
+const BASE: u64 = 0x400000;
+const PAGE: u64 = 0x1000;
+/// The ELF header and the program headers for the code, the memory and the stack
+const HEADERS: usize = 64 + 3 * 56;
+/// Where things are from `r12`, after the length of the output at 0: how much of the given
+/// input was read, the output and then the cells
+const INPUT_POS: i32 = 8;
+const BUFFER: i32 = 16;
+const BUFFER_SIZE: i32 = 4096;
+const MEM: i32 = BUFFER + BUFFER_SIZE;
+
+/// The machine code of the program, which starts at its first byte
+struct MachineCode {
+    buf: Vec<u8>,
+    /// Where the address of the memory goes in `buf`, as 8 bytes
+    memory: usize,
+    /// The bytes of the memory, with the output buffer before the cells
+    memory_size: u64,
+}
+
+/// The machine code of `code`, for the executable or the object file
+fn machine_code(code: &[Node], options: &CompileOptions) -> Result<MachineCode, String> {
+    brainfuck_only("elf", code)?;
+    let cell_bytes = options.cell_size as i32 / 8;
+    let too_far = || "The program is too big for --elf".to_owned();
+    let mut asm = Assembler {
+        buf: Vec::new(),
+        labels: Vec::new(),
+        fixups: Vec::new(),
+        cell_bytes,
+        unbuffered: options.unbuffered,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+        too_far: false,
+    };
+    let (putchar, flush, getch, input) = (asm.label(), asm.label(), asm.label(), asm.label());
+
+    asm.emit(&[0x49, 0xbc]); // mov r12, the memory
+    let memory = asm.buf.len();
+    asm.emit(&[0; 8]);
+    let start = i32::try_from(options.offset)
+        .ok()
+        .and_then(|offset| offset.checked_mul(cell_bytes)?.checked_add(MEM))
+        .ok_or_else(too_far)?;
+    asm.emit(&[0x49, 0x8d, 0x9c, 0x24]); // lea rbx, [r12 + start]
+    asm.emit(&start.to_le_bytes());
+    asm.block(code, putchar, flush, getch);
+    asm.jump(&[0xe8], flush); // call flush
+
+    // The same status as the interpreter exits with, of which the system keeps the low byte
+    let load: &[u8] = match cell_bytes {
+        1 => &[0x0f, 0xb6],
+        2 => &[0x0f, 0xb7],
+        _ => &[0x8b],
+    };
+    match options.exit_cell {
+        Some(ExitCell::Current) => {
+            asm.emit(load);
+            asm.emit(&[0x3b]); // edi, [rbx]
+        }
+        Some(ExitCell::Cell(cell)) => {
+            let at = i32::try_from(cell)
+                .ok()
+                .and_then(|cell| cell.checked_mul(cell_bytes)?.checked_add(MEM))
+                .ok_or_else(too_far)?;
+            asm.emit(&[0x41]);
+            asm.emit(load);
+            asm.emit(&[0xbc, 0x24]); // edi, [r12 + at]
+            asm.emit(&at.to_le_bytes());
+        }
+        None => asm.emit(&[0x31, 0xff]), // xor edi, edi
+    }
+    asm.emit(&[0xb8, 60, 0, 0, 0]); // mov eax, exit
+    asm.emit(&[0x0f, 0x05]); // syscall
+
+    // Adds the byte in al to the output, and writes it when the buffer is full
+    asm.bind(putchar);
+    asm.emit(&[0x49, 0x8b, 0x0c, 0x24]); // mov rcx, [r12]
+    asm.emit(&[0x41, 0x88, 0x44, 0x0c, BUFFER as u8]); // mov [r12 + rcx + BUFFER], al
+    asm.emit(&[0x48, 0xff, 0xc1]); // inc rcx
+    asm.emit(&[0x49, 0x89, 0x0c, 0x24]); // mov [r12], rcx
+    asm.emit(&[0x48, 0x81, 0xf9]); // cmp rcx, BUFFER_SIZE
+    asm.emit(&BUFFER_SIZE.to_le_bytes());
+    asm.jump(&[0x0f, 0x84], flush); // je flush
+    asm.emit(&[0xc3]); // ret
+
+    // Writes the output to stdout. When it cannot be written, it is dropped
+    let (write, done) = (asm.label(), asm.label());
+    asm.bind(flush);
+    asm.emit(&[0x49, 0x8b, 0x14, 0x24]); // mov rdx, [r12]
+    asm.emit(&[0x49, 0x8d, 0x74, 0x24, BUFFER as u8]); // lea rsi, [r12 + BUFFER]
+    asm.bind(write);
+    asm.emit(&[0x48, 0x85, 0xd2]); // test rdx, rdx
+    asm.jump(&[0x0f, 0x84], done); // jz done
+    asm.emit(&[0xbf, 1, 0, 0, 0]); // mov edi, stdout
+    asm.emit(&[0xb8, 1, 0, 0, 0]); // mov eax, write
+    asm.emit(&[0x0f, 0x05]); // syscall
+    asm.emit(&[0x48, 0x85, 0xc0]); // test rax, rax
+    asm.jump(&[0x0f, 0x8e], done); // jle done
+    asm.emit(&[0x48, 0x01, 0xc6]); // add rsi, rax
+    asm.emit(&[0x48, 0x29, 0xc2]); // sub rdx, rax
+    asm.jump(&[0xe9], write); // jmp write
+    asm.bind(done);
+    asm.emit(&[0x49, 0xc7, 0x04, 0x24, 0, 0, 0, 0]); // mov qword [r12], 0
+    asm.emit(&[0xc3]); // ret
+
+    // Reads a byte into eax for `,`, from the given input and then stdin, where the end of the
+    // input reads as 0 like in the interpreter
+    let stdin = asm.label();
+    let input_len = i32::try_from(options.input.len()).map_err(|_| too_far())?;
+    asm.bind(getch);
+    asm.emit(&[0x49, 0x8b, 0x4c, 0x24, INPUT_POS as u8]); // mov rcx, [r12 + INPUT_POS]
+    asm.emit(&[0x48, 0x81, 0xf9]); // cmp rcx, the length of the input
+    asm.emit(&input_len.to_le_bytes());
+    asm.jump(&[0x0f, 0x83], stdin); // jae stdin
+    asm.jump(&[0x48, 0x8d, 0x15], input); // lea rdx, [rip + input]
+    asm.emit(&[0x0f, 0xb6, 0x04, 0x0a]); // movzx eax, byte [rdx + rcx]
+    asm.emit(&[0x48, 0xff, 0xc1]); // inc rcx
+    asm.emit(&[0x49, 0x89, 0x4c, 0x24, INPUT_POS as u8]); // mov [r12 + INPUT_POS], rcx
+    asm.emit(&[0xc3]); // ret
+    asm.bind(stdin);
+    asm.jump(&[0xe8], flush); // call flush
+    asm.emit(&[0x48, 0x83, 0xec, 0x08]); // sub rsp, 8
+    asm.emit(&[0x31, 0xff]); // xor edi, edi
+    asm.emit(&[0x48, 0x89, 0xe6]); // mov rsi, rsp
+    asm.emit(&[0xba, 1, 0, 0, 0]); // mov edx, 1
+    asm.emit(&[0x31, 0xc0]); // xor eax, eax
+    asm.emit(&[0x0f, 0x05]); // syscall
+    asm.emit(&[0x0f, 0xb6, 0x0c, 0x24]); // movzx ecx, byte [rsp]
+    asm.emit(&[0x48, 0x83, 0xc4, 0x08]); // add rsp, 8
+    asm.emit(&[0x48, 0x83, 0xf8, 0x01]); // cmp rax, 1
+    asm.emit(&[0xb8, 0, 0, 0, 0]); // mov eax, 0
+    asm.emit(&[0x0f, 0x44, 0xc1]); // cmove eax, ecx
+    asm.emit(&[0xc3]); // ret
+
+    // What `,` reads before stdin
+    asm.bind(input);
+    asm.emit(options.input);
+    if asm.too_far {
+        return Err(too_far());
+    }
+    asm.resolve();
+    Ok(MachineCode {
+        buf: asm.buf,
+        memory,
+        memory_size: MEM as u64 + options.mem_size as u64 * cell_bytes as u64,
+    })
+}
+
+/// The executable of `code`
+pub fn program(code: &[Node], options: &CompileOptions) -> Result<Vec<u8>, String> {
+    let MachineCode {
+        mut buf,
+        memory,
+        memory_size,
+    } = machine_code(code, options)?;
+    let file_size = (HEADERS + buf.len()) as u64;
+    let memory_start = (BASE + file_size).next_multiple_of(PAGE);
+    buf[memory..memory + 8].copy_from_slice(&memory_start.to_le_bytes());
+
+    let mut elf = Vec::with_capacity(file_size as usize);
+    header(&mut elf, 2); // an executable
+    elf.extend_from_slice(&(BASE + HEADERS as u64).to_le_bytes()); // where the code starts
+    elf.extend_from_slice(&64u64.to_le_bytes()); // where the program headers are
+    elf.extend_from_slice(&0u64.to_le_bytes()); // no section headers
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for size in [64u16, 56, 3, 64, 0, 0] {
+        elf.extend_from_slice(&size.to_le_bytes());
+    }
+    // The file itself, which can be read and run, the memory, which starts zeroed, and the
+    // stack, which cannot be run
+    let segments = [
+        (1, 5, BASE, file_size, file_size, PAGE),
+        (1, 6, memory_start, 0, memory_size, PAGE),
+        (0x6474e551, 6, 0, 0, 0, 16),
+    ];
+    for (kind, flags, address, file_size, memory_size, align) in segments {
+        elf.extend_from_slice(&(kind as u32).to_le_bytes());
+        elf.extend_from_slice(&(flags as u32).to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes()); // where it is in the file
+        elf.extend_from_slice(&address.to_le_bytes());
+        elf.extend_from_slice(&address.to_le_bytes());
+        elf.extend_from_slice(&file_size.to_le_bytes());
+        elf.extend_from_slice(&memory_size.to_le_bytes());
+        elf.extend_from_slice(&align.to_le_bytes());
+    }
+    elf.extend_from_slice(&buf);
+    Ok(elf)
+}
+
+/// The object file of `code` for `--aot`, which ld links into the program. The code is `.text`,
+/// starting at `_start`, and the memory is `.bss`, whose address the linker writes into the code
+#[cfg(feature = "aot")]
+pub fn object(code: &[Node], options: &CompileOptions) -> Result<Vec<u8>, String> {
+    let MachineCode {
+        buf,
+        memory,
+        memory_size,
+    } = machine_code(code, options)?;
+    // The section of the memory, which the address is taken from, and `_start`
+    let mut symbols = vec![0; 24];
+    for (name, info, section, size) in [(0u32, 3, 2u16, 0), (1, 0x12, 1, buf.len() as u64)] {
+        symbols.extend_from_slice(&name.to_le_bytes());
+        symbols.extend_from_slice(&[info, 0]);
+        symbols.extend_from_slice(&section.to_le_bytes());
+        symbols.extend_from_slice(&0u64.to_le_bytes()); // where it is in the section
+        symbols.extend_from_slice(&size.to_le_bytes());
+    }
+    // The 8 bytes of the address of the memory, which are the one of the first symbol
+    let mut relocation = Vec::new();
+    relocation.extend_from_slice(&(memory as u64).to_le_bytes());
+    relocation.extend_from_slice(&(1u64 << 32 | 1).to_le_bytes()); // R_X86_64_64
+    relocation.extend_from_slice(&0u64.to_le_bytes());
+
+    let names = [
+        ".text",
+        ".bss",
+        ".note.GNU-stack",
+        ".symtab",
+        ".strtab",
+        ".rela.text",
+        ".shstrtab",
+    ];
+    let mut strings = vec![0];
+    let mut offsets = Vec::new();
+    for name in names {
+        offsets.push(strings.len() as u32);
+        strings.extend_from_slice(name.as_bytes());
+        strings.push(0);
+    }
+    // The kind, the flags, the contents, or the size of the ones that are not in the file, the
+    // linked section, the info, the alignment and the size of an entry. The memory starts
+    // zeroed, and the stack cannot be run
+    let sections = [
+        (1u32, 6u64, Ok(&buf[..]), 0u32, 0u32, 16, 0u64),
+        (8, 3, Err(memory_size), 0, 0, PAGE, 0),
+        (1, 0, Ok(&[]), 0, 0, 1, 0),
+        (2, 0, Ok(&symbols), 5, 2, 8, 24),
+        (3, 0, Ok(b"\0_start\0"), 0, 0, 1, 0),
+        (4, 0x40, Ok(&relocation), 4, 1, 8, 24),
+        (3, 0, Ok(&strings), 0, 0, 1, 0),
+    ];
+
+    let mut elf = Vec::new();
+    header(&mut elf, 1); // a relocatable file
+    elf.extend_from_slice(&0u64.to_le_bytes()); // no entry point
+    elf.extend_from_slice(&0u64.to_le_bytes()); // no program headers
+    let section_headers = elf.len();
+    elf.extend_from_slice(&0u64.to_le_bytes());
+    elf.extend_from_slice(&0u32.to_le_bytes());
+    for size in [
+        64u16,
+        0,
+        0,
+        64,
+        sections.len() as u16 + 1,
+        sections.len() as u16,
+    ] {
+        elf.extend_from_slice(&size.to_le_bytes());
+    }
+    let mut headers = vec![0; 64];
+    for (name, (kind, flags, contents, link, info, align, entry_size)) in
+        offsets.into_iter().zip(sections)
+    {
+        elf.resize(elf.len().next_multiple_of(align as usize), 0);
+        let start = elf.len() as u64;
+        let size = match contents {
+            Ok(contents) => {
+                elf.extend_from_slice(contents);
+                contents.len() as u64
+            }
+            Err(size) => size,
+        };
+        headers.extend_from_slice(&name.to_le_bytes());
+        headers.extend_from_slice(&kind.to_le_bytes());
+        headers.extend_from_slice(&flags.to_le_bytes());
+        headers.extend_from_slice(&0u64.to_le_bytes()); // no address before it is linked
+        headers.extend_from_slice(&start.to_le_bytes());
+        headers.extend_from_slice(&size.to_le_bytes());
+        headers.extend_from_slice(&link.to_le_bytes());
+        headers.extend_from_slice(&info.to_le_bytes());
+        headers.extend_from_slice(&align.to_le_bytes());
+        headers.extend_from_slice(&entry_size.to_le_bytes());
+    }
+    elf.resize(elf.len().next_multiple_of(8), 0);
+    let start = elf.len() as u64;
+    elf[section_headers..section_headers + 8].copy_from_slice(&start.to_le_bytes());
+    elf.extend_from_slice(&headers);
+    Ok(elf)
+}
+
+/// Writes the start of the ELF header of a file of `kind` for x86-64, up to the entry point
+fn header(elf: &mut Vec<u8>, kind: u16) {
+    elf.extend_from_slice(b"\x7fELF");
+    elf.extend_from_slice(&[2, 1, 1, 0]); // 64 bit, little endian, version 1, System V
+    elf.extend_from_slice(&[0; 8]);
+    elf.extend_from_slice(&kind.to_le_bytes());
+    elf.extend_from_slice(&0x3eu16.to_le_bytes()); // for x86-64
+    elf.extend_from_slice(&1u32.to_le_bytes());
+}
+
+/// The machine code being written, with the jumps whose target is not known yet
+struct Assembler {
+    buf: Vec<u8>,
+    /// Where every label is, once it is bound
+    labels: Vec<usize>,
+    /// Where to write the distance to the label, and the label
+    fixups: Vec<(usize, usize)>,
+    /// The bytes of a cell, 1, 2 or 4
+    cell_bytes: i32,
+    /// Whether the output is written after every `.`
+    unbuffered: bool,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+    /// Whether a move or an offset did not fit in 32 bits
+    too_far: bool,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes)
+    }
+
+    fn label(&mut self) -> usize {
+        self.labels.push(usize::MAX);
+        self.labels.len() - 1
+    }
+
+    fn bind(&mut self, label: usize) {
+        self.labels[label] = self.buf.len();
+    }
+
+    /// An instruction ending with a 32 bit distance to `label`, like a jump
+    fn jump(&mut self, opcode: &[u8], label: usize) {
+        self.emit(opcode);
+        self.fixups.push((self.buf.len(), label));
+        self.emit(&[0; 4]);
+    }
+
+    /// Writes the distances to the labels
+    fn resolve(&mut self) {
+        for &(at, label) in &self.fixups {
+            let distance = self.labels[label] as i32 - at as i32 - 4;
+            self.buf[at..at + 4].copy_from_slice(&distance.to_le_bytes());
+        }
+    }
+
+    /// The bytes `cells` cells make up, or 0 if that does not fit in 32 bits
+    fn bytes(&mut self, cells: isize) -> i32 {
+        match i32::try_from(cells)
+            .ok()
+            .and_then(|cells| cells.checked_mul(self.cell_bytes))
+        {
+            Some(bytes) => bytes,
+            None => {
+                self.too_far = true;
+                0
+            }
+        }
+    }
+
+    /// The operand size prefix of 16 bit cells
+    fn prefix(&mut self) {
+        if self.cell_bytes == 2 {
+            self.emit(&[0x66]);
+        }
+    }
+
+    /// `opcode` on `reg` and the cell `offset` away from `rbx`, where `reg` is a register or
+    /// the extension of the opcode
+    fn cell(&mut self, opcode: &[u8], reg: u8, offset: isize) {
+        let disp = self.bytes(offset);
+        self.emit(opcode);
+        match disp {
+            0 => self.emit(&[reg << 3 | 3]),
+            -128..=127 => self.emit(&[0x40 | reg << 3 | 3, disp as u8]),
+            _ => {
+                self.emit(&[0x80 | reg << 3 | 3]);
+                self.emit(&disp.to_le_bytes());
+            }
+        }
+    }
+
+    /// Compares the current cell with 0
+    fn test(&mut self) {
+        self.prefix();
+        match self.cell_bytes {
+            1 => self.cell(&[0x80], 7, 0), // cmp byte [rbx], 0
+            _ => self.cell(&[0x83], 7, 0), // cmp [rbx], 0
+        }
+        self.emit(&[0]);
+    }
+
+    fn move_by(&mut self, amount: isize) {
+        let bytes = self.bytes(amount);
+        match i8::try_from(bytes) {
+            Ok(bytes) => self.emit(&[0x48, 0x83, 0xc3, bytes as u8]), // add rbx, bytes
+            Err(_) => {
+                self.emit(&[0x48, 0x81, 0xc3]); // add rbx, bytes
+                self.emit(&bytes.to_le_bytes());
+            }
+        }
+    }
+
+    fn putchar(&mut self, putchar: usize, flush: usize) {
+        self.jump(&[0xe8], putchar); // call putchar
+        if self.unbuffered {
+            self.jump(&[0xe8], flush); // call flush
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node], putchar: usize, flush: usize, getch: usize) {
+        for node in nodes {
+            self.node(node, putchar, flush, getch);
+        }
+    }
+
+    fn node(&mut self, node: &Node, putchar: usize, flush: usize, getch: usize) {
+        let byte = self.cell_bytes == 1;
+        match *node {
+            Node::Move(amount) => self.move_by(amount),
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } => {
+                self.prefix();
+                match byte {
+                    true => self.cell(&[0x80], 0, offset), // add byte [rbx + offset], amount
+                    false => self.cell(&[0x83], 0, offset), // add [rbx + offset], amount
+                }
+                self.emit(&[amount]);
+            }
+            Node::SetZero => {
+                self.prefix();
+                match byte {
+                    true => self.cell(&[0xc6], 0, 0),  // mov byte [rbx], 0
+                    false => self.cell(&[0xc7], 0, 0), // mov [rbx], 0
+                }
+                let size = self.cell_bytes as usize;
+                self.emit(&[0; 4][..size]);
+            }
+            Node::Scan(amount) => {
+                let (start, end) = (self.label(), self.label());
+                self.bind(start);
+                self.test();
+                self.jump(&[0x0f, 0x84], end); // je end
+                self.move_by(amount);
+                self.jump(&[0xe9], start); // jmp start
+                self.bind(end);
+            }
+            Node::MulAdd { offset, factor } => {
+                match self.cell_bytes {
+                    1 => self.cell(&[0x0f, 0xb6], 0, 0), // movzx eax, byte [rbx]
+                    2 => self.cell(&[0x0f, 0xb7], 0, 0), // movzx eax, word [rbx]
+                    _ => self.cell(&[0x8b], 0, 0),       // mov eax, [rbx]
+                }
+                let skip = self.label();
+                if self.guard {
+                    self.emit(&[0x85, 0xc0]); // test eax, eax
+                    self.jump(&[0x0f, 0x84], skip); // je skip
+                }
+                let opcode = match factor {
+                    255 => 0x28, // sub
+                    _ => 0x00,   // add
+                };
+                if !matches!(factor, 1 | 255) {
+                    self.emit(&[0x6b, 0xc0, factor]); // imul eax, eax, factor
+                }
+                self.prefix();
+                match byte {
+                    true => self.cell(&[opcode], 0, offset), // [rbx + offset], al
+                    false => self.cell(&[opcode + 1], 0, offset), // [rbx + offset], eax
+                }
+                self.bind(skip);
+            }
+            Node::Output { offset } => {
+                // Only the low byte is written, like for the C code
+                self.cell(&[0x8a], 0, offset); // mov al, byte [rbx + offset]
+                self.putchar(putchar, flush);
+            }
+            Node::Print(value) => {
+                self.emit(&[0xb0, value]); // mov al, value
+                self.putchar(putchar, flush);
+            }
+            Node::Input { offset } => {
+                self.jump(&[0xe8], getch); // call getch
+                self.prefix();
+                match byte {
+                    true => self.cell(&[0x88], 0, offset), // mov byte [rbx + offset], al
+                    false => self.cell(&[0x89], 0, offset), // mov [rbx + offset], eax
+                }
+            }
+            Node::Loop { ref body, .. } => {
+                let (start, end) = (self.label(), self.label());
+                self.test();
+                self.jump(&[0x0f, 0x84], end); // je end
+                self.bind(start);
+                self.block(body, putchar, flush, getch);
+                self.test();
+                self.jump(&[0x0f, 0x85], start); // jne start
+                self.bind(end);
+            }
+            // There is no debug mode in the program
+            Node::DebugCell | Node::DebugMem => (),
+            // Turned down by `program`
+            _ => (),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "aot"))]
+mod tests {
+    use super::*;
+
+    fn options() -> CompileOptions<'static> {
+        CompileOptions {
+            mem_size: 300,
+            offset: 0,
+            cell_size: 8,
+            input: b"hi",
+            exit_cell: None,
+            debug: false,
+            debug_env: false,
+            unbuffered: false,
+        }
+    }
+
+    fn u16_at(file: &[u8], at: usize) -> u16 {
+        u16::from_le_bytes(file[at..at + 2].try_into().unwrap())
+    }
+
+    fn u64_at(file: &[u8], at: usize) -> u64 {
+        u64::from_le_bytes(file[at..at + 8].try_into().unwrap())
+    }
+
+    #[test]
+    fn object_has_the_code_of_the_executable() {
+        let config = Default::default();
+        let code = ir::optimize(ir::parse(",[.,]++[>+<-]>.", &config).unwrap(), &config);
+        let program = program(&code, &options()).unwrap();
+        let object = object(&code, &options()).unwrap();
+        assert_eq!(u16_at(&object, 16), 1);
+        assert_eq!(u16_at(&object, 18), 0x3e);
+        // The sections are `.text`, `.bss`, the note of the stack, `.symtab`, `.strtab`,
+        // `.rela.text` and their names
+        let headers = u64_at(&object, 40) as usize;
+        assert_eq!(u16_at(&object, 60), 8);
+        let section = |index: usize| &object[headers + index * 64..headers + (index + 1) * 64];
+        let (text, bss, relocation) = (section(1), section(2), section(6));
+        let start = u64_at(text, 24) as usize;
+        let text = &object[start..start + u64_at(text, 32) as usize];
+        assert_eq!(u64_at(bss, 32), MEM as u64 + 300);
+
+        // The address of the memory is left for the linker, after `mov r12`, and the rest is the
+        // code of the executable
+        let relocation = u64_at(relocation, 24) as usize;
+        let at = u64_at(&object, relocation) as usize;
+        assert_eq!(&text[at - 2..at + 8], &[0x49, 0xbc, 0, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(u64_at(&object, relocation + 8), 1 << 32 | 1);
+        let mut executable = program[HEADERS..].to_vec();
+        executable[at..at + 8].fill(0);
+        assert_eq!(text, executable);
+        let strings = section(5);
+        let start = u64_at(strings, 24) as usize;
+        assert_eq!(&object[start..start + 8], b"\0_start\0");
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{
+    bytecode::{self, Instr},
+    interpreter::{BfError, BfIo, Config, Interpreter, State},
+};
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+/// How many random inputs [`check`] tries after the short ones
+const RANDOM_INPUTS: usize = 256;
+/// The longest random input
+const RANDOM_LEN: usize = 32;
+
+/// How a program ended on an input, see [`check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum End {
+    Halted,
+    /// It read all the input and wanted more
+    NeedsInput,
+    OutOfBounds,
+    /// It was still running after all the steps, so only the output so far can be compared
+    OutOfSteps,
+}
+
+impl fmt::Display for End {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            End::Halted => write!(f, "halted"),
+            End::NeedsInput => write!(f, "needed more input"),
+            End::OutOfBounds => write!(f, "went out of the memory"),
+            End::OutOfSteps => write!(f, "was still running"),
+        }
+    }
+}
+
+/// What a program wrote on an input, and how it ended
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub output: Vec<u8>,
+    pub end: End,
+}
+
+/// An input the two programs of [`check`] do not agree on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    pub input: Vec<u8>,
+    pub outcomes: [Outcome; 2],
+}
+
+/// Collects the output, and gives no input besides what was fed
+struct Recorder(Vec<u8>);
+
+impl BfIo for Recorder {
+    fn read(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn write(&mut self, b: u8) {
+        self.0.push(b)
+    }
+}
+
+/// Runs `code`, lowered from `contents`, on `input` for at most `steps` instructions
+fn outcome(
+    contents: &str,
+    code: &[Instr],
+    config: Config,
+    input: &[u8],
+    steps: usize,
+) -> Result<Outcome, BfError> {
+    let mut interpreter = Interpreter::from_code(contents, code.to_vec(), config);
+    interpreter.feed(input);
+    let mut io = Recorder(Vec::new());
+    let end = match interpreter.run_steps(&mut io, steps) {
+        Ok(State::Halted) => End::Halted,
+        Ok(State::NeedsInput) => End::NeedsInput,
+        Ok(State::Paused) => End::OutOfSteps,
+        Err(BfError::MemoryOutOfBounds) => End::OutOfBounds,
+        Err(err) => return Err(err),
+    };
+    Ok(Outcome { output: io.0, end })
+}
+
+/// Whether the outcomes can come from the same program. A program that ran out of steps might
+/// still write what the other one did, so only the output it has so far is compared
+fn agree(a: &Outcome, b: &Outcome) -> bool {
+    match (a.end, b.end) {
+        (End::OutOfSteps, End::OutOfSteps) => {
+            a.output.starts_with(&b.output) || b.output.starts_with(&a.output)
+        }
+        (End::OutOfSteps, _) => b.output.starts_with(&a.output),
+        (_, End::OutOfSteps) => a.output.starts_with(&b.output),
+        _ => a == b,
+    }
+}
+
+/// The inputs [`check`] tries: no input, every single byte, then random ones
+fn inputs() -> impl Iterator<Item = Vec<u8>> {
+    // xorshift, so that the same inputs are tried every time
+    let mut state: u64 = 0x2545f4914f6cdd1d;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let random = (0..RANDOM_INPUTS).map(move |_| {
+        let len = next() as usize % RANDOM_LEN + 1;
+        (0..len).map(|_| next() as u8).collect::<Vec<_>>()
+    });
+    let bytes = (0..=255).map(|byte| vec![byte]);
+    [Vec::new()].into_iter().chain(bytes).chain(random)
+}
+
+/// Looks for an input on which `a` and `b` write something different or end differently,
+/// running each of them for at most `steps` instructions. `None` means that they agreed on
+/// every input tried, which makes them likely, but not certainly, equivalent
+pub fn check(
+    a: &str,
+    b: &str,
+    config: Config,
+    steps: usize,
+) -> Result<Option<Difference>, BfError> {
+    let code = [bytecode::lower(a, &config)?, bytecode::lower(b, &config)?];
+    // Without any `,` the input changes nothing, so one run is enough
+    let reads = code
+        .iter()
+        .flatten()
+        .any(|instr| matches!(instr, Instr::Input { .. }));
+    for input in inputs().take(if reads { usize::MAX } else { 1 }) {
+        let outcomes = [
+            outcome(a, &code[0], config, &input, steps)?,
+            outcome(b, &code[1], config, &input, steps)?,
+        ];
+        if !agree(&outcomes[0], &outcomes[1]) {
+            return Ok(Some(Difference { input, outcomes }));
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dialect::Dialect;
+
+    /// [`check`](super::check) with the default settings
+    fn check(a: &str, b: &str) -> Option<Difference> {
+        super::check(a, b, Config::default(), 1 << 16).unwrap()
+    }
+
+    #[test]
+    fn equivalent_programs_agree() {
+        for (a, b) in [
+            ("+++[-]", "[-]"),
+            (",[->+<]>.", ",[>+<-]>."),
+            (",.", ",>+<."),
+            ("++[>+++<-]>.", ">++++++."),
+            ("+[]", "+[+-]"),
+            // One that runs out of steps might still write what the other one does
+            ("+[]", "."),
+        ] {
+            assert_eq!(check(a, b), None, "{} and {}", a, b);
+        }
+    }
+
+    #[test]
+    fn different_programs_give_the_input() {
+        let difference = check(",.", ",[-]+.").unwrap();
+        assert_eq!(difference.input, [0]);
+        assert_eq!(
+            difference.outcomes,
+            [
+                Outcome {
+                    output: vec![0],
+                    end: End::Halted
+                },
+                Outcome {
+                    output: vec![1],
+                    end: End::Halted
+                },
+            ]
+        );
+
+        let difference = check(",.", ",+.").unwrap();
+        assert_eq!(difference.input, [0]);
+
+        let difference = check(".", "<.").unwrap();
+        assert_eq!(difference.outcomes[1].end, End::OutOfBounds);
+
+        let difference = check(".+[]", "+.").unwrap();
+        assert_eq!(difference.outcomes[0].end, End::OutOfSteps);
+    }
+
+    #[test]
+    fn check_runs_with_the_config() {
+        let config = Config {
+            mem_size: 3,
+            ..Config::default()
+        };
+        assert_eq!(check(">>>>.", "."), None);
+        let difference = super::check(">>>>.", ".", config, 1 << 16)
+            .unwrap()
+            .unwrap();
+        assert_eq!(difference.outcomes[0].end, End::OutOfBounds);
+
+        let config = Config {
+            dialect: Dialect::Pbrain,
+            ..Config::default()
+        };
+        // The body of a procedure only runs when it is called
+        assert!(check("(+).", "+.").is_none());
+        assert!(super::check("(+).", "+.", config, 1 << 16)
+            .unwrap()
+            .is_some());
+    }
+}
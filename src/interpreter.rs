@@ -0,0 +1,1301 @@
+use crate::{
+    bytecode::{self, Instr},
+    dialect::Dialect,
+    ir,
+};
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    format,
+    string::{String, ToString},
+    vec,
+    vec::Vec,
+};
+use core::{fmt, mem};
+
+/// Which cell's value becomes the exit code when the program ends
+#[derive(Clone, Copy)]
+pub enum ExitCell {
+    Current,
+    Cell(usize),
+}
+
+/// The settings of an [`Interpreter`]
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub mem_size: usize,
+    pub offset: usize,
+    pub debug: bool,
+    /// Every character is run instead of skipping the ones that are not instructions
+    pub verbose: bool,
+    pub exit_cell: Option<ExitCell>,
+    /// Which of the [`ir::PASSES`] run, as a mask
+    pub passes: u32,
+    /// Check the nodes after every pass and the instructions after lowering, see [`ir::optimize_with`]
+    pub verify_ir: bool,
+    pub dialect: Dialect,
+    /// How many tapes there are in the multitape dialect, each of `mem_size` cells
+    pub tapes: usize,
+    /// Where the random bytes of `?` start from, the same seed giving the same bytes
+    pub seed: u64,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            mem_size: 30000,
+            offset: 0,
+            debug: false,
+            verbose: false,
+            exit_cell: None,
+            passes: ir::level_passes(3),
+            verify_ir: false,
+            dialect: Dialect::Brainfuck,
+            tapes: 2,
+            seed: 0,
+        }
+    }
+}
+
+/// The input and output behind `,` and `.`
+pub trait BfIo {
+    /// Returns the next input byte, or `None` once there is no more input
+    fn read(&mut self) -> Option<u8>;
+    fn write(&mut self, b: u8);
+    /// Makes everything written so far visible, called before waiting for input
+    fn flush(&mut self) {}
+    /// Opens the file `name` that `:` of the files dialect writes to, or else the one `;` reads
+    /// from, after closing the one that was open for the same. An empty name only closes it.
+    /// Returns whether the file was opened, which it never is unless this is implemented
+    fn open(&mut self, _name: &[u8], _write: bool) -> bool {
+        false
+    }
+    /// Returns the next byte of the file open for reading, or `None` at its end or without one
+    fn read_file(&mut self) -> Option<u8> {
+        None
+    }
+    /// Writes `b` to the file open for writing, if there is one
+    fn write_file(&mut self, _b: u8) {}
+}
+
+impl<T: BfIo + ?Sized> BfIo for &mut T {
+    fn read(&mut self) -> Option<u8> {
+        (**self).read()
+    }
+    fn write(&mut self, b: u8) {
+        (**self).write(b)
+    }
+    fn flush(&mut self) {
+        (**self).flush()
+    }
+    fn open(&mut self, name: &[u8], write: bool) -> bool {
+        (**self).open(name, write)
+    }
+    fn read_file(&mut self) -> Option<u8> {
+        (**self).read_file()
+    }
+    fn write_file(&mut self, b: u8) {
+        (**self).write_file(b)
+    }
+}
+
+impl<T: BfIo + ?Sized> BfIo for Box<T> {
+    fn read(&mut self) -> Option<u8> {
+        (**self).read()
+    }
+    fn write(&mut self, b: u8) {
+        (**self).write(b)
+    }
+    fn flush(&mut self) {
+        (**self).flush()
+    }
+    fn open(&mut self, name: &[u8], write: bool) -> bool {
+        (**self).open(name, write)
+    }
+    fn read_file(&mut self) -> Option<u8> {
+        (**self).read_file()
+    }
+    fn write_file(&mut self, b: u8) {
+        (**self).write_file(b)
+    }
+}
+
+/// A cell that belongs to the host, see [`Interpreter::map_cell`]. It is implemented for a pair
+/// of closures, the first for `get` and the second for `set`
+pub trait HostCell {
+    /// The value the code finds in the cell, asked for every time it is read
+    fn get(&mut self) -> u8;
+    /// Called with every value the code writes to the cell
+    fn set(&mut self, value: u8);
+}
+
+impl<G: FnMut() -> u8, S: FnMut(u8)> HostCell for (G, S) {
+    fn get(&mut self) -> u8 {
+        (self.0)()
+    }
+    fn set(&mut self, value: u8) {
+        (self.1)(value)
+    }
+}
+
+#[derive(Debug)]
+pub enum BfError {
+    MemoryOutOfBounds,
+    InvalidCharacter(char),
+    UnbalancedBrackets,
+    InvalidSnapshot(String),
+    InvalidBytecode(String),
+    /// An optimization pass broke the code, found with [`Config::verify_ir`]
+    InvalidIr(String),
+    /// A `:` of pbrain for a value no procedure was made for
+    UndefinedProcedure(u8),
+    /// More than [`CALL_DEPTH`] procedures of pbrain were running at once
+    CallStackOverflow,
+    /// More than [`THREAD_LIMIT`] threads of Brainfork were running at once
+    TooManyThreads,
+    #[cfg(feature = "std")]
+    Io(std::io::Error),
+}
+
+impl fmt::Display for BfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BfError::MemoryOutOfBounds => write!(f, "Memory index out of bound"),
+            BfError::InvalidCharacter(ch) => write!(f, "Invalid BrainFuck character: '{}'", ch),
+            BfError::UnbalancedBrackets => write!(f, "Unbalanced Brackets"),
+            BfError::InvalidSnapshot(err) => write!(f, "Invalid snapshot: {}", err),
+            BfError::InvalidBytecode(err) => write!(f, "Invalid bytecode: {}", err),
+            BfError::InvalidIr(err) => write!(f, "Invalid IR {}", err),
+            BfError::UndefinedProcedure(value) => write!(f, "No procedure for {}", value),
+            BfError::CallStackOverflow => write!(f, "Call stack overflow"),
+            BfError::TooManyThreads => write!(f, "Too many threads"),
+            #[cfg(feature = "std")]
+            BfError::Io(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BfError {}
+
+#[cfg(feature = "std")]
+impl From<std::io::Error> for BfError {
+    fn from(err: std::io::Error) -> Self {
+        BfError::Io(err)
+    }
+}
+
+impl From<BfError> for String {
+    fn from(err: BfError) -> Self {
+        err.to_string()
+    }
+}
+
+#[cfg(feature = "std")]
+const SNAPSHOT_VERSION: u32 = 8;
+
+/// The most cells and the most memories, of the tapes and the threads, a snapshot can have, so
+/// that a broken one cannot make [`Interpreter::restore`] ask for more memory than there is
+#[cfg(feature = "std")]
+const SNAPSHOT_CELLS: usize = 1 << 30;
+#[cfg(feature = "std")]
+const SNAPSHOT_MEMORIES: usize = 1 << 16;
+
+/// How many procedures of pbrain can run inside each other
+pub const CALL_DEPTH: usize = 1 << 16;
+
+/// How many threads of Brainfork can run at once, each with a memory of its own
+pub const THREAD_LIMIT: usize = 1 << 10;
+
+/// Why [`Interpreter::run`] returned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+    /// A `,` was reached without any input, [`Interpreter::feed`] some and run again
+    NeedsInput,
+    /// The step quota of [`Interpreter::run_steps`] ran out
+    Paused,
+    /// The end of the code was reached
+    Halted,
+}
+
+/// How often a loop ran, see [`Interpreter::profile`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LoopCount {
+    /// How many times the `[` was reached
+    pub entries: u64,
+    /// How many times the body ran, over all the entries
+    pub iterations: u64,
+}
+
+/// A thread of Brainfork that waits for its turn
+#[derive(Clone)]
+struct Thread {
+    id: usize,
+    codeptr: usize,
+    mem: Vec<u8>,
+    cellptr: usize,
+}
+
+/// An interpreter that can stop in the middle of the program and be resumed later
+pub struct Interpreter {
+    source: String,
+    code: Vec<Instr>,
+    codeptr: usize,
+    mem: Vec<u8>,
+    cellptr: usize,
+    input: VecDeque<u8>,
+    debug_count: usize,
+    /// The [`LoopCount`] of every `[`, by the index of its instruction
+    profile: Option<Vec<LoopCount>>,
+    /// Whether the code is known to stay in the memory, so that moves do not need to be checked
+    in_bounds: bool,
+    /// Where the procedure of every value of a cell starts, for pbrain
+    procs: Vec<Option<usize>>,
+    /// Where every procedure that is running was called from
+    calls: Vec<usize>,
+    /// The value `$` stored, for Extended Brainfuck Type I
+    storage: u8,
+    /// The memory and the pointer of every tape of the multitape dialect. The one in use is in
+    /// `mem` and `cellptr` instead, and the ones never used yet are empty
+    tapes: Vec<(Vec<u8>, usize)>,
+    tape: usize,
+    /// The other threads of Brainfork, in the order they run in. The one running is in
+    /// `codeptr`, `mem` and `cellptr` instead, and is called `thread`
+    threads: VecDeque<Thread>,
+    thread: usize,
+    /// How many threads were forked, which is the id of the last one
+    forks: usize,
+    /// The state of the generator of `?`, starting at [`Config::seed`]
+    rng: u64,
+    /// Whether the last `;` of the files dialect read a byte
+    file_status: bool,
+    /// The cells that belong to the host, by their address
+    hosts: BTreeMap<usize, Box<dyn HostCell>>,
+    config: Config,
+}
+
+impl Interpreter {
+    pub fn new(contents: &str, config: Config) -> Result<Self, BfError> {
+        let lowered = bytecode::lower_with_info(contents, &config)?;
+        let mut interpreter = Self::from_code(contents, lowered.code, config);
+        interpreter.in_bounds = interpreter.fits(lowered.bounds);
+        Ok(interpreter)
+    }
+
+    /// Runs `code` that was already lowered from `contents`, such as one read from a `.bfc` file
+    pub fn from_code(contents: &str, code: Vec<Instr>, config: Config) -> Self {
+        Self {
+            source: contents.into(),
+            code,
+            codeptr: 0,
+            mem: vec![0; config.mem_size.max(config.offset) + 1],
+            cellptr: config.offset,
+            input: VecDeque::new(),
+            debug_count: 0,
+            profile: None,
+            in_bounds: false,
+            procs: vec![None; 256],
+            calls: Vec::new(),
+            storage: 0,
+            tapes: vec![Default::default(); Self::tape_count(&config)],
+            tape: 0,
+            threads: VecDeque::new(),
+            thread: 0,
+            forks: 0,
+            rng: config.seed,
+            file_status: false,
+            hosts: BTreeMap::new(),
+            config,
+        }
+    }
+
+    /// How many tapes the code of `config` can use
+    fn tape_count(config: &Config) -> usize {
+        match config.dialect {
+            Dialect::Multitape => config.tapes.max(1),
+            _ => 1,
+        }
+    }
+
+    /// Whether code touching `bounds` stays in the memory when it starts on the current cell
+    fn fits(&self, bounds: Option<ir::Bounds>) -> bool {
+        bounds.is_some_and(|bounds| {
+            let low = self.cellptr.checked_add_signed(bounds.low);
+            let high = self.cellptr.checked_add_signed(bounds.high);
+            low.is_some() && high.is_some_and(|high| high < self.mem.len())
+        })
+    }
+
+    /// Replaces the code with `contents` and starts from its beginning, keeping the memory
+    pub fn load(&mut self, contents: &str) -> Result<(), BfError> {
+        // The passes that know the value of cells count on the memory being all 0 at the start
+        if self.mem.iter().any(|&cell| cell != 0) {
+            for name in ["dead-code", "unroll", "const-fold", "eval"] {
+                self.config.passes &= !ir::pass_mask(name).unwrap_or(0);
+            }
+        }
+        let lowered = bytecode::lower_with_info(contents, &self.config)?;
+        self.code = lowered.code;
+        self.in_bounds = self.fits(lowered.bounds);
+        self.source = contents.into();
+        self.codeptr = 0;
+        // The procedures were made by the code they start in, and the threads ran it
+        self.procs.fill(None);
+        self.calls.clear();
+        self.threads.clear();
+        if self.profile.is_some() {
+            self.enable_profile()
+        }
+        Ok(())
+    }
+
+    /// Binds the cell at `cell` to the host, so that the code reads it from `host` and writes it
+    /// to `host`, on every tape and in every thread. A `+` does both. The code is lowered again
+    /// without any passes, so that every instruction of the source reaches the cell, and starts
+    /// from its beginning, so map the cells before running it
+    pub fn map_cell(&mut self, cell: usize, host: impl HostCell + 'static) -> Result<(), BfError> {
+        if cell >= self.mem.len() {
+            return Err(BfError::MemoryOutOfBounds);
+        }
+        self.hosts.insert(cell, Box::new(host));
+        if self.config.passes != 0 {
+            self.config.passes = 0;
+            let source = mem::take(&mut self.source);
+            self.load(&source)?;
+        }
+        Ok(())
+    }
+
+    /// Gives the cell at `cell` back to the code, returning what it was mapped to
+    pub fn unmap_cell(&mut self, cell: usize) -> Option<Box<dyn HostCell>> {
+        self.hosts.remove(&cell)
+    }
+
+    /// Starts counting how often every loop runs, which slows the loops down a bit
+    pub fn enable_profile(&mut self) {
+        self.profile = Some(vec![LoopCount::default(); self.code.len()])
+    }
+
+    /// How often every loop ran since [`Interpreter::enable_profile`], in the order of their `[`
+    /// in the source. Loops that became a single instruction, like `[-]`, stay at 0
+    pub fn profile(&self) -> Option<Vec<LoopCount>> {
+        let counts = self.profile.as_ref()?;
+        let loops = bytecode::lower_with_info(&self.source, &self.config)
+            .ok()?
+            .loops;
+        let mut profile = vec![LoopCount::default(); self.source.matches('[').count()];
+        let starts = self
+            .code
+            .iter()
+            .enumerate()
+            .filter(|(_, instr)| matches!(instr, Instr::JumpIfZero(_)));
+        for ((start, _), bracket) in starts.zip(loops) {
+            profile[bracket] = counts[start];
+        }
+        Some(profile)
+    }
+
+    /// Queues input that `,` reads before asking the [`BfIo`]
+    pub fn feed(&mut self, input: &[u8]) {
+        self.input.extend(input)
+    }
+
+    pub fn mem(&self) -> &[u8] {
+        &self.mem
+    }
+
+    pub fn cellptr(&self) -> usize {
+        self.cellptr
+    }
+
+    /// The tape `mem` and `cellptr` are the ones of, always 0 outside of the multitape dialect
+    pub fn tape(&self) -> usize {
+        self.tape
+    }
+
+    /// The thread `mem`, `cellptr` and `codeptr` are the ones of, always 0 outside of Brainfork.
+    /// The first thread is 0, and the others are counted in the order they were forked
+    pub fn thread(&self) -> usize {
+        self.thread
+    }
+
+    pub fn codeptr(&self) -> usize {
+        self.codeptr
+    }
+
+    /// The code that is being run
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// The instructions lowered from the source
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    /// The instruction that runs next, or `None` once the program has ended
+    pub fn current(&self) -> Option<Instr> {
+        self.code.get(self.codeptr).copied()
+    }
+
+    /// The value of the exit cell, or 0 if there is none
+    pub fn exit_code(&self) -> i32 {
+        match self.config.exit_cell {
+            Some(ExitCell::Current) => self.mem[self.cellptr] as i32,
+            Some(ExitCell::Cell(cell)) => self.mem.get(cell).copied().unwrap_or(0) as i32,
+            None => 0,
+        }
+    }
+
+    /// Runs until the program ends or needs input
+    pub fn run(&mut self, io: &mut impl BfIo) -> Result<State, BfError> {
+        self.run_steps(io, usize::MAX)
+    }
+
+    /// Runs at most `steps` instructions
+    pub fn run_steps(&mut self, io: &mut impl BfIo, steps: usize) -> Result<State, BfError> {
+        let mut steps = steps;
+        loop {
+            let Some(&instr) = self.code.get(self.codeptr) else {
+                // The program ends with the last thread
+                match self.threads.pop_front() {
+                    Some(thread) => {
+                        self.switch_thread(thread);
+                        continue;
+                    }
+                    None => return Ok(State::Halted),
+                }
+            };
+            if steps == 0 {
+                return Ok(State::Paused);
+            }
+            steps -= 1;
+            let cellptr = self.cellptr;
+            if !self.hosts.is_empty() {
+                self.pull(instr);
+            }
+            match instr {
+                Instr::Move(amount) => {
+                    if !self.in_bounds {
+                        self.cell(amount)?;
+                    }
+                    self.cellptr = self.cellptr.wrapping_add_signed(amount);
+                }
+                Instr::Add { offset, amount } => {
+                    let cell = self.cell(offset)?;
+                    *cell = cell.wrapping_add(amount)
+                }
+                Instr::SetZero => self.mem[self.cellptr] = 0,
+                Instr::Set(value) => self.mem[self.cellptr] = value,
+                Instr::AddMove { offset, amount, by } => {
+                    let cell = self.cell(offset)?;
+                    *cell = cell.wrapping_add(amount);
+                    if !self.in_bounds {
+                        self.cell(by)?;
+                    }
+                    self.cellptr = self.cellptr.wrapping_add_signed(by);
+                }
+                Instr::Scan(amount) => self.scan(amount)?,
+                Instr::MulAdd { offset, factor } => {
+                    let value = self.mem[self.cellptr];
+                    if value != 0 {
+                        let cell = self.cell(offset)?;
+                        *cell = cell.wrapping_add(value.wrapping_mul(factor))
+                    }
+                }
+                Instr::Output { offset } => io.write(*self.cell(offset)?),
+                Instr::Print(value) => io.write(value),
+                Instr::Input { offset } => {
+                    io.flush();
+                    match self.input.pop_front().or_else(|| io.read()) {
+                        Some(byte) => *self.cell(offset)? = byte,
+                        None => return Ok(State::NeedsInput),
+                    }
+                }
+                Instr::JumpIfZero(target) => {
+                    let zero = self.mem[self.cellptr] == 0;
+                    if let Some(profile) = &mut self.profile {
+                        profile[self.codeptr].entries += 1;
+                        profile[self.codeptr].iterations += !zero as u64;
+                    }
+                    if zero {
+                        self.codeptr = target
+                    }
+                }
+                Instr::JumpUnlessZero(target) => {
+                    if self.mem[self.cellptr] != 0 {
+                        if let Some(profile) = &mut self.profile {
+                            profile[target].iterations += 1;
+                        }
+                        self.codeptr = target
+                    }
+                }
+                Instr::DebugCell => {
+                    self.debug_count += 1;
+
+                    format!(
+                        "\ndebug flag {} : {} {} {}{}\n",
+                        self.debug_count,
+                        self.mem[self.cellptr] as char,
+                        self.mem[self.cellptr],
+                        self.cellptr,
+                        self.tape_name()
+                    )
+                    .bytes()
+                    .for_each(|b| io.write(b))
+                }
+                Instr::DebugMem => format!(
+                    "\n{:?}{}\n",
+                    &self.mem
+                        [self.cellptr.saturating_sub(10)..(self.cellptr + 11).min(self.mem.len())],
+                    self.tape_name()
+                )
+                .bytes()
+                .for_each(|b| io.write(b)),
+                Instr::Proc(end) => {
+                    self.procs[self.mem[self.cellptr] as usize] = Some(self.codeptr + 1);
+                    self.codeptr = end;
+                }
+                Instr::Return => {
+                    self.codeptr = self.calls.pop().ok_or_else(|| {
+                        BfError::InvalidBytecode("return outside of a procedure".into())
+                    })?;
+                }
+                Instr::Call => {
+                    let value = self.mem[self.cellptr];
+                    let start =
+                        self.procs[value as usize].ok_or(BfError::UndefinedProcedure(value))?;
+                    if self.calls.len() == CALL_DEPTH {
+                        return Err(BfError::CallStackOverflow);
+                    }
+                    self.calls.push(self.codeptr);
+                    self.codeptr = start;
+                    continue;
+                }
+                Instr::Store => self.storage = self.mem[self.cellptr],
+                Instr::Retrieve => self.mem[self.cellptr] = self.storage,
+                Instr::Bits(op) => {
+                    let cell = &mut self.mem[self.cellptr];
+                    *cell = op.apply(*cell, self.storage)
+                }
+                Instr::End => {
+                    self.codeptr = self.code.len();
+                    self.calls.clear();
+                    return Ok(State::Halted);
+                }
+                Instr::Tape(amount) => self.switch_tape(amount),
+                Instr::Fork => self.fork()?,
+                Instr::Random => self.mem[self.cellptr] = self.random(),
+                Instr::FileOpen { write } => {
+                    let name = &self.mem[self.cellptr..];
+                    let len = name.iter().position(|&cell| cell == 0);
+                    let opened = io.open(&name[..len.unwrap_or(name.len())], write);
+                    self.mem[self.cellptr] = opened as u8;
+                }
+                Instr::FileRead => {
+                    let byte = io.read_file();
+                    self.file_status = byte.is_some();
+                    self.mem[self.cellptr] = byte.unwrap_or(0);
+                }
+                Instr::FileWrite => io.write_file(self.mem[self.cellptr]),
+                Instr::FileStatus => self.mem[self.cellptr] = self.file_status as u8,
+            }
+            if !self.hosts.is_empty() {
+                self.push(instr, cellptr);
+            }
+            self.codeptr += 1;
+            // The threads take turns after every instruction
+            if let Some(thread) = self.threads.pop_front() {
+                let current = self.switch_thread(thread);
+                self.threads.push_back(current);
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    /// The cell `offset` away from the pointer
+    fn cell(&mut self, offset: isize) -> Result<&mut u8, BfError> {
+        self.cellptr
+            .checked_add_signed(offset)
+            .and_then(|cell| self.mem.get_mut(cell))
+            .ok_or(BfError::MemoryOutOfBounds)
+    }
+
+    /// Brings the mapped cells `instr` reads in from the host
+    fn pull(&mut self, instr: Instr) {
+        let start = match instr {
+            Instr::Add { offset, .. } | Instr::Output { offset } => {
+                self.cellptr.wrapping_add_signed(offset)
+            }
+            Instr::JumpIfZero(_)
+            | Instr::JumpUnlessZero(_)
+            | Instr::Proc(_)
+            | Instr::Call
+            | Instr::Store
+            | Instr::Bits(_)
+            | Instr::Fork
+            | Instr::FileOpen { .. }
+            | Instr::FileWrite => self.cellptr,
+            _ => return,
+        };
+        // The name of a file goes on up to the first 0 cell, which can be a mapped one too
+        let end = match instr {
+            Instr::FileOpen { .. } => self.mem.len(),
+            _ => start.saturating_add(1),
+        };
+        for cell in start..end {
+            if let (Some(host), Some(value)) = (self.hosts.get_mut(&cell), self.mem.get_mut(cell)) {
+                *value = host.get();
+            }
+            if self.mem.get(cell).is_none_or(|&value| value == 0) {
+                break;
+            }
+        }
+    }
+
+    /// Sends the mapped cell `instr` wrote out to the host, with the pointer at `cellptr` before it
+    fn push(&mut self, instr: Instr, cellptr: usize) {
+        let cell = match instr {
+            Instr::Add { offset, .. } | Instr::Input { offset } => {
+                cellptr.wrapping_add_signed(offset)
+            }
+            Instr::Retrieve
+            | Instr::Bits(_)
+            | Instr::Fork
+            | Instr::Random
+            | Instr::FileOpen { .. }
+            | Instr::FileRead
+            | Instr::FileStatus => cellptr,
+            // The other instructions that write come from the passes, which are off
+            _ => return,
+        };
+        if let (Some(host), Some(&value)) = (self.hosts.get_mut(&cell), self.mem.get(cell)) {
+            host.set(value)
+        }
+    }
+
+    /// Which tape or thread the debug views are on, only said when there can be more than one
+    fn tape_name(&self) -> String {
+        match self.config.dialect {
+            Dialect::Brainfork => format!(" in thread {}", self.thread),
+            _ if self.tapes.len() > 1 => format!(" on tape {}", self.tape),
+            _ => String::new(),
+        }
+    }
+
+    /// Forks a thread that runs after the current instruction, with a copy of the memory and
+    /// the pointer on the next cell, which becomes 1. The current cell becomes 0
+    fn fork(&mut self) -> Result<(), BfError> {
+        if self.threads.len() + 1 == THREAD_LIMIT {
+            return Err(BfError::TooManyThreads);
+        }
+        let cellptr = self.cellptr + 1;
+        let mut mem = self.mem.clone();
+        *mem.get_mut(cellptr).ok_or(BfError::MemoryOutOfBounds)? = 1;
+        self.mem[self.cellptr] = 0;
+        self.forks += 1;
+        self.threads.push_back(Thread {
+            id: self.forks,
+            codeptr: self.codeptr + 1,
+            mem,
+            cellptr,
+        });
+        Ok(())
+    }
+
+    /// The next byte of SplitMix64, which the C code has too so that a seed gives the same
+    /// bytes in both
+    fn random(&mut self) -> u8 {
+        self.rng = self.rng.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.rng;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        ((z ^ (z >> 31)) >> 56) as u8
+    }
+
+    /// Runs `thread` instead of the current one, which is returned
+    fn switch_thread(&mut self, thread: Thread) -> Thread {
+        Thread {
+            id: mem::replace(&mut self.thread, thread.id),
+            codeptr: mem::replace(&mut self.codeptr, thread.codeptr),
+            mem: mem::replace(&mut self.mem, thread.mem),
+            cellptr: mem::replace(&mut self.cellptr, thread.cellptr),
+        }
+    }
+
+    /// Goes to the tape `amount` after the current one, making its memory the first time
+    fn switch_tape(&mut self, amount: isize) {
+        let count = self.tapes.len() as isize;
+        let next = (self.tape as isize + amount).rem_euclid(count) as usize;
+        self.tapes[self.tape] = (mem::take(&mut self.mem), self.cellptr);
+        (self.mem, self.cellptr) = mem::take(&mut self.tapes[next]);
+        if self.mem.is_empty() {
+            self.mem = vec![0; self.config.mem_size.max(self.config.offset) + 1];
+            self.cellptr = self.config.offset;
+        }
+        self.tape = next;
+    }
+
+    /// Moves by `amount` until the current cell is 0
+    fn scan(&mut self, amount: isize) -> Result<(), BfError> {
+        let step = amount.unsigned_abs();
+        self.cellptr = if amount > 0 {
+            let mut cells = self.mem[self.cellptr..].iter().step_by(step);
+            let pos = cells.position(|&cell| cell == 0);
+            self.cellptr + pos.ok_or(BfError::MemoryOutOfBounds)? * step
+        } else {
+            let mut cells = self.mem[..=self.cellptr].iter().rev().step_by(step);
+            let pos = cells.position(|&cell| cell == 0);
+            self.cellptr - pos.ok_or(BfError::MemoryOutOfBounds)? * step
+        };
+        Ok(())
+    }
+}
+
+#[cfg(feature = "jit")]
+impl Interpreter {
+    /// Runs the rest of the program as native code, reading 0 once there is no input left.
+    /// Returns `None` if the code can't be compiled, like in the debug mode
+    pub fn run_jit(&mut self, io: &mut impl BfIo) -> Option<Result<(), BfError>> {
+        if !self.threads.is_empty() || !self.hosts.is_empty() {
+            return None;
+        }
+        let jit = crate::jit::Jit::compile(&self.code, self.codeptr)?;
+        let result = jit.run(&mut self.mem, self.cellptr, &mut self.input, io);
+        Some(result.map(|cell| {
+            self.cellptr = cell;
+            self.codeptr = self.code.len();
+        }))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Interpreter {
+    /// Writes the whole state to `w` so that it can be brought back with [`Interpreter::restore`].
+    /// The source is written instead of the instructions, which are lowered again from it.
+    /// The mapped cells are not saved, and have to be mapped again after restoring it
+    pub fn save(&self, mut w: impl std::io::Write) -> std::io::Result<()> {
+        let join = |bytes: &mut dyn Iterator<Item = &u8>| {
+            bytes.map(u8::to_string).collect::<Vec<_>>().join(" ")
+        };
+        writeln!(w, "brainfuck snapshot {}", SNAPSHOT_VERSION)?;
+        writeln!(w, "mem_size {}", self.config.mem_size)?;
+        writeln!(w, "offset {}", self.config.offset)?;
+        writeln!(w, "debug {}", self.config.debug)?;
+        writeln!(w, "verbose {}", self.config.verbose)?;
+        writeln!(w, "passes {}", self.config.passes)?;
+        writeln!(w, "dialect {}", self.config.dialect.name())?;
+        writeln!(w, "tapes {}", self.config.tapes)?;
+        writeln!(w, "seed {}", self.config.seed)?;
+        match self.config.exit_cell {
+            Some(ExitCell::Current) => writeln!(w, "exit_cell current")?,
+            Some(ExitCell::Cell(cell)) => writeln!(w, "exit_cell {}", cell)?,
+            None => writeln!(w, "exit_cell none")?,
+        }
+        writeln!(w, "debug_count {}", self.debug_count)?;
+        writeln!(w, "cellptr {}", self.cellptr)?;
+        writeln!(w, "codeptr {}", self.codeptr)?;
+        // The zeros at the end of a memory are left out and put back by `restore`
+        let trimmed = |mem: &[u8]| {
+            let used = mem
+                .iter()
+                .rposition(|&cell| cell != 0)
+                .map_or(0, |last| last + 1);
+            join(&mut mem[..used].iter())
+        };
+        writeln!(w, "mem {}", trimmed(&self.mem))?;
+        writeln!(w, "input {}", join(&mut self.input.iter()))?;
+        // The procedures as their value followed by where they start
+        let procs = self
+            .procs
+            .iter()
+            .enumerate()
+            .filter_map(|(value, start)| start.map(|start| format!("{} {}", value, start)));
+        writeln!(w, "procs {}", procs.collect::<Vec<_>>().join(" "))?;
+        let calls = self.calls.iter().map(usize::to_string);
+        writeln!(w, "calls {}", calls.collect::<Vec<_>>().join(" "))?;
+        writeln!(w, "storage {}", self.storage)?;
+        writeln!(w, "tape {}", self.tape)?;
+        // The other tapes that were used, as their pointer followed by their memory
+        for (tape, (mem, cellptr)) in self.tapes.iter().enumerate() {
+            if !mem.is_empty() {
+                writeln!(w, "tape_{} {} {}", tape, cellptr, trimmed(mem))?;
+            }
+        }
+        writeln!(w, "rng {}", self.rng)?;
+        // The open files are in the io, which is not saved
+        writeln!(w, "file_status {}", self.file_status)?;
+        writeln!(w, "thread {}", self.thread)?;
+        writeln!(w, "forks {}", self.forks)?;
+        writeln!(w, "threads {}", self.threads.len())?;
+        // The threads waiting for their turn, as their id, where they are in the code and their
+        // pointer, followed by their memory
+        for (i, thread) in self.threads.iter().enumerate() {
+            writeln!(
+                w,
+                "thread_{} {} {} {} {}",
+                i,
+                thread.id,
+                thread.codeptr,
+                thread.cellptr,
+                trimmed(&thread.mem)
+            )?;
+        }
+        write!(w, "code {}", self.source)
+    }
+
+    /// Reads back a state written by [`Interpreter::save`]
+    pub fn restore(mut r: impl std::io::Read) -> Result<Self, BfError> {
+        let invalid = |what: &str| BfError::InvalidSnapshot(what.to_owned());
+        let mut snapshot = String::new();
+        r.read_to_string(&mut snapshot)?;
+        let (header, code) = snapshot
+            .split_once("\ncode ")
+            .ok_or_else(|| invalid("missing code"))?;
+        let fields = header
+            .lines()
+            .map(|line| line.split_once(' ').unwrap_or((line, "")))
+            .collect::<std::collections::HashMap<_, _>>();
+        let field = |key: &str| fields.get(key).copied().ok_or_else(|| invalid(key));
+        let number = |key: &str| field(key)?.parse::<usize>().map_err(|_| invalid(key));
+        let flag = |key: &str| field(key)?.parse::<bool>().map_err(|_| invalid(key));
+        let bytes = |key: &str| {
+            field(key)?
+                .split_whitespace()
+                .map(|byte| byte.parse::<u8>().map_err(|_| invalid(key)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let numbers = |key: &str| {
+            field(key)?
+                .split_whitespace()
+                .map(|number| number.parse::<usize>().map_err(|_| invalid(key)))
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        if field("brainfuck")? != format!("snapshot {}", SNAPSHOT_VERSION) {
+            return Err(invalid("unknown version"));
+        }
+        let mut interpreter = Self {
+            source: String::new(),
+            code: Vec::new(),
+            codeptr: 0,
+            mem: Vec::new(),
+            cellptr: number("cellptr")?,
+            input: bytes("input")?.into(),
+            debug_count: number("debug_count")?,
+            profile: None,
+            in_bounds: false,
+            procs: vec![None; 256],
+            calls: Vec::new(),
+            storage: number("storage")?
+                .try_into()
+                .map_err(|_| invalid("storage"))?,
+            tapes: Vec::new(),
+            tape: number("tape")?,
+            threads: VecDeque::new(),
+            thread: number("thread")?,
+            forks: number("forks")?,
+            rng: field("rng")?.parse().map_err(|_| invalid("rng"))?,
+            file_status: flag("file_status")?,
+            hosts: BTreeMap::new(),
+            config: Config {
+                mem_size: number("mem_size")?,
+                offset: number("offset")?,
+                debug: flag("debug")?,
+                verbose: flag("verbose")?,
+                passes: field("passes")?.parse().map_err(|_| invalid("passes"))?,
+                verify_ir: false,
+                dialect: Dialect::from_name(field("dialect")?).ok_or_else(|| invalid("dialect"))?,
+                tapes: number("tapes")?,
+                seed: field("seed")?.parse().map_err(|_| invalid("seed"))?,
+                exit_cell: match field("exit_cell")? {
+                    "none" => None,
+                    "current" => Some(ExitCell::Current),
+                    _ => Some(ExitCell::Cell(number("exit_cell")?)),
+                },
+            },
+        };
+        let threads = number("threads")?;
+        if threads >= THREAD_LIMIT {
+            return Err(invalid("threads"));
+        }
+        // Every tape and every thread gets a memory of `size` cells
+        let memories = Self::tape_count(&interpreter.config)
+            .checked_add(threads)
+            .filter(|&memories| memories <= SNAPSHOT_MEMORIES)
+            .ok_or_else(|| invalid("tapes"))?;
+        let size = interpreter
+            .config
+            .mem_size
+            .max(interpreter.config.offset)
+            .checked_add(1)
+            .filter(|size| {
+                size.checked_mul(memories)
+                    .is_some_and(|cells| cells <= SNAPSHOT_CELLS)
+            })
+            .ok_or_else(|| invalid("mem_size"))?;
+        // Loaded before the memory so that the passes are the ones the code was saved with
+        interpreter.load(code)?;
+        interpreter.mem = bytes("mem")?;
+        interpreter.codeptr = number("codeptr")?;
+        for pair in numbers("procs")?.chunks(2) {
+            match *pair {
+                [value, start] if value < 256 && start < interpreter.code.len() => {
+                    interpreter.procs[value] = Some(start)
+                }
+                _ => return Err(invalid("procs")),
+            }
+        }
+        interpreter.calls = numbers("calls")?;
+        // The bounds are from the start of the code, which may not be where it resumes
+        interpreter.in_bounds = false;
+        if interpreter.mem.len() > size {
+            return Err(invalid("mem"));
+        }
+        interpreter.mem.resize(size, 0);
+        interpreter.tapes = vec![Default::default(); Self::tape_count(&interpreter.config)];
+        if interpreter.tape >= interpreter.tapes.len() {
+            return Err(invalid("tape"));
+        }
+        for tape in 0..interpreter.tapes.len() {
+            let key = format!("tape_{}", tape);
+            if tape == interpreter.tape || !fields.contains_key(key.as_str()) {
+                continue;
+            }
+            let values = numbers(&key)?;
+            let (&cellptr, cells) = values.split_first().ok_or_else(|| invalid(&key))?;
+            let mut mem = cells
+                .iter()
+                .map(|&cell| u8::try_from(cell).map_err(|_| invalid(&key)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if mem.len() > size || cellptr >= size {
+                return Err(invalid(&key));
+            }
+            mem.resize(size, 0);
+            interpreter.tapes[tape] = (mem, cellptr);
+        }
+        for i in 0..threads {
+            let key = format!("thread_{}", i);
+            let values = numbers(&key)?;
+            let [id, codeptr, cellptr, ref cells @ ..] = values[..] else {
+                return Err(invalid(&key));
+            };
+            let mut mem = cells
+                .iter()
+                .map(|&cell| u8::try_from(cell).map_err(|_| invalid(&key)))
+                .collect::<Result<Vec<_>, _>>()?;
+            if mem.len() > size || cellptr >= size || codeptr > interpreter.code.len() {
+                return Err(invalid(&key));
+            }
+            mem.resize(size, 0);
+            interpreter.threads.push_back(Thread {
+                id,
+                codeptr,
+                mem,
+                cellptr,
+            });
+        }
+        if interpreter.cellptr >= interpreter.mem.len()
+            || interpreter.codeptr > interpreter.code.len()
+            || interpreter
+                .calls
+                .iter()
+                .any(|&call| call >= interpreter.code.len())
+        {
+            return Err(invalid("pointer out of bounds"));
+        }
+        Ok(interpreter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Collects the output, and gives no input besides what was fed
+    struct Recorder(Vec<u8>);
+
+    impl BfIo for Recorder {
+        fn read(&mut self) -> Option<u8> {
+            None
+        }
+
+        fn write(&mut self, b: u8) {
+            self.0.push(b)
+        }
+    }
+
+    /// A snapshot of a program paused after writing `A`, before reading its input
+    #[cfg(feature = "std")]
+    fn snapshot() -> String {
+        let mut interpreter =
+            Interpreter::new("+++++++[>+++++++++<-]>++.,.", Config::default()).unwrap();
+        let mut io = Recorder(Vec::new());
+        assert!(matches!(interpreter.run(&mut io), Ok(State::NeedsInput)));
+        assert_eq!(io.0, b"A");
+        let mut snapshot = Vec::new();
+        interpreter.save(&mut snapshot).unwrap();
+        String::from_utf8(snapshot).unwrap()
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn restore_goes_on_from_the_snapshot() {
+        let mut interpreter = Interpreter::restore(snapshot().as_bytes()).unwrap();
+        interpreter.feed(b"z");
+        let mut io = Recorder(Vec::new());
+        assert!(matches!(interpreter.run(&mut io), Ok(State::Halted)));
+        assert_eq!(io.0, b"z");
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn restore_turns_down_broken_snapshots() {
+        let snapshot = snapshot();
+        let mem_size = format!("mem_size {}", Config::default().mem_size);
+        for (from, to) in [
+            (mem_size.clone(), format!("mem_size {}", usize::MAX)),
+            (mem_size.clone(), format!("mem_size {}", SNAPSHOT_CELLS)),
+            ("offset 0".into(), format!("offset {}", usize::MAX)),
+            (
+                "dialect brainfuck\ntapes 2".into(),
+                format!("dialect multitape\ntapes {}", usize::MAX),
+            ),
+            (
+                "dialect brainfuck\ntapes 2".into(),
+                format!("dialect multitape\ntapes {}", SNAPSHOT_MEMORIES),
+            ),
+            ("threads 0".into(), format!("threads {}", THREAD_LIMIT)),
+            ("cellptr 0".into(), format!("cellptr {}", usize::MAX)),
+            ("mem 0 65".into(), format!("mem{}", " 1".repeat(30002))),
+            ("mem 0 65".into(), "mem 0 256".into()),
+            ("codeptr 5".into(), "codeptr 99".into()),
+            ("brainfuck snapshot".into(), "brainfuck snapshot 0 ".into()),
+        ] {
+            assert!(snapshot.contains(&from), "{}", from);
+            let broken = snapshot.replacen(&from, &to, 1);
+            assert!(
+                matches!(
+                    Interpreter::restore(broken.as_bytes()),
+                    Err(BfError::InvalidSnapshot(_))
+                ),
+                "{}",
+                to
+            );
+        }
+    }
+
+    /// Keeps what the interpreter asked of it in order, `|` for a flush and `r` for a read
+    struct Log {
+        input: Vec<u8>,
+        events: String,
+    }
+
+    impl BfIo for Log {
+        fn read(&mut self) -> Option<u8> {
+            self.events.push('r');
+            self.input.pop()
+        }
+
+        fn write(&mut self, b: u8) {
+            self.events.push(b as char)
+        }
+
+        fn flush(&mut self) {
+            self.events.push('|')
+        }
+    }
+
+    #[test]
+    fn io_is_flushed_before_reading() {
+        let mut interpreter =
+            Interpreter::new("++++++++[>++++++++<-]>+.,.,.", Config::default()).unwrap();
+        let mut io = Log {
+            input: b"B".to_vec(),
+            events: String::new(),
+        };
+        assert!(matches!(interpreter.run(&mut io), Ok(State::NeedsInput)));
+        assert_eq!(io.events, "A|rB|r");
+        // The files are only there where they are implemented
+        assert!(!io.open(b"file", true));
+        assert_eq!(io.read_file(), None);
+        interpreter.feed(b"C");
+        assert!(matches!(interpreter.run(&mut io), Ok(State::Halted)));
+        assert_eq!(io.events, "A|rB|r|C");
+    }
+
+    #[test]
+    fn run_steps_pauses_and_goes_on() {
+        let code = "++++++++[>++++++++<-]>+.>+++[-<+>]<.";
+        let mut interpreter = Interpreter::new(code, Config::default()).unwrap();
+        let mut io = Recorder(Vec::new());
+        assert!(matches!(
+            interpreter.run_steps(&mut io, 0),
+            Ok(State::Paused)
+        ));
+        assert_eq!(interpreter.codeptr(), 0);
+        // It stops in between the outputs, and goes on from there
+        let mut between = false;
+        while let State::Paused = interpreter.run_steps(&mut io, 1).unwrap() {
+            between |= io.0 == b"A";
+        }
+        assert!(between);
+        assert_eq!(io.0, b"AD");
+        assert!(matches!(
+            interpreter.run_steps(&mut io, 1),
+            Ok(State::Halted)
+        ));
+    }
+
+    /// Runs `contents` in `dialect` to its end, with what it wrote
+    fn run_in(contents: &str, dialect: Dialect) -> (Result<State, BfError>, Vec<u8>) {
+        let config = Config {
+            dialect,
+            ..Config::default()
+        };
+        let mut interpreter = Interpreter::new(contents, config).unwrap();
+        let mut io = Recorder(Vec::new());
+        (interpreter.run(&mut io), io.0)
+    }
+
+    #[test]
+    fn procedures_run_where_they_are_called() {
+        let (state, output) = run_in("+(.>+<)::>.", Dialect::Pbrain);
+        assert!(matches!(state, Ok(State::Halted)));
+        assert_eq!(output, [1, 1, 2]);
+        let (state, output) = run_in("+(.+)::", Dialect::Pbrain);
+        assert!(matches!(state, Err(BfError::UndefinedProcedure(2))));
+        assert_eq!(output, [1]);
+        let (state, _) = run_in("+(:):", Dialect::Pbrain);
+        assert!(matches!(state, Err(BfError::CallStackOverflow)));
+    }
+
+    #[test]
+    fn extended1_stores_shifts_and_ends() {
+        let (state, output) = run_in("+++$>!.{.}.~.^.>++++++&.|.@.", Dialect::Extended1);
+        assert!(matches!(state, Ok(State::Halted)));
+        assert_eq!(output, [3, 6, 3, 252, 255, 2, 3]);
+    }
+
+    #[test]
+    fn tapes_are_apart_and_wrap_around() {
+        let (state, output) = run_in("+++}++.{.}.}.", Dialect::Multitape);
+        assert!(matches!(state, Ok(State::Halted)));
+        assert_eq!(output, [2, 3, 2, 3]);
+        // Without the dialect the braces are comments
+        let (_, output) = run_in("+}+.", Dialect::Brainfuck);
+        assert_eq!(output, [2]);
+    }
+
+    #[test]
+    fn forks_take_turns_on_copies_of_the_memory() {
+        // The thread starts after the fork on the next cell, which is 1 only in its memory
+        let (state, output) = run_in(">+Y+.<.", Dialect::Brainfork);
+        assert!(matches!(state, Ok(State::Halted)));
+        assert_eq!(output, [2, 1, 1, 0]);
+        let (state, _) = run_in("+[Y+]", Dialect::Brainfork);
+        assert!(matches!(state, Err(BfError::TooManyThreads)));
+    }
+
+    #[test]
+    fn a_seed_gives_the_same_random_bytes() {
+        let random = |seed| {
+            let config = Config {
+                dialect: Dialect::Random,
+                seed,
+                ..Config::default()
+            };
+            let mut interpreter = Interpreter::new("?.?.?.?.", config).unwrap();
+            let mut io = Recorder(Vec::new());
+            assert!(matches!(interpreter.run(&mut io), Ok(State::Halted)));
+            io.0
+        };
+        assert_eq!(random(7), random(7));
+        assert_ne!(random(7), random(8));
+        let (_, output) = run_in("+?.", Dialect::Brainfuck);
+        assert_eq!(output, [1]);
+    }
+
+    /// Files in memory, with the output as `Recorder` has it
+    #[derive(Default)]
+    struct Disk {
+        files: BTreeMap<Vec<u8>, Vec<u8>>,
+        reading: VecDeque<u8>,
+        writing: Option<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl BfIo for Disk {
+        fn read(&mut self) -> Option<u8> {
+            None
+        }
+
+        fn write(&mut self, b: u8) {
+            self.output.push(b)
+        }
+
+        fn open(&mut self, name: &[u8], write: bool) -> bool {
+            if write {
+                self.files.insert(name.to_vec(), Vec::new());
+                self.writing = Some(name.to_vec());
+                return true;
+            }
+            match self.files.get(name) {
+                Some(file) => self.reading = file.iter().copied().collect(),
+                None => return false,
+            }
+            true
+        }
+
+        fn read_file(&mut self) -> Option<u8> {
+            self.reading.pop_front()
+        }
+
+        fn write_file(&mut self, b: u8) {
+            if let Some(name) = &self.writing {
+                self.files.get_mut(name).unwrap().push(b)
+            }
+        }
+    }
+
+    #[test]
+    fn files_are_written_and_read_back() {
+        let config = Config {
+            dialect: Dialect::Files,
+            ..Config::default()
+        };
+        // Writes 1 and 2 to the file "a", and reads them back past its end
+        let code = "++++++++[>++++++++++++<-]>+\\.:+:<++++++++[>>++++++++++++<<-]>>+/.;.;.;.=.>/.";
+        let mut interpreter = Interpreter::new(code, config).unwrap();
+        let mut io = Disk::default();
+        assert!(matches!(interpreter.run(&mut io), Ok(State::Halted)));
+        assert_eq!(io.files[&b"a"[..]], [1, 2]);
+        // The empty name at the end is no file
+        assert_eq!(io.output, [1, 1, 1, 2, 0, 0, 0]);
+    }
+
+    #[test]
+    fn mapped_cells_go_through_the_host() {
+        use alloc::rc::Rc;
+        use core::cell::RefCell;
+
+        let mut interpreter = Interpreter::new(">.+++<+>.", Config::default()).unwrap();
+        let written = Rc::new(RefCell::new(Vec::new()));
+        let set = {
+            let written = written.clone();
+            move |value| written.borrow_mut().push(value)
+        };
+        interpreter.map_cell(1, (|| 10, set)).unwrap();
+        let mut io = Recorder(Vec::new());
+        assert!(matches!(interpreter.run(&mut io), Ok(State::Halted)));
+        // Every `+` reads the cell and writes it, even after the passes would have joined them
+        assert_eq!(io.0, [10, 10]);
+        assert_eq!(*written.borrow(), [11, 11, 11]);
+        assert!(interpreter.unmap_cell(1).is_some());
+        assert!(matches!(
+            interpreter.map_cell(30001, (|| 0, |_| ())),
+            Err(BfError::MemoryOutOfBounds)
+        ));
+    }
+}
@@ -0,0 +1,1757 @@
+use crate::{
+    dialect::Dialect,
+    interpreter::{BfError, Config},
+};
+use alloc::{collections::BTreeMap, format, string::String, vec, vec::Vec};
+use core::{fmt, mem};
+
+/// A piece of the program, with every loop holding its body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Node {
+    /// Moves the pointer by the given amount
+    Move(isize),
+    /// Adds `amount` to the cell `offset` away from the pointer
+    Add {
+        offset: isize,
+        amount: u8,
+    },
+    /// A `[-]` or `[+]` loop, which always ends with the cell at 0
+    SetZero,
+    /// A `[>]` or `[<]` loop, moving by the given amount until the cell is 0
+    Scan(isize),
+    /// Adds the current cell times `factor` to the cell `offset` away, if the current cell is
+    /// not 0. A copy loop like `[->+>++<<]` becomes one of these for every cell it adds to
+    MulAdd {
+        offset: isize,
+        factor: u8,
+    },
+    Output {
+        offset: isize,
+    },
+    Input {
+        offset: isize,
+    },
+    /// Outputs the given value, for a `.` on a cell whose value is known
+    Print(u8),
+    /// `[...]`, where `id` is the position of its `[` among all the `[` of the source
+    Loop {
+        id: usize,
+        body: Vec<Node>,
+    },
+    /// `#`, prints the current cell in the debug mode
+    DebugCell,
+    /// `|`, prints the memory around the current cell in the debug mode
+    DebugMem,
+    /// `(...)` of pbrain, which makes `body` the procedure of the value of the current cell
+    /// instead of running it
+    Proc {
+        body: Vec<Node>,
+    },
+    /// `:` of pbrain, runs the procedure of the value of the current cell
+    Call,
+    /// `$` of Extended Type I, copies the current cell to the storage
+    Store,
+    /// `!` of Extended Type I, copies the storage to the current cell
+    Retrieve,
+    /// One of the bitwise instructions of Extended Type I on the current cell
+    Bits(Bits),
+    /// `@` of Extended Type I, ends the program
+    End,
+    /// A run of `{` and `}` of the multitape dialect, going to the tape the given amount after
+    /// the current one, where its own pointer is
+    Tape(isize),
+    /// `Y` of Brainfork, forks the program into a thread of its own
+    Fork,
+    /// `?` of the random dialect, sets the current cell to a random byte
+    Random,
+    /// `/` and `\` of the files dialect, open the file named from the current cell on
+    FileOpen {
+        write: bool,
+    },
+    /// `;` of the files dialect, reads a byte of the file
+    FileRead,
+    /// `:` of the files dialect, writes the current cell to the file
+    FileWrite,
+    /// `=` of the files dialect, tells whether the last [`Node::FileRead`] read a byte
+    FileStatus,
+}
+
+/// What [`Node::Bits`] does to the current cell
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bits {
+    /// `{`, shifts it left by one bit
+    ShiftLeft,
+    /// `}`, shifts it right by one bit, putting a 0 in the top bit
+    ShiftRight,
+    /// `~`, flips every bit
+    Not,
+    /// `^`, with the storage
+    Xor,
+    /// `&`, with the storage
+    And,
+    /// `|`, with the storage
+    Or,
+}
+
+impl Bits {
+    /// The instruction it comes from
+    pub fn symbol(self) -> char {
+        match self {
+            Bits::ShiftLeft => '{',
+            Bits::ShiftRight => '}',
+            Bits::Not => '~',
+            Bits::Xor => '^',
+            Bits::And => '&',
+            Bits::Or => '|',
+        }
+    }
+
+    /// The operation of the instruction `symbol`
+    pub fn from_symbol(symbol: char) -> Option<Bits> {
+        [
+            Bits::ShiftLeft,
+            Bits::ShiftRight,
+            Bits::Not,
+            Bits::Xor,
+            Bits::And,
+            Bits::Or,
+        ]
+        .into_iter()
+        .find(|op| op.symbol() == symbol)
+    }
+
+    /// The new value of a cell with `value`, when the storage has `storage`
+    pub fn apply(self, value: u8, storage: u8) -> u8 {
+        match self {
+            Bits::ShiftLeft => value << 1,
+            Bits::ShiftRight => value >> 1,
+            Bits::Not => !value,
+            Bits::Xor => value ^ storage,
+            Bits::And => value & storage,
+            Bits::Or => value | storage,
+        }
+    }
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Node::Move(amount) => write!(f, "move {}", amount),
+            Node::Add { offset, amount } => write!(f, "add [{}] {}", offset, amount as i8),
+            Node::SetZero => write!(f, "zero"),
+            Node::Scan(amount) => write!(f, "scan {}", amount),
+            Node::MulAdd { offset, factor } => write!(f, "mul [{}] {}", offset, factor),
+            Node::Output { offset } => write!(f, "out [{}]", offset),
+            Node::Input { offset } => write!(f, "in [{}]", offset),
+            Node::Print(value) => write!(f, "out {}", value),
+            Node::Loop { id, .. } => write!(f, "loop {}", id),
+            Node::DebugCell => write!(f, "debug cell"),
+            Node::DebugMem => write!(f, "debug mem"),
+            Node::Proc { .. } => write!(f, "proc"),
+            Node::Call => write!(f, "call"),
+            Node::Store => write!(f, "store"),
+            Node::Retrieve => write!(f, "retrieve"),
+            Node::Bits(bits) => write!(f, "bits {}", bits.symbol()),
+            Node::End => write!(f, "end program"),
+            Node::Tape(amount) => write!(f, "tape {}", amount),
+            Node::Fork => write!(f, "fork"),
+            Node::Random => write!(f, "random"),
+            Node::FileOpen { write: false } => write!(f, "open file for reading"),
+            Node::FileOpen { write: true } => write!(f, "open file for writing"),
+            Node::FileRead => write!(f, "read file"),
+            Node::FileWrite => write!(f, "write file"),
+            Node::FileStatus => write!(f, "file status"),
+        }
+    }
+}
+
+/// Shows `nodes` one per line, with the body of every loop indented under it and the
+/// [`Summary`] of the body next to the loop
+pub fn dump(nodes: &[Node]) -> impl fmt::Display + '_ {
+    Block { nodes, depth: 0 }
+}
+
+struct Block<'a> {
+    nodes: &'a [Node],
+    depth: usize,
+}
+
+impl fmt::Display for Block<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let indent = self.depth * 4;
+        for node in self.nodes {
+            write!(f, "{:indent$}{}", "", node)?;
+            match node {
+                Node::Loop { body, .. } => writeln!(f, " ; {}", analyze(body))?,
+                _ => writeln!(f)?,
+            }
+            if let Node::Loop { body, .. } | Node::Proc { body } = node {
+                let depth = self.depth + 1;
+                write!(f, "{}", Block { nodes: body, depth })?;
+                writeln!(f, "{:indent$}end", "")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The Brainfuck source doing the same as `nodes`, with every node turned back into the
+/// shortest instructions for it. The pointer only moves when a node needs it somewhere, and the
+/// [`Node::MulAdd`]s of a copy loop become that loop again.
+///
+/// # Panics
+///
+/// If `nodes` has a [`Node::Print`], which does not say what cell it printed
+pub fn to_bf(nodes: &[Node]) -> String {
+    let mut code = String::new();
+    lower(nodes, &mut code);
+    // Where the pointer ends up does not matter
+    code.truncate(code.trim_end_matches(['<', '>']).len());
+    code
+}
+
+fn lower(nodes: &[Node], code: &mut String) {
+    // Where the pointer of the code is from the one of the nodes
+    let mut at = 0;
+    let mut rest = nodes;
+    while let Some((node, after)) = rest.split_first() {
+        rest = after;
+        match *node {
+            Node::Move(amount) => at -= amount,
+            Node::Add { offset, amount } => {
+                move_to(code, &mut at, offset);
+                add(code, amount);
+            }
+            Node::SetZero => {
+                move_to(code, &mut at, 0);
+                code.push_str("[-]");
+            }
+            Node::Scan(amount) => {
+                move_to(code, &mut at, 0);
+                code.push('[');
+                move_to(code, &mut 0, amount);
+                code.push(']');
+            }
+            Node::MulAdd { .. } => {
+                let count = rest
+                    .iter()
+                    .take_while(|node| matches!(node, Node::MulAdd { .. }))
+                    .count();
+                let (adds, after) = rest.split_at(count);
+                // Without the clear that ends it, the current cell is known to be 0 and the loop
+                // is skipped all the same
+                rest = after.strip_prefix(&[Node::SetZero]).unwrap_or(after);
+                move_to(code, &mut at, 0);
+                code.push_str("[-");
+                for add_node in [node].into_iter().chain(adds) {
+                    if let Node::MulAdd { offset, factor } = *add_node {
+                        move_to(code, &mut at, offset);
+                        add(code, factor);
+                    }
+                }
+                move_to(code, &mut at, 0);
+                code.push(']');
+            }
+            Node::Output { offset } => {
+                move_to(code, &mut at, offset);
+                code.push('.');
+            }
+            Node::Input { offset } => {
+                move_to(code, &mut at, offset);
+                code.push(',');
+            }
+            Node::Print(_) => panic!("a print has no cell to turn back into Brainfuck"),
+            Node::Loop { ref body, .. } => {
+                move_to(code, &mut at, 0);
+                code.push('[');
+                lower(body, code);
+                code.push(']');
+            }
+            Node::DebugCell
+            | Node::DebugMem
+            | Node::Call
+            | Node::Store
+            | Node::Retrieve
+            | Node::Bits(_)
+            | Node::End
+            | Node::Fork
+            | Node::Random
+            | Node::FileOpen { .. }
+            | Node::FileRead
+            | Node::FileWrite
+            | Node::FileStatus => {
+                move_to(code, &mut at, 0);
+                code.push(match *node {
+                    Node::DebugCell => '#',
+                    Node::DebugMem => '|',
+                    Node::Call => ':',
+                    Node::Store => '$',
+                    Node::Retrieve => '!',
+                    Node::Bits(bits) => bits.symbol(),
+                    Node::Fork => 'Y',
+                    Node::Random => '?',
+                    Node::FileOpen { write: false } => '/',
+                    Node::FileOpen { write: true } => '\\',
+                    Node::FileRead => ';',
+                    Node::FileWrite => ':',
+                    Node::FileStatus => '=',
+                    _ => '@',
+                });
+            }
+            Node::Proc { ref body } => {
+                move_to(code, &mut at, 0);
+                code.push('(');
+                lower(body, code);
+                code.push(')');
+            }
+            // The pointer of the other tape is somewhere else
+            Node::Tape(amount) => {
+                move_to(code, &mut at, 0);
+                let ch = if amount < 0 { '{' } else { '}' };
+                code.extend(core::iter::repeat_n(ch, amount.unsigned_abs()));
+            }
+        }
+    }
+    move_to(code, &mut at, 0);
+}
+
+/// Moves the pointer of the code from `at` to `to`
+fn move_to(code: &mut String, at: &mut isize, to: isize) {
+    let ch = if to > *at { '>' } else { '<' };
+    code.extend(core::iter::repeat_n(ch, to.abs_diff(*at)));
+    *at = to;
+}
+
+/// Adds `amount` to the cell, which is negative the same way for every cell size
+fn add(code: &mut String, amount: u8) {
+    let ch = if (amount as i8) < 0 { '-' } else { '+' };
+    code.extend(core::iter::repeat_n(
+        ch,
+        (amount as i8).unsigned_abs() as usize,
+    ));
+}
+
+/// Parses the source into nodes, one for every instruction.
+/// Characters that are not instructions are skipped, unless the code is verbose, in which
+/// case they are an error like `#` and `|` outside of the debug mode
+pub fn parse(contents: &str, config: &Config) -> Result<Vec<Node>, BfError> {
+    let mut current = Vec::new();
+    // The id of every loop that is still open, or `None` for a procedure, with the block it is in
+    let mut open = Vec::new();
+    let mut brackets = 0;
+    let pbrain = config.dialect == Dialect::Pbrain;
+    let extended = config.dialect == Dialect::Extended1;
+    let multitape = config.dialect == Dialect::Multitape;
+    let brainfork = config.dialect == Dialect::Brainfork;
+    let random = config.dialect == Dialect::Random;
+    let files = config.dialect == Dialect::Files;
+
+    for ch in contents.chars() {
+        let node = match ch {
+            '>' => Node::Move(1),
+            '<' => Node::Move(-1),
+            '+' => Node::Add {
+                offset: 0,
+                amount: 1,
+            },
+            '-' => Node::Add {
+                offset: 0,
+                amount: 255,
+            },
+            '.' => Node::Output { offset: 0 },
+            ',' => Node::Input { offset: 0 },
+            '[' => {
+                open.push((Some(brackets), mem::take(&mut current)));
+                brackets += 1;
+                continue;
+            }
+            ']' => match open.pop() {
+                Some((Some(id), outer)) => {
+                    let body = mem::replace(&mut current, outer);
+                    Node::Loop { id, body }
+                }
+                _ => return Err(BfError::UnbalancedBrackets),
+            },
+            '(' if pbrain => {
+                open.push((None, mem::take(&mut current)));
+                continue;
+            }
+            ')' if pbrain => match open.pop() {
+                Some((None, outer)) => Node::Proc {
+                    body: mem::replace(&mut current, outer),
+                },
+                _ => return Err(BfError::UnbalancedBrackets),
+            },
+            ':' if pbrain => Node::Call,
+            '$' if extended => Node::Store,
+            '!' if extended => Node::Retrieve,
+            '@' if extended => Node::End,
+            '{' if extended => Node::Bits(Bits::ShiftLeft),
+            '}' if extended => Node::Bits(Bits::ShiftRight),
+            '~' if extended => Node::Bits(Bits::Not),
+            '^' if extended => Node::Bits(Bits::Xor),
+            '&' if extended => Node::Bits(Bits::And),
+            '|' if extended => Node::Bits(Bits::Or),
+            '{' if multitape => Node::Tape(-1),
+            '}' if multitape => Node::Tape(1),
+            'Y' if brainfork => Node::Fork,
+            '?' if random => Node::Random,
+            '/' if files => Node::FileOpen { write: false },
+            '\\' if files => Node::FileOpen { write: true },
+            ';' if files => Node::FileRead,
+            ':' if files => Node::FileWrite,
+            '=' if files => Node::FileStatus,
+            '#' if config.debug => Node::DebugCell,
+            '|' if config.debug => Node::DebugMem,
+            ch if config.verbose || "#|".contains(ch) => return Err(BfError::InvalidCharacter(ch)),
+            _ => continue,
+        };
+        current.push(node);
+    }
+    if !open.is_empty() {
+        return Err(BfError::UnbalancedBrackets);
+    }
+    Ok(current)
+}
+
+/// An optimization over the nodes
+#[derive(Clone, Copy)]
+pub struct Pass {
+    pub name: &'static str,
+    /// The lowest optimization level that runs it, above 3 for the ones that only run when asked
+    pub level: u8,
+    /// Runs it on the nodes of the code that runs with the [`Config`]
+    pub run: fn(Vec<Node>, &Config) -> Vec<Node>,
+}
+
+/// Every pass, in the order they run. A set of passes is a mask with the bit `1 << i` for
+/// `PASSES[i]`
+pub const PASSES: &[Pass] = &[
+    Pass {
+        name: "peephole",
+        level: 1,
+        run: |nodes, _| peephole(nodes),
+    },
+    Pass {
+        name: "rle",
+        level: 1,
+        run: |nodes, _| merge_runs(nodes),
+    },
+    Pass {
+        name: "offsets",
+        level: 3,
+        run: |nodes, _| defer_moves(nodes),
+    },
+    Pass {
+        name: "clear-loops",
+        level: 2,
+        run: |nodes, _| clear_loops(nodes),
+    },
+    Pass {
+        name: "scan-loops",
+        level: 2,
+        run: |nodes, _| scan_loops(nodes),
+    },
+    Pass {
+        name: "copy-loops",
+        level: 2,
+        run: |nodes, _| copy_loops(nodes),
+    },
+    Pass {
+        name: "dead-code",
+        level: 2,
+        run: |nodes, _| dead_code(nodes),
+    },
+    Pass {
+        name: "unroll",
+        level: 3,
+        run: |nodes, _| unroll_loops(nodes),
+    },
+    Pass {
+        name: "const-fold",
+        level: 3,
+        run: |nodes, _| fold_constants(nodes),
+    },
+    Pass {
+        name: "eval",
+        level: 4,
+        run: evaluate,
+    },
+];
+
+/// The passes of an optimization level:
+/// - 0 runs none of them
+/// - 1 merges runs of the same instruction
+/// - 2 also turns clear, scan and copy loops into single nodes
+/// - 3 also defers pointer movement to the end of every block, addressing the cells in between
+///   by their offset
+///
+/// The `eval` pass is in none of them, and only runs when it is asked for with `--eval` or
+/// `--passes=eval`. There is no level 4 for it, and `-O4` is turned down
+pub fn level_passes(level: u8) -> u32 {
+    PASSES
+        .iter()
+        .enumerate()
+        .filter(|(_, pass)| pass.level <= level)
+        .fold(0, |passes, (i, _)| passes | 1 << i)
+}
+
+/// The mask of the pass called `name`
+pub fn pass_mask(name: &str) -> Option<u32> {
+    let i = PASSES.iter().position(|pass| pass.name == name)?;
+    Some(1 << i)
+}
+
+/// Runs the passes in the [`Config::passes`] mask on `nodes`, panicking where [`optimize_with`]
+/// fails
+pub fn optimize(nodes: Vec<Node>, config: &Config) -> Vec<Node> {
+    optimize_with(nodes, config).unwrap_or_else(|err| panic!("{}", err))
+}
+
+/// [`optimize_with`], along with what every pass changed
+pub fn optimize_with_report(
+    nodes: Vec<Node>,
+    config: &Config,
+) -> Result<(Vec<Node>, Report), BfError> {
+    // Debug builds always check the passes, to catch the ones that break the nodes
+    run_passes(nodes, config, config.verify_ir || cfg!(debug_assertions))
+}
+
+/// Runs the passes of `config`, checking the nodes with [`verify`] after every one of them if
+/// it asks to
+pub fn optimize_with(nodes: Vec<Node>, config: &Config) -> Result<Vec<Node>, BfError> {
+    optimize_with_report(nodes, config).map(|(nodes, _)| nodes)
+}
+
+fn run_passes(
+    mut nodes: Vec<Node>,
+    config: &Config,
+    verify_ir: bool,
+) -> Result<(Vec<Node>, Report), BfError> {
+    let passes = config.passes;
+    let mut report = Report {
+        parsed: Counts::of(&nodes),
+        passes: Vec::new(),
+        bounds: None,
+    };
+    let limits = Limits::of(&nodes, passes);
+    for (i, pass) in PASSES.iter().enumerate() {
+        if passes & 1 << i != 0 {
+            nodes = (pass.run)(nodes, config);
+            if verify_ir {
+                verify(&nodes, &limits).map_err(|err| {
+                    BfError::InvalidIr(format!("after the {} pass, {}", pass.name, err))
+                })?;
+            }
+            report.passes.push((pass.name, Counts::of(&nodes)));
+        }
+    }
+    report.bounds = bounds(&nodes);
+    Ok((nodes, report))
+}
+
+/// What [`verify`] holds the nodes to, worked out from the parsed nodes
+struct Limits {
+    /// How many loops the source has
+    loops: usize,
+    /// How far from the pointer a node can reach, which is never further than all the moves of
+    /// the source put together, apart from the cells the `eval` pass used
+    reach: usize,
+}
+
+impl Limits {
+    fn of(nodes: &[Node], passes: u32) -> Limits {
+        fn moves(nodes: &[Node]) -> usize {
+            nodes
+                .iter()
+                .map(|node| match node {
+                    Node::Loop { body, .. } | Node::Proc { body } => moves(body),
+                    node => reach(node).unsigned_abs(),
+                })
+                .sum()
+        }
+        let eval = pass_mask("eval").is_some_and(|mask| passes & mask != 0);
+        Limits {
+            loops: Counts::of(nodes).loops,
+            reach: moves(nodes) + if eval { EVAL_CELLS } else { 0 },
+        }
+    }
+}
+
+/// How far from the pointer `node` moves or touches a cell
+fn reach(node: &Node) -> isize {
+    match *node {
+        Node::Move(amount) | Node::Scan(amount) => amount,
+        Node::Add { offset, .. }
+        | Node::MulAdd { offset, .. }
+        | Node::Output { offset }
+        | Node::Input { offset } => offset,
+        _ => 0,
+    }
+}
+
+/// Checks that the nodes could have come from the source: the loops are in the order of
+/// their ids and none of them is there twice, no node reaches further than [`Limits`]
+/// allows, and no node does something that cannot be run
+fn verify(nodes: &[Node], limits: &Limits) -> Result<(), String> {
+    fn check(nodes: &[Node], limits: &Limits, next: &mut usize) -> Result<(), String> {
+        for node in nodes {
+            if reach(node).unsigned_abs() > limits.reach {
+                return Err(format!("'{}' reaches further than the source", node));
+            }
+            match *node {
+                Node::Scan(0) | Node::MulAdd { offset: 0, .. } => {
+                    return Err(format!("'{}' is not a valid node", node))
+                }
+                Node::Loop { id, ref body } => {
+                    if id < *next || id >= limits.loops {
+                        return Err(format!("loop {} is out of order", id));
+                    }
+                    *next = id + 1;
+                    check(body, limits, next)?;
+                }
+                Node::Proc { ref body } => check(body, limits, next)?,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+    check(nodes, limits, &mut 0)
+}
+
+/// How many nodes of each kind there are, loop bodies included
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Counts {
+    pub nodes: usize,
+    pub loops: usize,
+    pub clears: usize,
+    pub scans: usize,
+    pub mul_adds: usize,
+}
+
+impl Counts {
+    pub fn of(nodes: &[Node]) -> Counts {
+        let mut counts = Counts::default();
+        for node in nodes {
+            counts.nodes += 1;
+            match node {
+                Node::Loop { body, .. } | Node::Proc { body } => {
+                    let body = Counts::of(body);
+                    counts.nodes += body.nodes;
+                    counts.loops += body.loops + matches!(node, Node::Loop { .. }) as usize;
+                    counts.clears += body.clears;
+                    counts.scans += body.scans;
+                    counts.mul_adds += body.mul_adds;
+                }
+                Node::SetZero => counts.clears += 1,
+                Node::Scan(_) => counts.scans += 1,
+                Node::MulAdd { .. } => counts.mul_adds += 1,
+                _ => (),
+            }
+        }
+        counts
+    }
+}
+
+/// What the passes changed, shown as one line per pass
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    /// The nodes before any pass
+    pub parsed: Counts,
+    /// The name of every pass that ran, with the nodes after it
+    pub passes: Vec<(&'static str, Counts)>,
+    /// The cells the optimized nodes can touch, if they are known
+    pub bounds: Option<Bounds>,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parsed = self.parsed;
+        writeln!(f, "parsed: {} nodes, {} loops", parsed.nodes, parsed.loops)?;
+        let mut before = parsed;
+        for &(name, after) in &self.passes {
+            write!(f, "{}: {} -> {} nodes", name, before.nodes, after.nodes)?;
+            let changes = [
+                (after.loops as isize - before.loops as isize, "loops"),
+                (after.clears as isize - before.clears as isize, "clears"),
+                (after.scans as isize - before.scans as isize, "scans"),
+                (
+                    after.mul_adds as isize - before.mul_adds as isize,
+                    "multiplications",
+                ),
+            ];
+            for (change, kind) in changes {
+                if change != 0 {
+                    write!(f, ", {:+} {}", change, kind)?;
+                }
+            }
+            writeln!(f)?;
+            before = after;
+        }
+        let eliminated = parsed.nodes as isize - before.nodes as isize;
+        writeln!(
+            f,
+            "total: {} -> {} nodes, {} eliminated",
+            parsed.nodes, before.nodes, eliminated
+        )?;
+        match self.bounds {
+            Some(bounds) => writeln!(f, "bounds: {}", bounds),
+            None => writeln!(f, "bounds: unknown, the pointer is checked on every move"),
+        }
+    }
+}
+
+/// Runs `pass` on the body of every loop and procedure in `nodes`
+fn in_loops(nodes: Vec<Node>, pass: fn(Vec<Node>) -> Vec<Node>) -> impl Iterator<Item = Node> {
+    nodes.into_iter().map(move |node| match node {
+        Node::Loop { id, body } => Node::Loop {
+            id,
+            body: pass(body),
+        },
+        Node::Proc { body } => Node::Proc { body: pass(body) },
+        node => node,
+    })
+}
+
+/// Cancels the nodes that undo each other, like `+-` or `><`, including the ones that only end
+/// up next to each other once the nodes between them are cancelled, like `+><-`. Also removes
+/// the adds to a cell that a clear loop right after them sets to 0
+fn peephole(nodes: Vec<Node>) -> Vec<Node> {
+    let mut kept: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in in_loops(nodes, peephole) {
+        match (kept.last(), &node) {
+            (Some(&Node::Move(amount)), &Node::Move(more)) if amount + more == 0 => {
+                kept.pop();
+            }
+            (
+                Some(&Node::Add { offset, amount }),
+                &Node::Add {
+                    offset: other,
+                    amount: more,
+                },
+            ) if offset == other && amount.wrapping_add(more) == 0 => {
+                kept.pop();
+            }
+            (_, Node::Loop { body, .. }) => {
+                if let [Node::Add { offset: 0, amount }] = body[..] {
+                    while amount % 2 == 1
+                        && matches!(kept.last(), Some(Node::Add { offset: 0, .. }))
+                    {
+                        kept.pop();
+                    }
+                }
+                kept.push(node);
+            }
+            _ => kept.push(node),
+        }
+    }
+    kept
+}
+
+/// Merges moves that follow each other, and adds to the same cell that follow each other
+fn merge_runs(nodes: Vec<Node>) -> Vec<Node> {
+    let mut merged: Vec<Node> = Vec::with_capacity(nodes.len());
+    for node in in_loops(nodes, merge_runs) {
+        match (merged.last_mut(), node) {
+            (Some(Node::Move(amount)), Node::Move(more))
+            | (Some(Node::Tape(amount)), Node::Tape(more)) => *amount += more,
+            (
+                Some(Node::Add { offset, amount }),
+                Node::Add {
+                    offset: other,
+                    amount: more,
+                },
+            ) if *offset == other => *amount = amount.wrapping_add(more),
+            (_, node) => merged.push(node),
+        }
+        if let Some(Node::Move(0) | Node::Tape(0) | Node::Add { amount: 0, .. }) = merged.last() {
+            merged.pop();
+        }
+    }
+    merged
+}
+
+/// Moves the pointer only once at the end of every block, and before the nodes that need it to
+/// be in place
+fn defer_moves(nodes: Vec<Node>) -> Vec<Node> {
+    let mut deferred = Vec::with_capacity(nodes.len());
+    let mut moved = 0;
+    for node in in_loops(nodes, defer_moves) {
+        match node {
+            Node::Move(amount) => moved += amount,
+            Node::Add { offset, amount } => deferred.push(Node::Add {
+                offset: offset + moved,
+                amount,
+            }),
+            Node::Output { offset } => deferred.push(Node::Output {
+                offset: offset + moved,
+            }),
+            Node::Input { offset } => deferred.push(Node::Input {
+                offset: offset + moved,
+            }),
+            node => {
+                if moved != 0 {
+                    deferred.push(Node::Move(moved));
+                    moved = 0;
+                }
+                deferred.push(node);
+            }
+        }
+    }
+    if moved != 0 {
+        deferred.push(Node::Move(moved));
+    }
+    deferred
+}
+
+/// Replaces the loops whose body passes `simplify` with what it returns
+fn simplify_loops(
+    nodes: Vec<Node>,
+    pass: fn(Vec<Node>) -> Vec<Node>,
+    simplify: fn(&[Node]) -> Option<Vec<Node>>,
+) -> Vec<Node> {
+    let mut simplified = Vec::with_capacity(nodes.len());
+    for node in in_loops(nodes, pass) {
+        match node {
+            Node::Loop { id, body } => match simplify(&body) {
+                Some(nodes) => simplified.extend(nodes),
+                None => simplified.push(Node::Loop { id, body }),
+            },
+            node => simplified.push(node),
+        }
+    }
+    simplified
+}
+
+/// Replaces clear loops with [`Node::SetZero`]
+fn clear_loops(nodes: Vec<Node>) -> Vec<Node> {
+    simplify_loops(nodes, clear_loops, |body| match *body {
+        // Adding an odd amount reaches 0 from any value
+        [Node::Add { offset: 0, amount }] if amount % 2 == 1 => Some(vec![Node::SetZero]),
+        _ => None,
+    })
+}
+
+/// Replaces scan loops with [`Node::Scan`]
+fn scan_loops(nodes: Vec<Node>) -> Vec<Node> {
+    simplify_loops(nodes, scan_loops, |body| match *body {
+        [Node::Move(amount)] => Some(vec![Node::Scan(amount)]),
+        _ => None,
+    })
+}
+
+/// Replaces copy loops with [`Node::MulAdd`]s
+fn copy_loops(nodes: Vec<Node>) -> Vec<Node> {
+    simplify_loops(nodes, copy_loops, copy_loop)
+}
+
+/// Removes the loops, clears and scans that start on a cell known to be 0, like the comment loop
+/// at the start of a program or a loop right after another one, and everything after a loop
+/// that never ends.
+/// The memory has to be all 0 at the start of `nodes`
+fn dead_code(nodes: Vec<Node>) -> Vec<Node> {
+    eliminate(nodes, Some(0), true)
+}
+
+/// `current` is the value of the current cell at the start of `nodes` if it is known, and
+/// `all_zero` is whether every cell is 0
+fn eliminate(nodes: Vec<Node>, mut current: Option<u8>, mut all_zero: bool) -> Vec<Node> {
+    let mut live = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        match node {
+            Node::Move(_) | Node::Tape(_) => current = all_zero.then_some(0),
+            Node::Add { offset, amount } => {
+                if offset == 0 {
+                    current = current.map(|value| value.wrapping_add(amount));
+                }
+                all_zero = false;
+            }
+            Node::Input { offset } => {
+                if offset == 0 {
+                    current = None;
+                }
+                all_zero = false;
+            }
+            Node::Loop { .. } | Node::SetZero | Node::Scan(_) if current == Some(0) => continue,
+            Node::SetZero | Node::Scan(_) => current = Some(0),
+            Node::Loop { id, body } => {
+                // An empty loop on a cell that is not 0 never ends
+                if body.is_empty() && current.is_some() {
+                    live.push(Node::Loop { id, body });
+                    break;
+                }
+                let body = eliminate(body, None, false);
+                live.push(Node::Loop { id, body });
+                current = Some(0);
+                all_zero = false;
+                continue;
+            }
+            // A procedure runs wherever it is called, where nothing is known
+            Node::Proc { body } => {
+                live.push(Node::Proc {
+                    body: eliminate(body, None, false),
+                });
+                continue;
+            }
+            // After a fork, the child runs the same code with another current cell, which is 1
+            Node::Call
+            | Node::Retrieve
+            | Node::Bits(_)
+            | Node::Fork
+            | Node::Random
+            | Node::FileOpen { .. }
+            | Node::FileRead
+            | Node::FileStatus => {
+                current = None;
+                all_zero = false;
+            }
+            // Nothing after it runs
+            Node::End => {
+                live.push(node);
+                break;
+            }
+            Node::MulAdd { .. }
+            | Node::Store
+            | Node::Output { .. }
+            | Node::Print(_)
+            | Node::DebugCell
+            | Node::DebugMem
+            | Node::FileWrite => (),
+        }
+        live.push(node);
+    }
+    live
+}
+
+/// Replaces the outputs of cells whose value is known with [`Node::Print`], and the
+/// multiplications by a known cell with [`Node::Add`]s.
+/// The memory has to be all 0 at the start of `nodes`
+fn fold_constants(nodes: Vec<Node>) -> Vec<Node> {
+    fold(nodes, Cells::new(Some(0)))
+}
+
+/// What is known about the cells, by their offset from the pointer
+struct Cells {
+    /// The cells whose value is different from `rest`, with `None` for the unknown ones
+    known: BTreeMap<isize, Option<u8>>,
+    /// The value of all the other cells, if it is known
+    rest: Option<u8>,
+    /// The lowest and the highest offset the nodes so far reached, which running them checked to
+    /// be in the memory, and so everything in between is too
+    reached: (isize, isize),
+}
+
+impl Cells {
+    fn new(rest: Option<u8>) -> Cells {
+        Cells {
+            known: BTreeMap::new(),
+            rest,
+            reached: (0, 0),
+        }
+    }
+
+    fn get(&self, offset: isize) -> Option<u8> {
+        self.known.get(&offset).copied().unwrap_or(self.rest)
+    }
+
+    /// Whether the cell at `offset` is known to be in the memory
+    fn in_bounds(&self, offset: isize) -> bool {
+        (self.reached.0..=self.reached.1).contains(&offset)
+    }
+
+    fn reach(&mut self, offset: isize) {
+        self.reached = (self.reached.0.min(offset), self.reached.1.max(offset));
+    }
+
+    fn add(&mut self, offset: isize, amount: u8) {
+        let value = self.get(offset).map(|value| value.wrapping_add(amount));
+        self.known.insert(offset, value);
+    }
+
+    /// Changes what is known to what it is after `node` runs
+    fn update(&mut self, node: &Node) {
+        match *node {
+            Node::Move(amount) => {
+                let known = mem::take(&mut self.known);
+                self.known = known
+                    .into_iter()
+                    .map(|(offset, value)| (offset - amount, value))
+                    .collect();
+                self.reached = (self.reached.0 - amount, self.reached.1 - amount);
+                self.reach(0);
+            }
+            Node::Add { offset, amount } => {
+                self.add(offset, amount);
+                self.reach(offset);
+            }
+            Node::SetZero => {
+                self.known.insert(0, Some(0));
+            }
+            Node::MulAdd { offset, factor } => match self.get(0) {
+                Some(value) => self.add(offset, value.wrapping_mul(factor)),
+                None => {
+                    self.known.insert(offset, None);
+                }
+            },
+            Node::Input { offset } => {
+                self.known.insert(offset, None);
+                self.reach(offset);
+            }
+            Node::Output { offset } => self.reach(offset),
+            // All that is known after a loop is that the current cell is 0
+            Node::Scan(_) | Node::Loop { .. } => {
+                *self = Cells::new(None);
+                self.known.insert(0, Some(0));
+            }
+            Node::Call | Node::Tape(_) | Node::Fork => *self = Cells::new(None),
+            Node::Retrieve
+            | Node::Bits(_)
+            | Node::Random
+            | Node::FileOpen { .. }
+            | Node::FileRead
+            | Node::FileStatus => {
+                self.known.insert(0, None);
+            }
+            Node::Store
+            | Node::End
+            | Node::Print(_)
+            | Node::DebugCell
+            | Node::DebugMem
+            | Node::Proc { .. }
+            | Node::FileWrite => (),
+        }
+    }
+}
+
+fn fold(nodes: Vec<Node>, mut cells: Cells) -> Vec<Node> {
+    let mut folded = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let node = match node {
+            Node::MulAdd { offset, factor } => match cells.get(0) {
+                Some(value) if value.wrapping_mul(factor) == 0 => continue,
+                Some(value) => Node::Add {
+                    offset,
+                    amount: value.wrapping_mul(factor),
+                },
+                None => node,
+            },
+            // A print does not check that the cell is in the memory like the output did
+            Node::Output { offset } => match cells.get(offset) {
+                Some(value) if cells.in_bounds(offset) => Node::Print(value),
+                _ => node,
+            },
+            Node::Loop { .. } if cells.get(0) == Some(0) => continue,
+            Node::Loop { id, body } => Node::Loop {
+                id,
+                body: fold(body, Cells::new(None)),
+            },
+            Node::Proc { body } => Node::Proc {
+                body: fold(body, Cells::new(None)),
+            },
+            node => node,
+        };
+        cells.update(&node);
+        folded.push(node);
+    }
+    folded
+}
+
+/// The most times a loop can run to be unrolled
+const UNROLL_LIMIT: u8 = 16;
+
+/// Unrolls the loops that only move and add, end where they started, and run at most
+/// [`UNROLL_LIMIT`] times from the known value of their cell. Running the body of such a loop
+/// `count` times is the same as adding `count` times as much to every cell, so the loop becomes
+/// a single [`Node::Add`] for every cell.
+/// The memory has to be all 0 at the start of `nodes`
+fn unroll_loops(nodes: Vec<Node>) -> Vec<Node> {
+    unroll(nodes, Cells::new(Some(0)))
+}
+
+fn unroll(nodes: Vec<Node>, mut cells: Cells) -> Vec<Node> {
+    let mut unrolled = Vec::with_capacity(nodes.len());
+    for node in nodes {
+        let node = match node {
+            Node::Loop { id, body } => match unrolled_loop(&body, cells.get(0)) {
+                Some(adds) => {
+                    for node in adds {
+                        cells.update(&node);
+                        unrolled.push(node);
+                    }
+                    continue;
+                }
+                None => Node::Loop {
+                    id,
+                    body: unroll(body, Cells::new(None)),
+                },
+            },
+            Node::Proc { body } => Node::Proc {
+                body: unroll(body, Cells::new(None)),
+            },
+            node => node,
+        };
+        cells.update(&node);
+        unrolled.push(node);
+    }
+    unrolled
+}
+
+/// The [`Node::Add`]s doing the same as the loop with `body` when its cell starts at `value`
+fn unrolled_loop(body: &[Node], value: Option<u8>) -> Option<Vec<Node>> {
+    let summary = analyze(body);
+    if !summary.balanced() {
+        return None;
+    }
+    let deltas = summary.deltas?;
+    let step = deltas
+        .iter()
+        .find(|&&(offset, _)| offset == 0)
+        .map_or(0, |&(_, step)| step);
+    let value = value?;
+    let count =
+        (0..=UNROLL_LIMIT).find(|&count| value.wrapping_add(step.wrapping_mul(count)) == 0)?;
+    let adds = deltas.into_iter().filter_map(|(offset, amount)| {
+        let amount = amount.wrapping_mul(count);
+        (amount != 0).then_some(Node::Add { offset, amount })
+    });
+    Some(adds.collect())
+}
+
+/// How many nodes [`evaluate`] runs before giving up
+const EVAL_STEPS: usize = 1 << 24;
+/// How many cells [`evaluate`] can use
+const EVAL_CELLS: usize = 1 << 16;
+
+/// Runs the whole program if it never reads input, and replaces it with its output followed by
+/// the nodes that leave the memory and the pointer as it did. Gives up on programs that take
+/// more than [`EVAL_STEPS`] nodes or [`EVAL_CELLS`] cells, that leave the memory of `config`,
+/// or that have debug symbols, so that they still stop where they would have.
+/// The memory has to be all 0 at the start of `nodes`
+fn evaluate(nodes: Vec<Node>, config: &Config) -> Vec<Node> {
+    let mut machine = Machine {
+        mem: Vec::new(),
+        cells: EVAL_CELLS.min(config.mem_size.saturating_sub(config.offset)),
+        cellptr: 0,
+        steps: EVAL_STEPS,
+        output: Vec::new(),
+    };
+    if machine.run(&nodes).is_none() {
+        return nodes;
+    }
+    let mut evaluated: Vec<Node> = machine.output.into_iter().map(Node::Print).collect();
+    for (cell, &amount) in machine.mem.iter().enumerate() {
+        if amount != 0 {
+            let offset = cell as isize;
+            evaluated.push(Node::Add { offset, amount });
+        }
+    }
+    if machine.cellptr != 0 {
+        evaluated.push(Node::Move(machine.cellptr as isize));
+    }
+    evaluated
+}
+
+/// The state of the program while [`evaluate`] runs it
+struct Machine {
+    mem: Vec<u8>,
+    /// How many cells the program can use, from where it starts
+    cells: usize,
+    cellptr: usize,
+    steps: usize,
+    output: Vec<u8>,
+}
+
+impl Machine {
+    /// Runs `nodes`, or returns `None` if the program cannot be run at compile time
+    fn run(&mut self, nodes: &[Node]) -> Option<()> {
+        for node in nodes {
+            self.steps = self.steps.checked_sub(1)?;
+            match *node {
+                Node::Move(amount) => self.move_by(amount)?,
+                Node::Add { offset, amount } => {
+                    let cell = self.cell(offset)?;
+                    *cell = cell.wrapping_add(amount);
+                }
+                Node::SetZero => *self.cell(0)? = 0,
+                Node::Scan(amount) => {
+                    while *self.cell(0)? != 0 {
+                        self.steps = self.steps.checked_sub(1)?;
+                        self.move_by(amount)?;
+                    }
+                }
+                Node::MulAdd { offset, factor } => {
+                    let value = *self.cell(0)?;
+                    if value != 0 {
+                        let cell = self.cell(offset)?;
+                        *cell = cell.wrapping_add(value.wrapping_mul(factor));
+                    }
+                }
+                Node::Output { offset } => {
+                    let value = *self.cell(offset)?;
+                    self.output.push(value);
+                }
+                Node::Print(value) => self.output.push(value),
+                Node::Loop { ref body, .. } => {
+                    while *self.cell(0)? != 0 {
+                        // Counted here too, so that an empty loop runs out of steps
+                        self.steps = self.steps.checked_sub(1)?;
+                        self.run(body)?;
+                    }
+                }
+                Node::Input { .. }
+                | Node::DebugCell
+                | Node::DebugMem
+                | Node::Proc { .. }
+                | Node::Call
+                | Node::Store
+                | Node::Retrieve
+                | Node::Bits(_)
+                | Node::End
+                | Node::Tape(_)
+                | Node::Fork
+                | Node::Random
+                | Node::FileOpen { .. }
+                | Node::FileRead
+                | Node::FileWrite
+                | Node::FileStatus => return None,
+            }
+        }
+        Some(())
+    }
+
+    /// Moves the pointer by `amount`, as long as it stays in the memory
+    fn move_by(&mut self, amount: isize) -> Option<()> {
+        let cell = self.cellptr.checked_add_signed(amount)?;
+        self.cellptr = (cell < self.cells).then_some(cell)?;
+        Some(())
+    }
+
+    /// The cell `offset` away from the pointer, growing the memory up to `cells`. The memory
+    /// left of where the program starts is not used at all
+    fn cell(&mut self, offset: isize) -> Option<&mut u8> {
+        let cell = self.cellptr.checked_add_signed(offset)?;
+        if cell >= self.cells {
+            return None;
+        }
+        if cell >= self.mem.len() {
+            self.mem.resize(cell + 1, 0);
+        }
+        self.mem.get_mut(cell)
+    }
+}
+
+/// What one run of a loop body does to the pointer and the cells, from [`analyze`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Summary {
+    /// How far the body moves the pointer, if it is always the same
+    pub movement: Option<isize>,
+    /// What the body adds to every cell, by its offset from where it started, if all it does is
+    /// move and add
+    pub deltas: Option<Vec<(isize, u8)>>,
+}
+
+impl Summary {
+    /// Whether the body ends where it started, so that the loop always runs on the same cell
+    pub fn balanced(&self) -> bool {
+        self.movement == Some(0)
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.movement {
+            Some(0) => write!(f, "balanced")?,
+            Some(amount) => write!(f, "moves {}", amount)?,
+            None => write!(f, "unbalanced")?,
+        }
+        for (offset, amount) in self.deltas.iter().flatten() {
+            write!(f, ", [{}] {}", offset, *amount as i8)?;
+        }
+        Ok(())
+    }
+}
+
+/// Works out what `body` does every time the loop runs it
+pub fn analyze(body: &[Node]) -> Summary {
+    Summary {
+        movement: movement(body),
+        deltas: deltas(body),
+    }
+}
+
+fn movement(nodes: &[Node]) -> Option<isize> {
+    nodes.iter().try_fold(0, |position, node| match node {
+        Node::Move(amount) => Some(position + amount),
+        Node::Scan(_) | Node::Call | Node::Tape(_) | Node::Fork => None,
+        // A loop runs any number of times, so it only keeps the position known if it is balanced
+        Node::Loop { body, .. } => (movement(body)? == 0).then_some(position),
+        _ => Some(position),
+    })
+}
+
+fn deltas(nodes: &[Node]) -> Option<Vec<(isize, u8)>> {
+    let mut position = 0;
+    let mut added: Vec<(isize, u8)> = Vec::new();
+    for node in nodes {
+        match *node {
+            Node::Move(amount) => position += amount,
+            Node::Add { offset, amount } => {
+                let cell = position + offset;
+                match added.iter_mut().find(|(other, _)| *other == cell) {
+                    Some((_, total)) => *total = total.wrapping_add(amount),
+                    None => added.push((cell, amount)),
+                }
+            }
+            _ => return None,
+        }
+    }
+    added.retain(|&(_, amount)| amount != 0);
+    Some(added)
+}
+
+/// The cells a program can touch, by their distance from the cell it starts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bounds {
+    pub low: isize,
+    pub high: isize,
+}
+
+impl fmt::Display for Bounds {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "cells {} to {} from the start", self.low, self.high)
+    }
+}
+
+/// Whether a [`Node::MulAdd`] of `code` may reach a cell out of a memory of `mem_size` cells
+/// starting at `offset`. It only does when the current cell is 0, when the copy loop it came
+/// from would not have run, so the backends only run it when the current cell is not 0 then
+pub fn needs_guard(code: &[Node], offset: usize, mem_size: usize) -> bool {
+    !bounds(code).is_some_and(|bounds| {
+        offset as isize + bounds.low >= 0 && offset as isize + bounds.high < mem_size as isize
+    })
+}
+
+/// Works out which cells `nodes` can touch, which is only known if every loop is balanced and
+/// there are no scans, so that the pointer is in the same place every time a node runs
+pub fn bounds(nodes: &[Node]) -> Option<Bounds> {
+    fn walk(nodes: &[Node], mut position: isize, bounds: &mut Bounds) -> Option<isize> {
+        for node in nodes {
+            let cell = match *node {
+                Node::Move(amount) => {
+                    position += amount;
+                    position
+                }
+                Node::Scan(_) | Node::Call | Node::Tape(_) | Node::Fork | Node::FileOpen { .. } => {
+                    return None
+                }
+                Node::Loop { ref body, .. } => {
+                    if walk(body, position, bounds)? != position {
+                        return None;
+                    }
+                    position
+                }
+                ref node => position + reach(node),
+            };
+            bounds.low = bounds.low.min(cell);
+            bounds.high = bounds.high.max(cell);
+        }
+        Some(position)
+    }
+    let mut bounds = Bounds { low: 0, high: 0 };
+    walk(nodes, 0, &mut bounds)?;
+    Some(bounds)
+}
+
+/// Whether `nodes` have a node `f` is true for, in the loops and the procedures too
+pub fn contains(nodes: &[Node], f: fn(&Node) -> bool) -> bool {
+    nodes.iter().any(|node| match node {
+        _ if f(node) => true,
+        Node::Loop { body, .. } | Node::Proc { body } => contains(body, f),
+        _ => false,
+    })
+}
+
+/// Whether `node` is one of the dialects, which only the interpreter and the C code have
+pub fn is_extension(node: &Node) -> bool {
+    matches!(
+        node,
+        Node::Proc { .. }
+            | Node::Call
+            | Node::Store
+            | Node::Retrieve
+            | Node::Bits(_)
+            | Node::End
+            | Node::Tape(_)
+            | Node::Fork
+            | Node::Random
+            | Node::FileOpen { .. }
+            | Node::FileRead
+            | Node::FileWrite
+            | Node::FileStatus
+    )
+}
+
+/// The [`Node::MulAdd`]s doing the same as `body`, followed by a [`Node::SetZero`], if it is
+/// balanced, only adds to cells, and takes exactly 1 from the current cell
+fn copy_loop(body: &[Node]) -> Option<Vec<Node>> {
+    let summary = analyze(body);
+    if !summary.balanced() {
+        return None;
+    }
+    let mut added = summary.deltas?;
+    let current = added.iter().position(|&(cell, _)| cell == 0)?;
+    if added.remove(current).1 != 255 {
+        return None;
+    }
+    let adds = added
+        .into_iter()
+        .map(|(offset, factor)| Node::MulAdd { offset, factor })
+        .chain([Node::SetZero]);
+    Some(adds.collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::interpreter::{BfIo, Interpreter, State};
+
+    /// Collects the output, and gives no input besides what was fed
+    struct Recorder(Vec<u8>);
+
+    impl BfIo for Recorder {
+        fn read(&mut self) -> Option<u8> {
+            None
+        }
+
+        fn write(&mut self, b: u8) {
+            self.0.push(b)
+        }
+    }
+
+    /// Programs with their input
+    const PROGRAMS: &[(&str, &[u8])] = &[
+        ("++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.------.--------.>>+.", b""),
+        ("[comment +-.,]++>+++[<+>-]<.", b""),
+        ("+++[>+++[>+<-]<-]>>.", b""),
+        ("-[>+<-]>.[-]++++[->++<]>.", b""),
+        (",[.,]", b"echo"),
+        (",[->+>++<<]>.>.", b"\x07"),
+        ("+>+>+>+<[<]>.>[>]<.", b""),
+        ("[-]>[-]<++[>+++<-]>[<+>-]<.", b""),
+        ("+[>+]", b""),
+        ("+[<+]", b""),
+        ("<.", b""),
+    ];
+
+    /// Runs `contents` with `passes`, checking the nodes after every pass, and returns what it
+    /// wrote and how it ended. The pointer starts at cell 20 of 200, so that the programs can go
+    /// out of the memory on both sides
+    fn run(contents: &str, input: &[u8], passes: u32) -> (Vec<u8>, Result<State, String>) {
+        let config = Config {
+            mem_size: 200,
+            offset: 20,
+            passes,
+            verify_ir: true,
+            ..Config::default()
+        };
+        let mut interpreter = match Interpreter::new(contents, config) {
+            Ok(interpreter) => interpreter,
+            Err(err) => panic!("{} with the passes {:#b}: {}", contents, passes, err),
+        };
+        interpreter.feed(input);
+        let mut io = Recorder(Vec::new());
+        let end = interpreter.run_steps(&mut io, 1 << 20);
+        (io.0, end.map_err(|err| err.to_string()))
+    }
+
+    /// Checks that `contents` does the same with `passes` as without any of them
+    fn assert_same(contents: &str, input: &[u8], passes: u32) {
+        assert_eq!(
+            run(contents, input, passes),
+            run(contents, input, 0),
+            "{} with the passes {:#b}",
+            contents,
+            passes
+        );
+    }
+
+    #[test]
+    fn levels_do_what_the_source_does() {
+        for &(contents, input) in PROGRAMS {
+            for level in 1..=3 {
+                assert_same(contents, input, level_passes(level));
+            }
+        }
+    }
+
+    #[test]
+    fn every_pass_does_what_the_source_does() {
+        let all = (1 << PASSES.len()) - 1;
+        for &(contents, input) in PROGRAMS {
+            for i in 0..PASSES.len() {
+                assert_same(contents, input, 1 << i);
+            }
+            assert_same(contents, input, all);
+        }
+    }
+
+    #[test]
+    fn report_has_the_passes_that_ran() {
+        let config = Config {
+            passes: pass_mask("rle").unwrap() | pass_mask("clear-loops").unwrap(),
+            ..Config::default()
+        };
+        let (nodes, report) =
+            optimize_with_report(parse("+++[-]>", &config).unwrap(), &config).unwrap();
+        assert_eq!(
+            nodes,
+            [
+                Node::Add {
+                    offset: 0,
+                    amount: 3
+                },
+                Node::SetZero,
+                Node::Move(1)
+            ]
+        );
+        assert_eq!(
+            report
+                .passes
+                .iter()
+                .map(|&(name, _)| name)
+                .collect::<Vec<_>>(),
+            ["rle", "clear-loops"]
+        );
+        assert_eq!(report.parsed.nodes, 6);
+    }
+
+    /// The nodes of `contents` as they are parsed
+    fn nodes(contents: &str) -> Vec<Node> {
+        parse(contents, &Config::default()).unwrap()
+    }
+
+    #[test]
+    fn dead_code_removes_loops_that_never_run() {
+        assert_eq!(
+            dead_code(nodes("[-]>[+.]+[-][>]")),
+            [
+                Node::Move(1),
+                Node::Add {
+                    offset: 0,
+                    amount: 1
+                },
+                Node::Loop {
+                    id: 2,
+                    body: vec![Node::Add {
+                        offset: 0,
+                        amount: 255
+                    }]
+                },
+            ]
+        );
+        // Nothing after a loop that never ends runs
+        assert_eq!(
+            dead_code(nodes("+[].")),
+            [
+                Node::Add {
+                    offset: 0,
+                    amount: 1
+                },
+                Node::Loop {
+                    id: 0,
+                    body: Vec::new()
+                },
+            ]
+        );
+        // The cell is not known after an input
+        let code = nodes(",[-]");
+        assert_eq!(dead_code(code.clone()), code);
+    }
+
+    #[test]
+    fn const_fold_prints_known_cells() {
+        assert_eq!(
+            fold_constants(defer_moves(merge_runs(nodes("++.<<.>[->+<]>.")))),
+            [
+                Node::Add {
+                    offset: 0,
+                    amount: 2
+                },
+                Node::Print(2),
+                // Not known to be in the memory
+                Node::Output { offset: -2 },
+                // The loop runs on a cell known to be 0
+                Node::Move(-1),
+                Node::Print(2),
+                Node::Move(1),
+            ]
+        );
+    }
+
+    #[test]
+    fn const_fold_keeps_outputs_out_of_the_memory() {
+        let programs = [
+            format!("+.{}.", "<".repeat(52)),
+            format!("+.{}+.", ">".repeat(300)),
+        ];
+        let fold = pass_mask("const-fold").unwrap();
+        for contents in programs {
+            assert_same(&contents, b"", fold | level_passes(1));
+            assert_same(&contents, b"", level_passes(3));
+        }
+    }
+
+    #[test]
+    fn analyze_tells_balanced_loops() {
+        let summary = analyze(&nodes(">++<-"));
+        assert!(summary.balanced());
+        assert_eq!(summary.deltas, Some(vec![(1, 2), (0, 255)]));
+        assert_eq!(summary.to_string(), "balanced, [1] 2, [0] -1");
+
+        let summary = analyze(&nodes(">+>[-]"));
+        assert_eq!(summary.movement, Some(2));
+        assert_eq!(summary.deltas, None);
+        assert_eq!(summary.to_string(), "moves 2");
+
+        // A loop inside only keeps the position known when it is balanced itself
+        assert!(!analyze(&nodes("[>]")).balanced());
+        assert!(!analyze(&nodes("[>+]")).balanced());
+        assert!(analyze(&nodes("[>+<-]")).balanced());
+    }
+
+    #[test]
+    fn eval_stays_in_the_memory() {
+        let config = Config {
+            mem_size: 200,
+            offset: 20,
+            ..Config::default()
+        };
+        let code = parse("+.>>>+.", &config).unwrap();
+        assert_eq!(
+            evaluate(code, &config),
+            [
+                Node::Print(1),
+                Node::Print(1),
+                Node::Add {
+                    offset: 0,
+                    amount: 1
+                },
+                Node::Add {
+                    offset: 3,
+                    amount: 1
+                },
+                Node::Move(3)
+            ]
+        );
+        let far = parse(&format!("+.{}+.", ">".repeat(300)), &config).unwrap();
+        assert_eq!(evaluate(far.clone(), &config), far);
+        // The input is not known while compiling
+        let reads = parse("+.,.", &config).unwrap();
+        assert_eq!(evaluate(reads.clone(), &config), reads);
+    }
+
+    #[test]
+    fn levels_leave_eval_out() {
+        let eval = pass_mask("eval").unwrap();
+        assert!((0..=3).all(|level| level_passes(level) & eval == 0));
+    }
+
+    #[test]
+    fn unroll_replaces_short_loops_with_adds() {
+        assert_eq!(
+            unroll_loops(merge_runs(nodes("+++[>++<-]"))),
+            [
+                Node::Add {
+                    offset: 0,
+                    amount: 3
+                },
+                Node::Add {
+                    offset: 1,
+                    amount: 6
+                },
+                Node::Add {
+                    offset: 0,
+                    amount: 253
+                },
+            ]
+        );
+        // Runs too many times, from an unknown value, or is not balanced
+        for contents in ["-[>+<-]", ",[>+<-]", "+[>+<<-]"] {
+            let code = merge_runs(nodes(contents));
+            assert_eq!(unroll_loops(code.clone()), code, "{}", contents);
+        }
+    }
+
+    #[test]
+    fn peephole_cancels_what_undoes_itself() {
+        assert_eq!(peephole(nodes("+-<>+><-")), []);
+        assert_eq!(
+            peephole(nodes("[>+-<]")),
+            [Node::Loop {
+                id: 0,
+                body: Vec::new()
+            }]
+        );
+        // The adds before a clear loop, which sets the cell to 0 anyway
+        assert_eq!(peephole(nodes("++[-]")), nodes("[-]"));
+        // `[--]` only ends on an even value, so the adds before it matter
+        let code = nodes("+[--]");
+        assert_eq!(peephole(code.clone()), code);
+    }
+
+    #[test]
+    fn verify_finds_broken_nodes() {
+        let limits = Limits { loops: 2, reach: 3 };
+        let empty = |id| Node::Loop {
+            id,
+            body: Vec::new(),
+        };
+        assert!(verify(&[empty(0), empty(1)], &limits).is_ok());
+        assert!(verify(&[empty(1), empty(0)], &limits).is_err());
+        assert!(verify(&[empty(0), empty(0)], &limits).is_err());
+        assert!(verify(&[empty(2)], &limits).is_err());
+        assert!(verify(&[Node::Move(4)], &limits).is_err());
+        assert!(verify(&[Node::Scan(0)], &limits).is_err());
+        assert!(verify(
+            &[Node::MulAdd {
+                offset: 0,
+                factor: 1
+            }],
+            &limits
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn limits_come_from_the_source() {
+        let code = nodes("[>>]<[-]");
+        let limits = Limits::of(&code, level_passes(3));
+        assert_eq!((limits.loops, limits.reach), (2, 3));
+        let limits = Limits::of(&code, pass_mask("eval").unwrap());
+        assert_eq!(limits.reach, 3 + EVAL_CELLS);
+    }
+
+    #[test]
+    fn bounds_are_known_without_scans() {
+        assert_eq!(
+            bounds(&nodes("<<>>>[>+<-]")),
+            Some(Bounds { low: -2, high: 2 })
+        );
+        assert_eq!(bounds(&nodes("+[>]")), None);
+        assert_eq!(bounds(&nodes("+[>+]")), None);
+
+        let code = copy_loops(nodes("[->+<]"));
+        assert!(!needs_guard(&code, 0, 2));
+        assert!(needs_guard(&code, 1, 2));
+        assert!(needs_guard(&nodes("[>]"), 0, 100));
+    }
+
+    #[test]
+    fn copy_loops_at_the_end_of_the_memory_stay_in_it() {
+        // The loop would reach past the end if it ran, which it does not on the 0 it reads
+        let contents = format!("{},[->+<]+.", ">".repeat(180));
+        for passes in [level_passes(2), level_passes(3)] {
+            assert_same(&contents, b"\0", passes);
+        }
+    }
+}
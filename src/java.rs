@@ -0,0 +1,268 @@
+//! Writes the nodes as a Java class, for `--emit=java` and `--compiler=javac`.
+//!
+//! The class is `BfProgram`, which only uses `java.io`. The memory is an array indexed by the
+//! pointer, so going out of it throws instead of reaching anything else, and the cells wrap like
+//! the ones of the interpreter. The JVM does not compile big methods, so the code is split into
+//! methods of [`METHOD_NODES`] nodes at most, with the memory and the pointer in static fields
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+
+/// The most nodes a method has, which keeps them well under the 8000 bytes of bytecode the JIT
+/// compiles
+const METHOD_NODES: usize = 256;
+
+/// The Java code of `code`. `run` takes the input and the output, and `main` gives it stdin and
+/// stdout, so the class can also be used by other code
+pub fn translate(code: &[Node], options: &CompileOptions) -> Result<String, String> {
+    brainfuck_only("java", code)?;
+    let (cell, mask) = match options.cell_size {
+        8 => ("byte", " & 0xff"),
+        16 => ("char", ""),
+        _ => ("int", ""),
+    };
+    let mut writer = Writer {
+        code: String::new(),
+        methods: Vec::new(),
+        mask,
+        debug: options.debug,
+        debug_env: options.debug_env,
+        unbuffered: options.unbuffered,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+    };
+    writer.block(code, 2);
+    // The same status as the interpreter exits with
+    let exit_code = match options.exit_cell {
+        Some(ExitCell::Current) => format!("mem[p]{}", mask),
+        Some(ExitCell::Cell(cell)) => format!("mem[{}]{}", cell, mask),
+        None => "0".to_owned(),
+    };
+    let debug = match options.debug && options.debug_env {
+        true => "static boolean debug;\n    ",
+        false => "",
+    };
+    let debug_init = match options.debug && options.debug_env {
+        true => "        String var = System.getenv(\"BF_DEBUG\");\n        debug = var != null && !var.isEmpty() && !var.equals(\"0\");\n",
+        false => "",
+    };
+    Ok(format!(
+        "\
+import java.io.*;
+
+class BfProgram {{
+    /** What `,` reads before the input */
+    static final byte[] INPUT = {{{}}};
+
+    static {}[] mem;
+    static int p;
+    static int inputPos;
+    static int debugCount;
+    {}static InputStream in;
+    static OutputStream out;
+
+    /** Reads a byte for `,`, where the end of the input reads as 0 like in the interpreter */
+    static {} getch() throws IOException {{
+        if (inputPos < INPUT.length) return ({}) (INPUT[inputPos++] & 0xff);
+        out.flush();
+        int ch = in.read();
+        return ({}) (ch == -1 ? 0 : ch);
+    }}
+
+    /** Writes the text of the debug symbols */
+    static void print(String text) throws IOException {{
+        out.write(text.getBytes());
+    }}
+
+    /** Runs the program, and returns the exit code */
+    static int run(InputStream input, OutputStream output) throws IOException {{
+        in = input;
+        out = output;
+        mem = new {}[{}];
+        p = {};
+        inputPos = 0;
+        debugCount = 0;
+{}{}        out.flush();
+        return {};
+    }}
+{}
+    public static void main(String[] args) {{
+        OutputStream output = new BufferedOutputStream(new FileOutputStream(FileDescriptor.out), 1 << 16);
+        try {{
+            System.exit(run(System.in, output));
+        }} catch (IOException err) {{
+            System.err.println(err.getMessage());
+            System.exit(1);
+        }}
+    }}
+}}
+",
+        options
+            .input
+            .iter()
+            .map(|&byte| (byte as i8).to_string())
+            .collect::<Vec<_>>()
+            .join(", "),
+        cell,
+        debug,
+        cell,
+        cell,
+        cell,
+        cell,
+        options.mem_size,
+        options.offset,
+        debug_init,
+        writer.code,
+        exit_code,
+        writer.methods.concat()
+    ))
+}
+
+/// Writes the Java code for the nodes
+struct Writer {
+    code: String,
+    /// The methods the code is split into
+    methods: Vec<String>,
+    /// What makes a cell its unsigned value, for `byte`
+    mask: &'static str,
+    debug: bool,
+    /// Whether the debug symbols only print when `debug` is set
+    debug_env: bool,
+    unbuffered: bool,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+}
+
+impl Writer {
+    /// Writes a line indented by `depth` levels
+    fn line(&mut self, depth: usize, text: &str) {
+        self.code += &format!("{}{}\n", "    ".repeat(depth), text);
+    }
+
+    /// Writes `text`, which writes to the output, flushing it after with `--unbuffered`
+    fn output(&mut self, depth: usize, text: &str) {
+        match self.unbuffered {
+            true => self.line(depth, &format!("{} out.flush();", text)),
+            false => self.line(depth, text),
+        }
+    }
+
+    /// Writes the nodes, in methods of their own when there are too many of them for one
+    fn block(&mut self, nodes: &[Node], depth: usize) {
+        if size(nodes) <= METHOD_NODES {
+            for node in nodes {
+                self.node(node, depth);
+            }
+            return;
+        }
+        let mut rest = nodes;
+        while !rest.is_empty() {
+            // As many nodes as fit, and at least one, whose loop splits its own body
+            let mut len = 1;
+            let mut total = size(&rest[..1]);
+            while let Some(node) = rest.get(len) {
+                total += size(std::slice::from_ref(node));
+                if total > METHOD_NODES {
+                    break;
+                }
+                len += 1;
+            }
+            // The methods of the loops in it come after it
+            let index = self.methods.len();
+            let name = format!("part{}", index);
+            self.methods.push(String::new());
+            let code = std::mem::take(&mut self.code);
+            for node in &rest[..len] {
+                self.node(node, 2);
+            }
+            let body = std::mem::replace(&mut self.code, code);
+            self.methods[index] = format!(
+                "\n    static void {}() throws IOException {{\n{}    }}\n",
+                name, body
+            );
+            self.line(depth, &format!("{}();", name));
+            rest = &rest[len..];
+        }
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) {
+        let line = match *node {
+            Node::Move(amount) if amount < 0 => format!("p -= {};", amount.unsigned_abs()),
+            Node::Move(amount) => format!("p += {};", amount),
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } if (amount as i8) < 0 => {
+                format!("{} -= {};", cell(offset), (amount as i8).unsigned_abs())
+            }
+            Node::Add { offset, amount } => format!("{} += {};", cell(offset), amount),
+            Node::SetZero => "mem[p] = 0;".to_owned(),
+            Node::Scan(amount) if amount < 0 => {
+                format!("while (mem[p] != 0) p -= {};", amount.unsigned_abs())
+            }
+            Node::Scan(amount) => format!("while (mem[p] != 0) p += {};", amount),
+            Node::MulAdd { offset, factor: 1 } => format!("{} += mem[p];", cell(offset)),
+            Node::MulAdd { offset, factor: 255 } => format!("{} -= mem[p];", cell(offset)),
+            Node::MulAdd { offset, factor } if (factor as i8) < 0 => format!(
+                "{} -= mem[p] * {};",
+                cell(offset),
+                (factor as i8).unsigned_abs()
+            ),
+            Node::MulAdd { offset, factor } => {
+                format!("{} += mem[p] * {};", cell(offset), factor)
+            }
+            // Only the low byte is written, like for the C code
+            Node::Output { offset } => {
+                return self.output(depth, &format!("out.write({});", cell(offset)))
+            }
+            Node::Print(value) => return self.output(depth, &format!("out.write({});", value)),
+            Node::Input { offset } => format!("{} = getch();", cell(offset)),
+            Node::Loop { ref body, .. } => {
+                self.line(depth, "while (mem[p] != 0) {");
+                self.block(body, depth + 1);
+                self.line(depth, "}");
+                return;
+            }
+            // The same as the C code prints
+            Node::DebugCell if self.debug => format!(
+                "debugCount++; print(\"\\ndebug flag \" + debugCount + \" : \"); out.write(mem[p]); print(\", \" + (int) (mem[p]{}) + \", \" + p + \"\\n\");",
+                self.mask
+            ),
+            Node::DebugMem if self.debug => format!(
+                "print(\"\\n\"); for (int i = Math.max(p - 15, 0); i < Math.min(p + 15, mem.length); i++) print(i == p ? \"|\" + (int) (mem[i]{}) + \"| \" : (int) (mem[i]{}) + \" \"); print(\"\\n\");",
+                self.mask, self.mask
+            ),
+            Node::DebugCell | Node::DebugMem => return,
+            // Turned down by `translate`
+            _ => return,
+        };
+        let line = match *node {
+            Node::DebugCell | Node::DebugMem if self.debug_env => {
+                format!("if (debug) {{ {} }}", line)
+            }
+            Node::MulAdd { .. } if self.guard => format!("if (mem[p] != 0) {}", line),
+            _ => line,
+        };
+        self.line(depth, &line);
+    }
+}
+
+/// How many nodes `nodes` are, with the ones in loops
+fn size(nodes: &[Node]) -> usize {
+    nodes
+        .iter()
+        .map(|node| match node {
+            Node::Loop { body, .. } => 1 + size(body),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// The Java place for the cell `offset` away from the pointer
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "mem[p]".to_owned(),
+        _ if offset < 0 => format!("mem[p - {}]", offset.unsigned_abs()),
+        _ => format!("mem[p + {}]", offset),
+    }
+}
@@ -0,0 +1,286 @@
+//! Turns the instructions into x86-64 machine code, for `--jit`.
+//!
+//! Registers while the code runs: `rbx` is the current cell, `r12` and `r13` are the start and
+//! the end of the memory, `r14` is the [`Context`] passed to `,` and `.`. Every pointer movement
+//! is checked against `r12` and `r13`, so the code never touches anything outside the memory
+
+use crate::{
+    bytecode::Instr,
+    interpreter::{BfError, BfIo},
+};
+use std::{collections::VecDeque, mem, ptr};
+
+/// What `,` and `.` reach from the native code
+struct Context<'a> {
+    input: &'a mut VecDeque<u8>,
+    io: &'a mut dyn BfIo,
+}
+
+extern "C" fn write(context: &mut Context, byte: u8) {
+    context.io.write(byte)
+}
+
+extern "C" fn read(context: &mut Context) -> u8 {
+    context.io.flush();
+    context
+        .input
+        .pop_front()
+        .or_else(|| context.io.read())
+        .unwrap_or(0)
+}
+
+/// Takes the context, the current cell and the start and the end of the memory, and returns the
+/// cell the program ended on, or null if it went out of the memory
+type Entry = unsafe extern "C" fn(*mut libc::c_void, *mut u8, *mut u8, *mut u8) -> *mut u8;
+
+/// The machine code being written, with the jumps whose target is not known yet
+struct Assembler {
+    buf: Vec<u8>,
+    /// Where to write the distance to the label, and the label
+    fixups: Vec<(usize, usize)>,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes)
+    }
+
+    /// A jump with a 32 bit distance to `label`
+    fn jump(&mut self, opcode: &[u8], label: usize) {
+        self.emit(opcode);
+        self.fixups.push((self.buf.len(), label));
+        self.emit(&[0; 4]);
+    }
+
+    /// Jumps to `error` unless the pointer in `rbx`, or `rcx` if `rcx` is set, is in the memory
+    fn check(&mut self, rcx: bool, error: usize) {
+        let (start, end) = if rcx { (0xe1, 0xe9) } else { (0xe3, 0xeb) };
+        self.emit(&[0x4c, 0x39, start]); // cmp reg, r12
+        self.jump(&[0x0f, 0x82], error); // jb error
+        self.emit(&[0x4c, 0x39, end]); // cmp reg, r13
+        self.jump(&[0x0f, 0x83], error); // jae error
+    }
+
+    /// Puts the address of the cell `offset` away from the current one in `rcx`
+    fn address(&mut self, offset: i32, error: usize) {
+        self.emit(&[0x48, 0x8d, 0x8b]); // lea rcx, [rbx + offset]
+        self.emit(&offset.to_le_bytes());
+        self.check(true, error);
+    }
+
+    fn move_by(&mut self, amount: i32, error: usize) {
+        self.emit(&[0x48, 0x81, 0xc3]); // add rbx, amount
+        self.emit(&amount.to_le_bytes());
+        self.check(false, error);
+    }
+
+    fn add(&mut self, offset: i32, amount: u8, error: usize) {
+        if offset == 0 {
+            self.emit(&[0x80, 0x03, amount]); // add byte [rbx], amount
+        } else {
+            self.address(offset, error);
+            self.emit(&[0x80, 0x01, amount]); // add byte [rcx], amount
+        }
+    }
+
+    /// Calls `function` with the context as its first argument
+    fn call(&mut self, function: usize) {
+        self.emit(&[0x4c, 0x89, 0xf7]); // mov rdi, r14
+        self.emit(&[0x48, 0xb8]); // mov rax, function
+        self.emit(&(function as u64).to_le_bytes());
+        self.emit(&[0xff, 0xd0]); // call rax
+    }
+}
+
+/// A program compiled to native code
+pub struct Jit {
+    code: *mut libc::c_void,
+    len: usize,
+}
+
+impl Jit {
+    /// Compiles `code` to start running from the instruction at `entry`.
+    /// Returns `None` for code with debug symbols, whose output needs the interpreter,
+    /// or when there is no executable memory to put it in
+    pub fn compile(code: &[Instr], entry: usize) -> Option<Jit> {
+        // Every instruction is a label, followed by the end of the code and the error
+        let exit = code.len();
+        let error = exit + 1;
+        let mut labels = vec![0; code.len() + 2];
+        let mut asm = Assembler {
+            buf: Vec::new(),
+            fixups: Vec::new(),
+        };
+
+        // push rbx, r12, r13, r14 and r15, which also aligns the stack for the calls
+        asm.emit(&[0x53, 0x41, 0x54, 0x41, 0x55, 0x41, 0x56, 0x41, 0x57]);
+        asm.emit(&[0x49, 0x89, 0xfe]); // mov r14, rdi
+        asm.emit(&[0x48, 0x89, 0xf3]); // mov rbx, rsi
+        asm.emit(&[0x49, 0x89, 0xd4]); // mov r12, rdx
+        asm.emit(&[0x49, 0x89, 0xcd]); // mov r13, rcx
+        asm.jump(&[0xe9], entry); // jmp entry
+
+        for (i, &instr) in code.iter().enumerate() {
+            labels[i] = asm.buf.len();
+            match instr {
+                Instr::Move(amount) => asm.move_by(amount.try_into().ok()?, error),
+                Instr::Add { offset, amount } => asm.add(offset.try_into().ok()?, amount, error),
+                Instr::SetZero => asm.emit(&[0xc6, 0x03, 0]), // mov byte [rbx], 0
+                Instr::Set(value) => asm.emit(&[0xc6, 0x03, value]), // mov byte [rbx], value
+                Instr::AddMove { offset, amount, by } => {
+                    asm.add(offset.try_into().ok()?, amount, error);
+                    asm.move_by(by.try_into().ok()?, error);
+                }
+                Instr::Scan(amount) => {
+                    let start = asm.buf.len();
+                    asm.emit(&[0x80, 0x3b, 0]); // cmp byte [rbx], 0
+                    asm.emit(&[0x0f, 0x84, 0, 0, 0, 0]); // je end
+                    let end = asm.buf.len();
+                    asm.move_by(amount.try_into().ok()?, error);
+                    asm.emit(&[0xe9]); // jmp start
+                    asm.emit(&(start as i32 - asm.buf.len() as i32 - 4).to_le_bytes());
+                    let distance = (asm.buf.len() - end) as i32;
+                    asm.buf[end - 4..end].copy_from_slice(&distance.to_le_bytes());
+                }
+                Instr::MulAdd { offset, factor } => {
+                    asm.emit(&[0x0f, 0xb6, 0x03]); // movzx eax, byte [rbx]
+                    asm.emit(&[0x85, 0xc0]); // test eax, eax
+                    asm.emit(&[0x0f, 0x84, 0, 0, 0, 0]); // je end
+                    let end = asm.buf.len();
+                    asm.address(offset.try_into().ok()?, error);
+                    if factor != 1 {
+                        asm.emit(&[0x69, 0xc0]); // imul eax, eax, factor
+                        asm.emit(&(factor as i32).to_le_bytes());
+                    }
+                    asm.emit(&[0x00, 0x01]); // add byte [rcx], al
+                    let distance = (asm.buf.len() - end) as i32;
+                    asm.buf[end - 4..end].copy_from_slice(&distance.to_le_bytes());
+                }
+                Instr::Output { offset } => {
+                    if offset == 0 {
+                        asm.emit(&[0x0f, 0xb6, 0x33]); // movzx esi, byte [rbx]
+                    } else {
+                        asm.address(offset.try_into().ok()?, error);
+                        asm.emit(&[0x0f, 0xb6, 0x31]); // movzx esi, byte [rcx]
+                    }
+                    asm.call(write as extern "C" fn(&mut Context, u8) as usize);
+                }
+                Instr::Print(value) => {
+                    asm.emit(&[0xbe]); // mov esi, value
+                    asm.emit(&(value as u32).to_le_bytes());
+                    asm.call(write as extern "C" fn(&mut Context, u8) as usize);
+                }
+                Instr::Input { offset } => {
+                    asm.call(read as extern "C" fn(&mut Context) -> u8 as usize);
+                    if offset == 0 {
+                        asm.emit(&[0x88, 0x03]); // mov byte [rbx], al
+                    } else {
+                        asm.address(offset.try_into().ok()?, error);
+                        asm.emit(&[0x88, 0x01]); // mov byte [rcx], al
+                    }
+                }
+                Instr::JumpIfZero(target) => {
+                    asm.emit(&[0x80, 0x3b, 0]); // cmp byte [rbx], 0
+                    asm.jump(&[0x0f, 0x84], target + 1); // je after the ]
+                }
+                Instr::JumpUnlessZero(target) => {
+                    asm.emit(&[0x80, 0x3b, 0]); // cmp byte [rbx], 0
+                    asm.jump(&[0x0f, 0x85], target + 1); // jne after the [
+                }
+                Instr::DebugCell
+                | Instr::DebugMem
+                | Instr::Proc(_)
+                | Instr::Return
+                | Instr::Call
+                | Instr::Store
+                | Instr::Retrieve
+                | Instr::Bits(_)
+                | Instr::End
+                | Instr::Tape(_)
+                | Instr::Fork
+                | Instr::Random
+                | Instr::FileOpen { .. }
+                | Instr::FileRead
+                | Instr::FileWrite
+                | Instr::FileStatus => return None,
+            }
+        }
+
+        labels[exit] = asm.buf.len();
+        asm.emit(&[0x48, 0x89, 0xd8]); // mov rax, rbx
+        asm.emit(&[0xeb, 0x02]); // jmp over the error
+        labels[error] = asm.buf.len();
+        asm.emit(&[0x31, 0xc0]); // xor eax, eax
+        asm.emit(&[0x41, 0x5f, 0x41, 0x5e, 0x41, 0x5d, 0x41, 0x5c, 0x5b]); // pop everything back
+        asm.emit(&[0xc3]); // ret
+
+        for &(at, label) in &asm.fixups {
+            let distance = labels[label] as i32 - at as i32 - 4;
+            asm.buf[at..at + 4].copy_from_slice(&distance.to_le_bytes());
+        }
+        Self::map(&asm.buf)
+    }
+
+    /// Copies `buf` into memory that can be executed
+    fn map(buf: &[u8]) -> Option<Jit> {
+        // SAFETY: the mapping is new, so nothing else can be using it
+        unsafe {
+            let code = libc::mmap(
+                ptr::null_mut(),
+                buf.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if code == libc::MAP_FAILED {
+                return None;
+            }
+            let jit = Jit {
+                code,
+                len: buf.len(),
+            };
+            ptr::copy_nonoverlapping(buf.as_ptr(), code.cast(), buf.len());
+            if libc::mprotect(code, buf.len(), libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                return None;
+            }
+            Some(jit)
+        }
+    }
+
+    /// Runs the program on `mem` starting at `cell`, and returns the cell it ended on
+    pub fn run(
+        &self,
+        mem: &mut [u8],
+        cell: usize,
+        input: &mut VecDeque<u8>,
+        io: &mut dyn BfIo,
+    ) -> Result<usize, BfError> {
+        let mut context = Context { input, io };
+        let range = mem.as_mut_ptr_range();
+        // SAFETY: the code was generated by `compile` for this calling convention, and it checks
+        // every move against the start and the end of `mem`
+        unsafe {
+            let entry: Entry = mem::transmute(self.code);
+            let end = entry(
+                ptr::addr_of_mut!(context).cast(),
+                range.start.add(cell),
+                range.start,
+                range.end,
+            );
+            if end.is_null() {
+                return Err(BfError::MemoryOutOfBounds);
+            }
+            Ok(end.offset_from(range.start) as usize)
+        }
+    }
+}
+
+impl Drop for Jit {
+    fn drop(&mut self) {
+        // SAFETY: `code` was mapped with this length by `map` and is not used after this
+        unsafe {
+            libc::munmap(self.code, self.len);
+        }
+    }
+}
@@ -0,0 +1,498 @@
+//! Writes the nodes as textual LLVM IR, for `--emit=llvm-ir` and `--llvm`.
+//!
+//! The IR uses the opaque `ptr` type of LLVM 15 and later, and leaves the target to the tools, so
+//! the same file builds for every target clang or llc can build for. The pointer is the index of
+//! the current cell in the memory, kept in an `alloca` that LLVM turns into a register
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+use std::fmt::Write;
+
+/// The LLVM IR of `code`, with the same settings as the Rust code
+pub fn translate(code: &[Node], options: &CompileOptions) -> Result<String, String> {
+    brainfuck_only("llvm-ir", code)?;
+    let cell = format!("i{}", options.cell_size);
+    let mem = format!("[{} x {}]", options.mem_size, cell);
+    let mut writer = Writer {
+        code: String::new(),
+        cell: cell.clone(),
+        cell_size: options.cell_size,
+        mem: mem.clone(),
+        debug: options.debug,
+        unbuffered: options.unbuffered,
+        temps: 0,
+        labels: 0,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+    };
+    writer.block(code);
+    let exit_code = match options.exit_cell {
+        Some(ExitCell::Current) => Some(writer.cell_at(0)),
+        Some(ExitCell::Cell(cell)) => {
+            let address = writer.temp();
+            writer.line(&format!(
+                "{} = getelementptr {}, ptr @mem, i64 0, i64 {}",
+                address, mem, cell
+            ));
+            Some(address)
+        }
+        None => None,
+    };
+    // The same status as the interpreter exits with
+    let exit_code = match exit_code {
+        Some(address) => {
+            let value = writer.temp();
+            writer.line(&format!("{} = load {}, ptr {}", value, cell, address));
+            writer.cast(&value, options.cell_size, 32)
+        }
+        None => "0".to_owned(),
+    };
+    let mut ir = format!(
+        "\
+; The memory starts zeroed like the one of the interpreter, and is not on the stack
+@mem = internal global {} zeroinitializer
+; What `,` reads before stdin
+@input = private constant {}
+@input_pos = internal global i64 0
+
+declare i32 @getchar()
+declare i32 @putchar(i32)
+declare i32 @fflush(ptr)
+
+; Reads a byte for `,`, where the end of the input reads as 0 like in the interpreter
+define internal {} @bf_getch() {{
+entry:
+  %pos = load i64, ptr @input_pos
+  %given = icmp ult i64 %pos, {}
+  br i1 %given, label %input, label %stdin
+input:
+  %address = getelementptr {}, ptr @input, i64 0, i64 %pos
+  %byte = load i8, ptr %address
+  %next = add i64 %pos, 1
+  store i64 %next, ptr @input_pos
+  %cell = {}
+  ret {} %cell
+stdin:
+  %flushed = call i32 @fflush(ptr null)
+  %ch = call i32 @getchar()
+  %eof = icmp slt i32 %ch, 0
+  %read = select i1 %eof, i32 0, i32 %ch
+  %read_cell = {}
+  ret {} %read_cell
+}}
+",
+        mem,
+        constant(options.input).1,
+        cell,
+        options.input.len(),
+        constant(options.input).0,
+        cast_to("%byte", 8, options.cell_size),
+        cell,
+        cast_to("%read", 32, options.cell_size),
+        cell
+    );
+    if options.debug {
+        ir += &debug_functions(options);
+    }
+    let _ = write!(
+        ir,
+        "
+define i32 @main() {{
+entry:
+  %ptr = alloca i64
+  store i64 {}, ptr %ptr
+{}  %flushed = call i32 @fflush(ptr null)
+  ret i32 {}
+}}
+",
+        options.offset, writer.code, exit_code
+    );
+    Ok(ir)
+}
+
+/// The functions `#` and `|` call in the debug mode, which print what the C code prints
+fn debug_functions(options: &CompileOptions) -> String {
+    let (cell, size) = (format!("i{}", options.cell_size), options.cell_size);
+    let mem = format!("[{} x {}]", options.mem_size, cell);
+    // printf takes the cells that are smaller than an int as an int
+    let (value, value_format) = match size {
+        64 => ("i64", "%llu"),
+        _ => ("i32", "%u"),
+    };
+    let value_size = match size {
+        64 => 64,
+        _ => 32,
+    };
+    let debug_flag = format!("\ndebug flag %d : %c, {}, %ld\n", value_format);
+    let here = format!("|{}| ", value_format);
+    let other = format!("{} ", value_format);
+    let strings = [
+        ("@debug_flag", debug_flag.as_bytes()),
+        ("@debug_here", here.as_bytes()),
+        ("@debug_other", other.as_bytes()),
+        ("@newline", b"\n"),
+        ("@debug_env", b"BF_DEBUG"),
+        ("@zero", b"0"),
+    ];
+    let mut ir = "\n@debug_count = internal global i32 0\n".to_owned();
+    for (name, text) in strings {
+        let mut text = text.to_vec();
+        text.push(0);
+        let _ = writeln!(ir, "{} = private constant {}", name, constant(&text).1);
+    }
+    // Without `--debug-env`, they always print
+    let enabled = match options.debug_env {
+        true => {
+            "\
+  %on = call i1 @bf_debug_on()
+  br i1 %on, label %start, label %done"
+        }
+        false => "  br label %start",
+    };
+    let _ = write!(
+        ir,
+        "
+declare i32 @printf(ptr, ...)
+declare ptr @getenv(ptr)
+declare i32 @strcmp(ptr, ptr)
+
+; Whether BF_DEBUG is set to something other than 0, for `--debug-env`
+define internal i1 @bf_debug_on() {{
+entry:
+  %env = call ptr @getenv(ptr @debug_env)
+  %set = icmp ne ptr %env, null
+  br i1 %set, label %check, label %off
+check:
+  %first = load i8, ptr %env
+  %empty = icmp eq i8 %first, 0
+  %compared = call i32 @strcmp(ptr %env, ptr @zero)
+  %is_zero = icmp eq i32 %compared, 0
+  %no = or i1 %empty, %is_zero
+  %on = xor i1 %no, true
+  ret i1 %on
+off:
+  ret i1 false
+}}
+
+; `#`, prints the current cell
+define internal void @bf_debug_cell(i64 %ptr) {{
+entry:
+{}
+start:
+  %count = load i32, ptr @debug_count
+  %next = add i32 %count, 1
+  store i32 %next, ptr @debug_count
+  %address = getelementptr {}, ptr @mem, i64 0, i64 %ptr
+  %cell = load {}, ptr %address
+  %byte = {}
+  %char = zext i8 %byte to i32
+  %value = {}
+  %printed = call i32 (ptr, ...) @printf(ptr @debug_flag, i32 %next, i32 %char, {} %value, i64 %ptr)
+  br label %done
+done:
+  ret void
+}}
+
+; `|`, prints the cells around the current one
+define internal void @bf_debug_mem(i64 %ptr) {{
+entry:
+{}
+start:
+  %started = call i32 (ptr, ...) @printf(ptr @newline)
+  %low = sub i64 %ptr, 15
+  %high = add i64 %ptr, 15
+  br label %loop
+loop:
+  %i = phi i64 [ %low, %start ], [ %next, %step ]
+  %more = icmp slt i64 %i, %high
+  br i1 %more, label %check, label %end
+check:
+  %below = icmp slt i64 %i, 0
+  %above = icmp sge i64 %i, {}
+  %outside = or i1 %below, %above
+  br i1 %outside, label %step, label %print
+print:
+  %address = getelementptr {}, ptr @mem, i64 0, i64 %i
+  %cell = load {}, ptr %address
+  %value = {}
+  %here = icmp eq i64 %i, %ptr
+  %format = select i1 %here, ptr @debug_here, ptr @debug_other
+  %printed = call i32 (ptr, ...) @printf(ptr %format, {} %value)
+  br label %step
+step:
+  %next = add i64 %i, 1
+  br label %loop
+end:
+  %ended = call i32 (ptr, ...) @printf(ptr @newline)
+  br label %done
+done:
+  ret void
+}}
+",
+        enabled,
+        mem,
+        cell,
+        cast_to("%cell", size, 8),
+        cast_to("%cell", size, value_size),
+        value,
+        enabled,
+        options.mem_size,
+        mem,
+        cell,
+        cast_to("%cell", size, value_size),
+        value
+    );
+    ir
+}
+
+/// Writes the IR for the nodes into the body of `main`
+struct Writer {
+    code: String,
+    /// The type of a cell
+    cell: String,
+    cell_size: u8,
+    /// The type of the memory
+    mem: String,
+    debug: bool,
+    /// Whether the output is flushed after every write
+    unbuffered: bool,
+    /// How many values and labels were named, which numbers the next ones
+    temps: usize,
+    labels: usize,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+}
+
+impl Writer {
+    fn line(&mut self, text: &str) {
+        self.code += &format!("  {}\n", text);
+    }
+
+    fn label(&mut self, name: &str) {
+        self.code += &format!("{}:\n", name);
+    }
+
+    /// The name of a new value
+    fn temp(&mut self) -> String {
+        self.temps += 1;
+        format!("%t{}", self.temps)
+    }
+
+    /// Converts `value` from a `from` bit integer to a `to` bit one, and returns the new value
+    fn cast(&mut self, value: &str, from: u8, to: u8) -> String {
+        if from == to {
+            return value.to_owned();
+        }
+        let temp = self.temp();
+        self.line(&format!("{} = {}", temp, cast_to(value, from, to)));
+        temp
+    }
+
+    /// The index of the current cell
+    fn ptr(&mut self) -> String {
+        let ptr = self.temp();
+        self.line(&format!("{} = load i64, ptr %ptr", ptr));
+        ptr
+    }
+
+    /// The address of the cell `offset` away from the pointer
+    fn cell_at(&mut self, offset: isize) -> String {
+        let ptr = self.ptr();
+        let index = match offset {
+            0 => ptr,
+            _ => {
+                let index = self.temp();
+                self.line(&format!("{} = add i64 {}, {}", index, ptr, offset));
+                index
+            }
+        };
+        let address = self.temp();
+        self.line(&format!(
+            "{} = getelementptr {}, ptr @mem, i64 0, i64 {}",
+            address, self.mem, index
+        ));
+        address
+    }
+
+    /// Loads the cell at `address`
+    fn load(&mut self, address: &str) -> String {
+        let value = self.temp();
+        self.line(&format!("{} = load {}, ptr {}", value, self.cell, address));
+        value
+    }
+
+    fn putchar(&mut self, value: &str) {
+        let result = self.temp();
+        self.line(&format!("{} = call i32 @putchar(i32 {})", result, value));
+        if self.unbuffered {
+            let result = self.temp();
+            self.line(&format!("{} = call i32 @fflush(ptr null)", result));
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        let (cell, size) = (self.cell.clone(), self.cell_size);
+        match *node {
+            Node::Move(amount) => {
+                let ptr = self.ptr();
+                let moved = self.temp();
+                self.line(&format!("{} = add i64 {}, {}", moved, ptr, amount));
+                self.line(&format!("store i64 {}, ptr %ptr", moved));
+            }
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } => {
+                let address = self.cell_at(offset);
+                let value = self.load(&address);
+                let sum = self.temp();
+                self.line(&format!(
+                    "{} = add {} {}, {}",
+                    sum, cell, value, amount as i8
+                ));
+                self.line(&format!("store {} {}, ptr {}", cell, sum, address));
+            }
+            Node::SetZero => {
+                let address = self.cell_at(0);
+                self.line(&format!("store {} 0, ptr {}", cell, address));
+            }
+            Node::Scan(amount) => {
+                self.labels += 1;
+                let (check, step, end) = (
+                    format!("scan{}", self.labels),
+                    format!("scan{}_step", self.labels),
+                    format!("scan{}_end", self.labels),
+                );
+                self.line(&format!("br label %{}", check));
+                self.label(&check);
+                let address = self.cell_at(0);
+                let value = self.load(&address);
+                let nonzero = self.temp();
+                self.line(&format!("{} = icmp ne {} {}, 0", nonzero, cell, value));
+                self.line(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    nonzero, step, end
+                ));
+                self.label(&step);
+                self.node(&Node::Move(amount));
+                self.line(&format!("br label %{}", check));
+                self.label(&end);
+            }
+            Node::MulAdd { offset, factor } => {
+                let current = self.cell_at(0);
+                let current = self.load(&current);
+                let skip = match self.guard {
+                    true => {
+                        self.labels += 1;
+                        let (add, skip) = (
+                            format!("mul{}", self.labels),
+                            format!("mul{}_end", self.labels),
+                        );
+                        let nonzero = self.temp();
+                        self.line(&format!("{} = icmp ne {} {}, 0", nonzero, cell, current));
+                        self.line(&format!(
+                            "br i1 {}, label %{}, label %{}",
+                            nonzero, add, skip
+                        ));
+                        self.label(&add);
+                        Some(skip)
+                    }
+                    false => None,
+                };
+                let address = self.cell_at(offset);
+                let value = self.load(&address);
+                let product = self.temp();
+                self.line(&format!(
+                    "{} = mul {} {}, {}",
+                    product, cell, current, factor as i8
+                ));
+                let sum = self.temp();
+                self.line(&format!("{} = add {} {}, {}", sum, cell, value, product));
+                self.line(&format!("store {} {}, ptr {}", cell, sum, address));
+                if let Some(skip) = skip {
+                    self.line(&format!("br label %{}", skip));
+                    self.label(&skip);
+                }
+            }
+            Node::Output { offset } => {
+                let address = self.cell_at(offset);
+                let value = self.load(&address);
+                // putchar writes the low byte, like for the C code
+                let byte = self.cast(&value, size, 8);
+                let int = self.cast(&byte, 8, 32);
+                self.putchar(&int);
+            }
+            Node::Print(value) => self.putchar(&value.to_string()),
+            Node::Input { offset } => {
+                let value = self.temp();
+                self.line(&format!("{} = call {} @bf_getch()", value, cell));
+                let address = self.cell_at(offset);
+                self.line(&format!("store {} {}, ptr {}", cell, value, address));
+            }
+            Node::Loop { ref body, .. } => {
+                self.labels += 1;
+                let (check, start, end) = (
+                    format!("loop{}", self.labels),
+                    format!("loop{}_body", self.labels),
+                    format!("loop{}_end", self.labels),
+                );
+                self.line(&format!("br label %{}", check));
+                self.label(&check);
+                let address = self.cell_at(0);
+                let value = self.load(&address);
+                let nonzero = self.temp();
+                self.line(&format!("{} = icmp ne {} {}, 0", nonzero, cell, value));
+                self.line(&format!(
+                    "br i1 {}, label %{}, label %{}",
+                    nonzero, start, end
+                ));
+                self.label(&start);
+                self.block(body);
+                self.line(&format!("br label %{}", check));
+                self.label(&end);
+            }
+            Node::DebugCell | Node::DebugMem if self.debug => {
+                let function = match node {
+                    Node::DebugCell => "bf_debug_cell",
+                    _ => "bf_debug_mem",
+                };
+                let ptr = self.ptr();
+                self.line(&format!("call void @{}(i64 {})", function, ptr));
+            }
+            Node::DebugCell | Node::DebugMem => (),
+            // Turned down by `translate`
+            _ => (),
+        }
+    }
+}
+
+/// The instruction converting `value` from a `from` bit integer to a `to` bit one
+fn cast_to(value: &str, from: u8, to: u8) -> String {
+    let op = match from.cmp(&to) {
+        std::cmp::Ordering::Less => "zext",
+        std::cmp::Ordering::Equal => "bitcast",
+        std::cmp::Ordering::Greater => "trunc",
+    };
+    format!("{} i{} {} to i{}", op, from, value, to)
+}
+
+/// The type and the value of a constant holding `bytes`
+fn constant(bytes: &[u8]) -> (String, String) {
+    let kind = format!("[{} x i8]", bytes.len());
+    let mut string = "c\"".to_owned();
+    for &byte in bytes {
+        match byte {
+            b' '..=b'~' if byte != b'"' && byte != b'\\' => string.push(byte as char),
+            _ => string += &format!("\\{:02X}", byte),
+        }
+    }
+    string.push('"');
+    let value = format!("{} {}", kind, string);
+    (kind, value)
+}
@@ -0,0 +1,221 @@
+//! The macros of `--macros`, expanded before the code is parsed.
+//!
+//! A line `!define NAME BODY` makes `@NAME` stand for the rest of the line, which can use other
+//! macros too. The definitions become empty lines and the code of a macro stays on the line it
+//! is used on, so the lines of the errors and of `--line-directives` are the ones of the source.
+//! The errors of the macros themselves point to where the name is written, even inside the body
+//! of another macro. The input after the `!` that ends the code is left as it is
+
+use alloc::{collections::BTreeMap, format, string::String};
+
+/// How many macros can be expanded inside each other, which stops the ones that use themselves
+pub const MACRO_DEPTH: usize = 64;
+
+/// How many bytes the code can have once the macros are expanded
+pub const MACRO_SIZE: usize = 1 << 24;
+
+/// The body of a macro, with the line and column it starts at
+struct Macro<'a> {
+    body: &'a str,
+    line: usize,
+    column: usize,
+}
+
+/// The code in `source` with the macros defined in it expanded. `first_line` is the number of
+/// lines already removed from the start of the file
+pub fn expand(source: &str, first_line: usize) -> Result<String, String> {
+    let mut macros = BTreeMap::new();
+    let mut code = String::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (line, text) in source.split_inclusive('\n').enumerate() {
+        let line = first_line + line + 1;
+        let definition = text.trim_end_matches(['\r', '\n']);
+        start += text.len();
+        let Some(rest) = definition.trim_start().strip_prefix("!define") else {
+            if let Some(input) = expand_line(&mut code, text, line, &macros, &mut depth)? {
+                code.push_str(&source[start - text.len() + input..]);
+                break;
+            }
+            continue;
+        };
+        let column = |rest: &str| definition[..definition.len() - rest.len()].chars().count() + 1;
+        let rest = rest.trim_start();
+        let len = rest.find(|ch| !is_name(ch)).unwrap_or(rest.len());
+        if len == 0 {
+            return Err(format!(
+                "Expected a macro name at {}:{}",
+                line,
+                column(rest)
+            ));
+        }
+        let (name, body) = rest.split_at(len);
+        let body = body.trim_start();
+        let body = Macro {
+            column: column(body),
+            body: body.trim_end(),
+            line,
+        };
+        if macros.insert(name, body).is_some() {
+            return Err(format!(
+                "Macro '{}' defined again at {}:{}",
+                name,
+                line,
+                column(rest)
+            ));
+        }
+        if text.ends_with('\n') {
+            code.push('\n');
+        }
+    }
+    Ok(code)
+}
+
+/// Writes `text`, the line `line` of the source, to `code` with the macros in it expanded, up to
+/// the `!` that starts the input if it is in it, and returns where that is. `depth` is how many
+/// loops the code so far is inside of
+fn expand_line(
+    code: &mut String,
+    text: &str,
+    line: usize,
+    macros: &BTreeMap<&str, Macro>,
+    depth: &mut usize,
+) -> Result<Option<usize>, String> {
+    let mut start = 0;
+    loop {
+        let end = text[start..]
+            .find('!')
+            .map_or(text.len(), |pos| start + pos);
+        let expanded = code.len();
+        let column = text[..start].chars().count() + 1;
+        expand_into(code, &text[start..end], line, column, macros, 0)?;
+        for ch in code[expanded..].chars() {
+            match ch {
+                '[' => *depth += 1,
+                ']' => *depth = depth.saturating_sub(1),
+                _ => (),
+            }
+        }
+        if end == text.len() {
+            return Ok(None);
+        }
+        // Inside a loop, the `!` is a comment like any other character
+        if *depth == 0 {
+            return Ok(Some(end));
+        }
+        code.push('!');
+        start = end + 1;
+    }
+}
+
+/// Whether `ch` can be in the name of a macro
+fn is_name(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_'
+}
+
+/// Writes `text`, which starts at `line`:`column` of the source, to `code` with the macros in it
+/// expanded. `depth` is how many macros `text` is inside of
+fn expand_into(
+    code: &mut String,
+    text: &str,
+    line: usize,
+    column: usize,
+    macros: &BTreeMap<&str, Macro>,
+    depth: usize,
+) -> Result<(), String> {
+    let mut rest = text;
+    while let Some(pos) = rest.find('@') {
+        code.push_str(&rest[..pos]);
+        let at = column + text[..text.len() - rest.len() + pos].chars().count();
+        rest = &rest[pos + 1..];
+        let len = rest.find(|ch| !is_name(ch)).unwrap_or(rest.len());
+        // An `@` without a name is a comment like any other character
+        if len == 0 {
+            code.push('@');
+            continue;
+        }
+        let name = &rest[..len];
+        rest = &rest[len..];
+        let Some(definition) = macros.get(name) else {
+            return Err(format!("Undefined macro '{}' at {}:{}", name, line, at));
+        };
+        if depth == MACRO_DEPTH {
+            return Err(format!(
+                "Macro '{}' at {}:{} is nested more than {} deep",
+                name, line, at, MACRO_DEPTH
+            ));
+        }
+        let body = definition.body;
+        expand_into(
+            code,
+            body,
+            definition.line,
+            definition.column,
+            macros,
+            depth + 1,
+        )?;
+        if code.len() > MACRO_SIZE {
+            return Err(format!(
+                "The macros expand to more than {} bytes, reached by '{}' at {}:{}",
+                MACRO_SIZE, name, line, at
+            ));
+        }
+    }
+    code.push_str(rest);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn macros_expand_on_their_line() {
+        let source = "!define zero [-]\n!define two ++\n!define set @zero@two\n@set.\n>@two.\n";
+        assert_eq!(expand(source, 0).unwrap(), "\n\n\n[-]++.\n>++.\n");
+        // An `@` without a name is a comment
+        assert_eq!(expand("@ +", 0).unwrap(), "@ +");
+    }
+
+    #[test]
+    fn the_input_is_left_as_it_is() {
+        let source = "!define out .\n,@out!@out @missing\n!define out +\n";
+        assert_eq!(
+            expand(source, 0).unwrap(),
+            "\n,.!@out @missing\n!define out +\n"
+        );
+        // Inside a loop, a `!` is a comment
+        let source = "!define out .\n[-!@out]!@out";
+        assert_eq!(expand(source, 0).unwrap(), "\n[-!.]!@out");
+    }
+
+    #[test]
+    fn errors_point_to_the_name() {
+        assert_eq!(
+            expand("+\n  @nope", 1),
+            Err("Undefined macro 'nope' at 3:3".to_owned())
+        );
+        assert_eq!(
+            expand("!define a @b\n@a", 0),
+            Err("Undefined macro 'b' at 1:11".to_owned())
+        );
+        assert_eq!(
+            expand("!define a +\n!define a -", 0),
+            Err("Macro 'a' defined again at 2:9".to_owned())
+        );
+        assert_eq!(
+            expand("!define \n", 0),
+            Err("Expected a macro name at 1:9".to_owned())
+        );
+        assert!(expand("!define a @a\n@a", 0)
+            .unwrap_err()
+            .contains("nested more than"));
+        let big = format!(
+            "!define a {}\n!define b {}\n!define c {}\n@c",
+            "+".repeat(1 << 12),
+            "@a".repeat(1 << 6),
+            "@b".repeat(1 << 7)
+        );
+        assert!(expand(&big, 0).unwrap_err().contains("bytes"));
+    }
+}
@@ -19,6 +19,55 @@ fn main() {
         }
     }
 
+    if args.get(1).map(String::as_str) == Some("disasm") {
+        if args.len() < 3 {
+            eprintln!("\x1b[91mError\x1b[0m: Usage: brainfuck disasm [options] file");
+            process::exit(1);
+        }
+        // The source is lowered with the options it is run with, and a `.bfc` file needs none
+        let options = [&args[..1], &args[2..]].concat();
+        let parsed = match &args[2..] {
+            [file] if file.ends_with(".bfc") => {
+                let mut args = Args::default();
+                args.file = file;
+                Ok(args)
+            }
+            _ => parse_args(&options),
+        };
+        match parsed.and_then(|args| disasm(&args)) {
+            Ok(_) => process::exit(0),
+            Err(e) => {
+                eprintln!("\x1b[91mError\x1b[0m: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.get(1).map(String::as_str) == Some("equiv") {
+        if args.len() < 4 {
+            eprintln!("\x1b[91mError\x1b[0m: Usage: brainfuck equiv [options] file file");
+            process::exit(1);
+        }
+        // The options come before the files, and both are read and run with them
+        let (options, files) = (&args[2..args.len() - 2], &args[args.len() - 2..]);
+        let a_args = [&args[..1], options, &files[..1]].concat();
+        let b_args = [&args[..1], options, &files[1..]].concat();
+        let (a, b) = match (parse_args(&a_args), parse_args(&b_args)) {
+            (Ok(a), Ok(b)) => (a, b),
+            (Err(e), _) | (_, Err(e)) => {
+                eprintln!("\x1b[91mError\x1b[0m: {}", e);
+                process::exit(1);
+            }
+        };
+        match equiv(&a, &b) {
+            Ok(same) => process::exit(!same as i32),
+            Err(e) => {
+                eprintln!("\x1b[91mError\x1b[0m: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     println!("\x1b[1mParsing arguments...\x1b[0m");
     let args = match parse_args(&args) {
         Ok(res) => {
@@ -46,7 +95,7 @@ fn main() {
 
     println!("\x1b[1mGetting file contents from {}...\x1b[0m", args.file);
 
-    let contents = match get_code(args.file) {
+    let contents = match get_code(&args) {
         Ok(contents) => contents,
         Err(e) => {
             eprintln!("\x1b[91mError\x1b[0m: {}", e);
@@ -54,10 +103,33 @@ fn main() {
         }
     };
 
+    if args.opt_report {
+        match opt_report(contents.clone(), &args) {
+            Ok(report) => print!("\x1b[1mOptimization report:\x1b[0m\n{}", report),
+            Err(e) => {
+                eprintln!("\x1b[91mError\x1b[0m: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
+    if args.emit.is_some_and(Emit::is_printed) {
+        match emit(contents, &args) {
+            Ok(text) => {
+                print!("{}", text);
+                process::exit(0)
+            }
+            Err(e) => {
+                eprintln!("\x1b[91mError\x1b[0m: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+
     if args.interpret {
         println!("\x1b[1mInterpreting the code...\x1b[0m");
         match interpret(contents, args) {
-            Ok(_) => (),
+            Ok(code) => process::exit(code),
             Err(e) => {
                 eprintln!("\x1b[91mError\x1b[0m: {}", e);
                 process::exit(1);
@@ -0,0 +1,96 @@
+//! The Ook! front-end, for `.ook` files.
+//!
+//! Ook! has the eight instructions as pairs of the tokens `Ook.`, `Ook?` and `Ook!`, so the
+//! source is split into tokens first and the pairs are turned into the Brainfuck instruction
+//! they stand for. Everything that is not a token is a comment, like in Brainfuck
+
+use alloc::{format, string::String, vec::Vec};
+
+/// A token, with the line and column it starts at
+struct Token {
+    mark: char,
+    line: usize,
+    column: usize,
+}
+
+/// The Brainfuck code of the Ook! code in `source`. The lines stay where they were, so the
+/// positions of the errors and of `--line-directives` are the ones of the source
+pub fn translate(source: &str) -> Result<String, String> {
+    let mut code = String::new();
+    let mut tokens = tokenize(source).into_iter();
+    let mut line = 1;
+    while let Some(first) = tokens.next() {
+        let Some(second) = tokens.next() else {
+            return Err(format!(
+                "Unpaired token 'Ook{}' at {}:{}",
+                first.mark, first.line, first.column
+            ));
+        };
+        let instr = match (first.mark, second.mark) {
+            ('.', '?') => '>',
+            ('?', '.') => '<',
+            ('.', '.') => '+',
+            ('!', '!') => '-',
+            ('!', '.') => '.',
+            ('.', '!') => ',',
+            ('!', '?') => '[',
+            ('?', '!') => ']',
+            (a, b) => {
+                return Err(format!(
+                    "Invalid pair 'Ook{} Ook{}' at {}:{}",
+                    a, b, first.line, first.column
+                ))
+            }
+        };
+        while line < first.line {
+            code.push('\n');
+            line += 1;
+        }
+        code.push(instr);
+    }
+    Ok(code)
+}
+
+/// The `Ook.`, `Ook?` and `Ook!` in `source`, in order
+fn tokenize(source: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    for (line, text) in source.lines().enumerate() {
+        let mut rest = text;
+        while let Some(pos) = rest.find("Ook") {
+            let after = &rest[pos + 3..];
+            match after.chars().next() {
+                Some(mark @ ('.' | '?' | '!')) => {
+                    let start = text.len() - rest.len() + pos;
+                    tokens.push(Token {
+                        mark,
+                        line: line + 1,
+                        column: text[..start].chars().count() + 1,
+                    });
+                    rest = &after[1..];
+                }
+                _ => rest = after,
+            }
+        }
+    }
+    tokens
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pairs_become_instructions() {
+        let source =
+            "Ook. Ook? Ook. Ook. Ook! Ook?\nOok! Ook! Ook? Ook!\n\nOok! Ook. a comment Ook. Ook!";
+        assert_eq!(translate(source).unwrap(), ">+[\n-]\n\n.,");
+        assert_eq!(
+            translate("Ook. Ook.\n  Ook? Ook?").unwrap_err(),
+            "Invalid pair 'Ook? Ook?' at 2:3"
+        );
+        assert_eq!(
+            translate("Ook. Ook. Ook!").unwrap_err(),
+            "Unpaired token 'Ook!' at 1:11"
+        );
+    }
+}
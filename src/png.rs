@@ -0,0 +1,422 @@
+//! A PNG decoder with only what Brainloller needs: the color of every pixel of an image that is
+//! not interlaced. The alpha channel is left out, and 16 bit samples keep their high byte.
+//!
+//! The pixels are compressed with zlib, so the inflate of RFC 1951 is here too. The checksums
+//! are not checked, a damaged image is a program that does something else
+
+use alloc::{format, string::String, vec, vec::Vec};
+
+/// The pixels of an image, row by row from the top left
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pixels: Vec<[u8; 3]>,
+}
+
+impl Image {
+    /// The red, green and blue of the pixel at `x`, `y`
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        self.pixels[y * self.width + x]
+    }
+}
+
+const SIGNATURE: &[u8; 8] = b"\x89PNG\r\n\x1a\n";
+
+/// What the `IHDR` chunk says about the image
+struct Header {
+    width: usize,
+    height: usize,
+    depth: u8,
+    color: u8,
+}
+
+impl Header {
+    fn parse(body: &[u8]) -> Result<Header, String> {
+        let [w0, w1, w2, w3, h0, h1, h2, h3, depth, color, compression, filter, interlace] = *body
+        else {
+            return Err("Invalid PNG header".into());
+        };
+        let header = Header {
+            width: u32::from_be_bytes([w0, w1, w2, w3]) as usize,
+            height: u32::from_be_bytes([h0, h1, h2, h3]) as usize,
+            depth,
+            color,
+        };
+        let depths: &[u8] = match color {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => return Err(format!("Invalid PNG color type {}", color)),
+        };
+        if !depths.contains(&depth) {
+            return Err(format!(
+                "Invalid PNG bit depth {} for the color type {}",
+                depth, color
+            ));
+        }
+        if compression != 0 || filter != 0 {
+            return Err("Unknown PNG compression or filter method".into());
+        }
+        if interlace != 0 {
+            return Err("Interlaced PNG images are not supported".into());
+        }
+        if header.width == 0 || header.height == 0 {
+            return Err("The PNG image has no pixels".into());
+        }
+        Ok(header)
+    }
+
+    /// How many samples every pixel has
+    fn channels(&self) -> usize {
+        match self.color {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+}
+
+/// Reads the pixels of the PNG image in `bytes`
+pub fn decode(bytes: &[u8]) -> Result<Image, String> {
+    let truncated = || String::from("Unexpected end of the PNG image");
+    let mut rest = bytes
+        .strip_prefix(SIGNATURE)
+        .ok_or_else(|| String::from("Not a PNG image"))?;
+    let mut header = None;
+    let mut palette = Vec::new();
+    let mut data = Vec::new();
+    loop {
+        let len = rest.get(..4).ok_or_else(truncated)?;
+        let len = u32::from_be_bytes([len[0], len[1], len[2], len[3]]) as usize;
+        let kind = rest.get(4..8).ok_or_else(truncated)?;
+        let body = rest.get(8..8 + len).ok_or_else(truncated)?;
+        // The CRC is after the body
+        rest = rest.get(12 + len..).ok_or_else(truncated)?;
+        match kind {
+            b"IHDR" => header = Some(Header::parse(body)?),
+            b"PLTE" => palette = body.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect(),
+            b"IDAT" => data.extend_from_slice(body),
+            b"IEND" => break,
+            // The ancillary chunks, like the gamma, don't change the colors of the pixels
+            _ => (),
+        }
+    }
+    let header = header.ok_or_else(|| String::from("The PNG image has no header"))?;
+    let data = inflate_zlib(&data)?;
+
+    let bits = header.channels() * header.depth as usize;
+    // The size comes from the file, which can say anything
+    let stride = header
+        .width
+        .checked_mul(bits)
+        .ok_or_else(too_large)?
+        .div_ceil(8);
+    let rows = unfilter(&data, stride, bits.div_ceil(8), header.height)?;
+    let len = header.width.checked_mul(header.height);
+    let mut pixels = Vec::with_capacity(len.ok_or_else(too_large)?);
+    for row in rows.chunks_exact(stride) {
+        for x in 0..header.width {
+            let sample = |channel: usize| sample(row, &header, x * header.channels() + channel);
+            pixels.push(match header.color {
+                3 => *palette
+                    .get(sample(0) as usize)
+                    .ok_or_else(|| format!("The color {} is not in the PNG palette", sample(0)))?,
+                2 | 6 => [sample(0), sample(1), sample(2)],
+                _ => [sample(0); 3],
+            });
+        }
+    }
+    Ok(Image {
+        width: header.width,
+        height: header.height,
+        pixels,
+    })
+}
+
+fn too_large() -> String {
+    "The PNG image is too large".into()
+}
+
+/// The sample at `index` of `row` as a byte. Gray samples of less than 8 bits are scaled up to
+/// it, and the index of a palette stays as it is
+fn sample(row: &[u8], header: &Header, index: usize) -> u8 {
+    match header.depth {
+        8 => row[index],
+        16 => row[index * 2],
+        depth => {
+            let bit = index * depth as usize;
+            let max = (1u16 << depth) - 1;
+            let value = (row[bit / 8] >> (8 - depth as usize - bit % 8)) as u16 & max;
+            match header.color {
+                3 => value as u8,
+                _ => (value * 255 / max) as u8,
+            }
+        }
+    }
+}
+
+/// The rows of `data` without the filter byte in front of every one, with the filters undone.
+/// `step` is how many bytes apart the samples a filter looks at are
+fn unfilter(data: &[u8], stride: usize, step: usize, height: usize) -> Result<Vec<u8>, String> {
+    let len = stride
+        .checked_add(1)
+        .and_then(|len| len.checked_mul(height));
+    if data.len() < len.ok_or_else(too_large)? {
+        return Err("The PNG image has fewer pixels than its size".into());
+    }
+    let mut rows = vec![0u8; stride * height];
+    for (y, line) in data.chunks_exact(stride + 1).take(height).enumerate() {
+        let (filter, line) = (line[0], &line[1..]);
+        let (above, row) = rows.split_at_mut(y * stride);
+        let above = above.get(above.len().wrapping_sub(stride)..);
+        let row = &mut row[..stride];
+        for i in 0..stride {
+            let a = if i >= step { row[i - step] } else { 0 };
+            let b = above.map_or(0, |above| above[i]);
+            let c = match above {
+                Some(above) if i >= step => above[i - step],
+                _ => 0,
+            };
+            let predicted = match filter {
+                0 => 0,
+                1 => a,
+                2 => b,
+                3 => ((a as u16 + b as u16) / 2) as u8,
+                4 => paeth(a, b, c),
+                _ => return Err(format!("Unknown PNG filter {} in row {}", filter, y)),
+            };
+            row[i] = line[i].wrapping_add(predicted);
+        }
+    }
+    Ok(rows)
+}
+
+/// Whichever of the left, above and upper left bytes is closest to `a + b - c`
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let (pa, pb, pc) = (
+        (p - a as i16).abs(),
+        (p - b as i16).abs(),
+        (p - c as i16).abs(),
+    );
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// The bytes that a zlib stream holds
+fn inflate_zlib(data: &[u8]) -> Result<Vec<u8>, String> {
+    let [cmf, flg, ..] = *data else {
+        return Err("The PNG image has no pixels".into());
+    };
+    if cmf & 0x0f != 8 || !(cmf as u16 * 256 + flg as u16).is_multiple_of(31) || flg & 0x20 != 0 {
+        return Err("Invalid zlib stream in the PNG image".into());
+    }
+    inflate(&data[2..])
+}
+
+/// The bits of a deflate stream, from the lowest bit of every byte
+struct Bits<'a> {
+    bytes: &'a [u8],
+    /// How many bits were read
+    pos: usize,
+}
+
+impl Bits<'_> {
+    /// The next `count` bits, the first one the lowest
+    fn read(&mut self, count: usize) -> Result<usize, String> {
+        let mut value = 0;
+        for i in 0..count {
+            let byte = self.bytes.get(self.pos / 8).ok_or_else(|| {
+                String::from("Unexpected end of the compressed pixels of the PNG image")
+            })?;
+            value |= ((byte >> (self.pos % 8)) as usize & 1) << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// A canonical Huffman code, by how many codes have every length and the symbols in the order
+/// of their codes
+struct Huffman {
+    counts: [usize; 16],
+    symbols: Vec<usize>,
+}
+
+impl Huffman {
+    /// The code where symbol `i` has a code `lengths[i]` bits long, or none if it is 0
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for &len in lengths {
+            counts[len as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut symbols = Vec::new();
+        for len in 1..16 {
+            symbols.extend((0..lengths.len()).filter(|&symbol| lengths[symbol] == len));
+        }
+        Huffman { counts, symbols }
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<usize, String> {
+        // The first code of the current length, and the index of its symbol
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for len in 1..16 {
+            code |= bits.read(1)?;
+            let count = self.counts[len];
+            if code < first + count {
+                return Ok(self.symbols[index + code - first]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code in the PNG image".into())
+    }
+}
+
+const LENGTH_BASE: [usize; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [usize; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+const DISTANCE_BASE: [usize; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [usize; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order the lengths of the code lengths come in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+/// The bytes that a deflate stream holds
+fn inflate(data: &[u8]) -> Result<Vec<u8>, String> {
+    let mut bits = Bits {
+        bytes: data,
+        pos: 0,
+    };
+    let mut out = Vec::new();
+    loop {
+        let last = bits.read(1)? == 1;
+        match bits.read(2)? {
+            0 => {
+                let start = bits.pos.div_ceil(8);
+                let header = data
+                    .get(start..start + 4)
+                    .ok_or_else(|| String::from("Unexpected end of the PNG image"))?;
+                let len = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if len != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err("Invalid stored block in the PNG image".into());
+                }
+                let stored = data
+                    .get(start + 4..start + 4 + len)
+                    .ok_or_else(|| String::from("Unexpected end of the PNG image"))?;
+                out.extend_from_slice(stored);
+                bits.pos = (start + 4 + len) * 8;
+            }
+            1 => {
+                let mut lengths = [0; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                block(&mut bits, &mut out, &literals, &distances)?;
+            }
+            _ => return Err("Invalid block type in the PNG image".into()),
+        }
+        if last {
+            return Ok(out);
+        }
+    }
+}
+
+/// Reads the codes at the start of a block with dynamic Huffman codes
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literals = bits.read(5)? + 257;
+    let distances = bits.read(5)? + 1;
+    let code_lengths = bits.read(4)? + 4;
+    let mut lengths = [0; 19];
+    for &symbol in &CODE_LENGTH_ORDER[..code_lengths] {
+        lengths[symbol] = bits.read(3)? as u8;
+    }
+    let code = Huffman::new(&lengths);
+
+    let mut lengths = Vec::with_capacity(literals + distances);
+    while lengths.len() < literals + distances {
+        let (len, times) = match code.decode(bits)? {
+            len @ 0..=15 => (len as u8, 1),
+            16 => {
+                let previous = *lengths
+                    .last()
+                    .ok_or_else(|| String::from("Invalid code lengths in the PNG image"))?;
+                (previous, 3 + bits.read(2)?)
+            }
+            17 => (0, 3 + bits.read(3)?),
+            _ => (0, 11 + bits.read(7)?),
+        };
+        lengths.extend(core::iter::repeat_n(len, times));
+    }
+    if lengths.len() > literals + distances {
+        return Err("Invalid code lengths in the PNG image".into());
+    }
+    let (literal_lengths, distance_lengths) = lengths.split_at(literals);
+    Ok((
+        Huffman::new(literal_lengths),
+        Huffman::new(distance_lengths),
+    ))
+}
+
+/// Reads the symbols of a compressed block until its end
+fn block(
+    bits: &mut Bits,
+    out: &mut Vec<u8>,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), String> {
+    loop {
+        match literals.decode(bits)? {
+            byte @ 0..=255 => out.push(byte as u8),
+            256 => return Ok(()),
+            symbol => {
+                let symbol = symbol - 257;
+                let (base, extra) = LENGTH_BASE
+                    .get(symbol)
+                    .zip(LENGTH_EXTRA.get(symbol))
+                    .ok_or_else(|| String::from("Invalid length in the PNG image"))?;
+                let len = base + bits.read(*extra)?;
+                let symbol = distances.decode(bits)?;
+                let (base, extra) = DISTANCE_BASE
+                    .get(symbol)
+                    .zip(DISTANCE_EXTRA.get(symbol))
+                    .ok_or_else(|| String::from("Invalid distance in the PNG image"))?;
+                let distance = base + bits.read(*extra)?;
+                if distance > out.len() {
+                    return Err("Invalid distance in the PNG image".into());
+                }
+                // The copy can overlap the bytes it makes, so it goes one byte at a time
+                let start = out.len() - distance;
+                for i in 0..len {
+                    out.push(out[start + i]);
+                }
+            }
+        }
+    }
+}
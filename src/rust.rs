@@ -0,0 +1,238 @@
+//! Writes the nodes as a Rust program, for `--emit=rust` and `--compiler=rustc`.
+//!
+//! The program only uses std. The memory is a `Vec` indexed by the pointer, so going out of it
+//! panics instead of reaching anything else, and the cells wrap like the ones of the interpreter
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+
+/// The Rust code of `code`. `run` takes the input and the output, and `main` gives it stdin and
+/// stdout, so the code can also be put in another program
+pub fn translate(code: &[Node], options: &CompileOptions) -> Result<String, String> {
+    brainfuck_only("rust", code)?;
+    let mut writer = Writer {
+        code: String::new(),
+        debug: options.debug,
+        debug_env: options.debug_env,
+        unbuffered: options.unbuffered,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+    };
+    if options.debug {
+        writer.line(1, "let mut debug_count = 0;");
+    }
+    if options.debug && options.debug_env {
+        writer.line(
+            1,
+            "let debug = std::env::var(\"BF_DEBUG\").is_ok_and(|var| !var.is_empty() && var != \"0\");",
+        );
+    }
+    writer.block(code, 1);
+    // The same status as the interpreter exits with
+    let exit_code = match options.exit_cell {
+        Some(ExitCell::Current) => "mem[ptr] as i32".to_owned(),
+        Some(ExitCell::Cell(cell)) => format!("mem[{}] as i32", cell),
+        None => "0".to_owned(),
+    };
+    Ok(format!(
+        "\
+use std::io::{{self, Read, Write}};
+
+type Cell = u{};
+
+/// What `,` reads before the input
+const INPUT: &[u8] = {};
+
+/// Reads a byte for `,`, where the end of the input reads as 0 like in the interpreter
+#[allow(dead_code)]
+fn getch(given: &mut std::slice::Iter<u8>, input: &mut impl Read, output: &mut impl Write) -> io::Result<Cell> {{
+    if let Some(&byte) = given.next() {{
+        return Ok(byte as Cell);
+    }}
+    output.flush()?;
+    let mut byte = [0];
+    match input.read_exact(&mut byte) {{
+        Ok(()) => Ok(byte[0] as Cell),
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => Ok(0),
+        Err(err) => Err(err),
+    }}
+}}
+
+/// Runs the program, and returns the exit code
+#[allow(unused_mut, unused_variables)]
+pub fn run(input: &mut impl Read, output: &mut impl Write) -> io::Result<i32> {{
+    let mut mem: Vec<Cell> = vec![0; {}];
+    let mut ptr: usize = {};
+    let mut given = INPUT.iter();
+{}    output.flush()?;
+    Ok({})
+}}
+
+fn main() {{
+    let mut output = io::BufWriter::with_capacity(1 << 16, io::stdout().lock());
+    match run(&mut io::stdin().lock(), &mut output) {{
+        Ok(code) => std::process::exit(code),
+        Err(err) => {{
+            eprintln!(\"{{}}\", err);
+            std::process::exit(1);
+        }}
+    }}
+}}
+",
+        options.cell_size,
+        byte_string(options.input),
+        options.mem_size,
+        options.offset,
+        writer.code,
+        exit_code
+    ))
+}
+
+/// Writes the Rust code for the nodes
+struct Writer {
+    code: String,
+    debug: bool,
+    /// Whether the debug symbols only print when `debug` is set
+    debug_env: bool,
+    unbuffered: bool,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+}
+
+impl Writer {
+    /// Writes a line indented by `depth` levels
+    fn line(&mut self, depth: usize, text: &str) {
+        self.code += &format!("{}{}\n", "    ".repeat(depth), text);
+    }
+
+    /// Writes `text`, which writes to the output, flushing it after with `--unbuffered`
+    fn output(&mut self, depth: usize, text: &str) {
+        match self.unbuffered {
+            true => self.line(depth, &format!("{} output.flush()?;", text)),
+            false => self.line(depth, text),
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node], depth: usize) {
+        let mut rest = nodes;
+        while let Some(node) = rest.first() {
+            // Outputs of known values that follow each other are written all at once
+            let bytes: Vec<u8> = rest
+                .iter()
+                .map_while(|node| match *node {
+                    Node::Print(value) => Some(value),
+                    _ => None,
+                })
+                .collect();
+            if bytes.len() > 1 {
+                self.output(
+                    depth,
+                    &format!("output.write_all({})?;", byte_string(&bytes)),
+                );
+                rest = &rest[bytes.len()..];
+            } else {
+                self.node(node, depth);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    fn node(&mut self, node: &Node, depth: usize) {
+        let line = match *node {
+            Node::Move(amount) if amount < 0 => format!("ptr -= {};", amount.unsigned_abs()),
+            Node::Move(amount) => format!("ptr += {};", amount),
+            Node::Add { offset, amount } if (amount as i8) < 0 => format!(
+                "{} = {}.wrapping_sub({});",
+                cell(offset),
+                cell(offset),
+                (amount as i8).unsigned_abs()
+            ),
+            Node::Add { offset, amount } => format!(
+                "{} = {}.wrapping_add({});",
+                cell(offset),
+                cell(offset),
+                amount
+            ),
+            Node::SetZero => "mem[ptr] = 0;".to_owned(),
+            // Past the end of the memory is out of it, like every other cell that is not in it
+            Node::Scan(1) => "ptr += mem[ptr..].iter().position(|&cell| cell == 0).unwrap_or(mem.len() - ptr);".to_owned(),
+            Node::Scan(-1) => "ptr = mem[..=ptr].iter().rposition(|&cell| cell == 0).expect(\"tape underflow\");".to_owned(),
+            Node::Scan(amount) if amount < 0 => {
+                format!("while mem[ptr] != 0 {{ ptr -= {}; }}", amount.unsigned_abs())
+            }
+            Node::Scan(amount) => format!("while mem[ptr] != 0 {{ ptr += {}; }}", amount),
+            Node::MulAdd { offset, factor: 1 } => {
+                format!("{} = {}.wrapping_add(mem[ptr]);", cell(offset), cell(offset))
+            }
+            Node::MulAdd { offset, factor: 255 } => {
+                format!("{} = {}.wrapping_sub(mem[ptr]);", cell(offset), cell(offset))
+            }
+            Node::MulAdd { offset, factor } if (factor as i8) < 0 => format!(
+                "{} = {}.wrapping_sub(mem[ptr].wrapping_mul({}));",
+                cell(offset),
+                cell(offset),
+                (factor as i8).unsigned_abs()
+            ),
+            Node::MulAdd { offset, factor } => format!(
+                "{} = {}.wrapping_add(mem[ptr].wrapping_mul({}));",
+                cell(offset),
+                cell(offset),
+                factor
+            ),
+            Node::Output { offset } => {
+                return self.output(depth, &format!("output.write_all(&[{} as u8])?;", cell(offset)))
+            }
+            Node::Print(value) => {
+                return self.output(depth, &format!("output.write_all(&[{}])?;", value))
+            }
+            Node::Input { offset } => format!(
+                "{} = getch(&mut given, input, output)?;",
+                cell(offset)
+            ),
+            Node::Loop { ref body, .. } => {
+                self.line(depth, "while mem[ptr] != 0 {");
+                self.block(body, depth + 1);
+                self.line(depth, "}");
+                return;
+            }
+            // The same as the C code prints
+            Node::DebugCell if self.debug => "debug_count += 1; write!(output, \"\\ndebug flag {} : \", debug_count)?; output.write_all(&[mem[ptr] as u8])?; writeln!(output, \", {}, {}\", mem[ptr], ptr)?;".to_owned(),
+            Node::DebugMem if self.debug => "writeln!(output)?; for i in ptr.saturating_sub(15)..(ptr + 15).min(mem.len()) { if i == ptr { write!(output, \"|{}| \", mem[i])?; } else { write!(output, \"{} \", mem[i])?; } } writeln!(output)?;".to_owned(),
+            Node::DebugCell | Node::DebugMem => return,
+            // Turned down by `translate`
+            _ => return,
+        };
+        let line = match *node {
+            Node::DebugCell | Node::DebugMem if self.debug_env => {
+                format!("if debug {{ {} }}", line)
+            }
+            Node::MulAdd { .. } if self.guard => format!("if mem[ptr] != 0 {{ {} }}", line),
+            _ => line,
+        };
+        self.line(depth, &line);
+    }
+}
+
+/// A Rust byte string literal holding `bytes`
+fn byte_string(bytes: &[u8]) -> String {
+    let mut string = "b\"".to_owned();
+    for &byte in bytes {
+        match byte {
+            b'"' | b'\\' => string += &format!("\\{}", byte as char),
+            b' '..=b'~' => string.push(byte as char),
+            _ => string += &format!("\\x{:02x}", byte),
+        }
+    }
+    string + "\""
+}
+
+/// The Rust place for the cell `offset` away from the pointer
+fn cell(offset: isize) -> String {
+    match offset {
+        0 => "mem[ptr]".to_owned(),
+        _ if offset < 0 => format!("mem[ptr - {}]", offset.unsigned_abs()),
+        _ => format!("mem[ptr + {}]", offset),
+    }
+}
@@ -0,0 +1,101 @@
+//! Builds the C code in this process with libtcc, for `--compiler=libtcc`, so that no C compiler
+//! has to be installed
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+
+/// The `TCCState` of libtcc, only ever used behind a pointer
+#[repr(C)]
+struct TccState {
+    _private: [u8; 0],
+}
+
+/// The output types of `tcc_set_output_type`
+const OUTPUT_EXE: c_int = 2;
+const OUTPUT_DLL: c_int = 3;
+const OUTPUT_OBJ: c_int = 4;
+
+/// What libtcc builds the code into
+pub enum Output {
+    Program,
+    Object,
+    SharedLibrary,
+}
+
+type ErrorFunc = extern "C" fn(*mut c_void, *const c_char);
+
+#[link(name = "tcc")]
+extern "C" {
+    fn tcc_new() -> *mut TccState;
+    fn tcc_delete(state: *mut TccState);
+    fn tcc_set_error_func(state: *mut TccState, opaque: *mut c_void, func: ErrorFunc);
+    fn tcc_set_options(state: *mut TccState, options: *const c_char);
+    fn tcc_set_output_type(state: *mut TccState, output_type: c_int) -> c_int;
+    fn tcc_compile_string(state: *mut TccState, code: *const c_char) -> c_int;
+    fn tcc_add_library(state: *mut TccState, name: *const c_char) -> c_int;
+    fn tcc_output_file(state: *mut TccState, path: *const c_char) -> c_int;
+}
+
+/// Adds the message of libtcc to the errors given to `tcc_set_error_func`
+extern "C" fn error(errors: *mut c_void, message: *const c_char) {
+    // SAFETY: `errors` is the `String` that `build` gave libtcc, and outlives the state
+    let errors = unsafe { &mut *(errors as *mut String) };
+    // SAFETY: libtcc passes a C string
+    let message = unsafe { CStr::from_ptr(message) };
+    errors.push_str(&message.to_string_lossy());
+    errors.push('\n');
+}
+
+/// Deletes the state when the build is done, however it ends
+struct State(*mut TccState);
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // SAFETY: the pointer came from `tcc_new` and is not used after this
+        unsafe { tcc_delete(self.0) }
+    }
+}
+
+fn c_string(text: &str) -> Result<CString, String> {
+    CString::new(text).map_err(|_| format!("{:?} has a 0 byte in it", text))
+}
+
+/// Builds `code` into the file `output` of the type `kind`, with the command line `options` of tcc
+/// and linked with `libs`
+pub fn build(
+    code: &str,
+    output: &str,
+    kind: Output,
+    options: &str,
+    libs: &[&str],
+) -> Result<(), String> {
+    let mut errors = String::new();
+    // SAFETY: every call gets the state from `tcc_new` and C strings that outlive it, and the
+    // error function gets `errors`, which lives until the state is deleted at the end
+    unsafe {
+        let state = State(tcc_new());
+        if state.0.is_null() {
+            return Err("libtcc could not start".to_owned());
+        }
+        tcc_set_error_func(state.0, &mut errors as *mut String as *mut c_void, error);
+        tcc_set_options(state.0, c_string(options)?.as_ptr());
+        let output_type = match kind {
+            Output::Program => OUTPUT_EXE,
+            Output::Object => OUTPUT_OBJ,
+            Output::SharedLibrary => OUTPUT_DLL,
+        };
+        let mut failed = tcc_set_output_type(state.0, output_type) != 0
+            || tcc_compile_string(state.0, c_string(code)?.as_ptr()) != 0;
+        for lib in libs {
+            failed = failed || tcc_add_library(state.0, c_string(lib)?.as_ptr()) != 0;
+        }
+        failed = failed || tcc_output_file(state.0, c_string(output)?.as_ptr()) != 0;
+        drop(state);
+        match failed {
+            true => Err(format!(
+                "libtcc failed to build the C code\n{}",
+                errors.trim_end()
+            )),
+            false => Ok(()),
+        }
+    }
+}
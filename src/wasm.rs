@@ -0,0 +1,524 @@
+//! Writes the nodes as a WebAssembly module for WASI, for `--emit=wasm`.
+//!
+//! The module is written directly, with no compiler to make it. It exports `_start` and its
+//! memory like any WASI program, and `.` and `,` go through `fd_write` and `fd_read`, so it runs
+//! under wasmtime or wasmer with its input and output piped like any other program. The pointer
+//! is a local of `_start` holding the address of the current cell
+
+use crate::{
+    backend::{brainfuck_only, CompileOptions},
+    interpreter::ExitCell,
+    ir::{self, Node},
+};
+
+/// Where things are in the memory: the buffer and the length `fd_write` and `fd_read` take,
+/// the bytes they wrote or read, the length of the output, how much of the given input was read,
+/// the byte read from stdin, the output and then the given input, before the cells
+const IOVEC: u32 = 0;
+const DONE: u32 = 8;
+const BUFFER_LEN: u32 = 12;
+const INPUT_POS: u32 = 16;
+const BYTE: u32 = 20;
+const BUFFER: u32 = 32;
+const BUFFER_SIZE: u32 = 4096;
+const INPUT: u32 = BUFFER + BUFFER_SIZE;
+const PAGE: u64 = 65536;
+
+/// The functions, the imports of WASI first
+const FD_WRITE: u32 = 0;
+const FD_READ: u32 = 1;
+const PROC_EXIT: u32 = 2;
+const START: u32 = 3;
+const PUTCHAR: u32 = 4;
+const FLUSH: u32 = 5;
+const GETCH: u32 = 6;
+
+/// The types of the functions: the ones of `fd_write` and `fd_read`, of `proc_exit` and
+/// `putchar`, of `_start` and `flush`, and of `getch`
+const TYPES: [(&[u8], &[u8]); 4] = [
+    (&[I32, I32, I32, I32], &[I32]),
+    (&[I32], &[]),
+    (&[], &[]),
+    (&[], &[I32]),
+];
+const I32: u8 = 0x7f;
+const VOID: u8 = 0x40;
+
+/// The module of `code`
+pub fn program(code: &[Node], options: &CompileOptions) -> Result<Vec<u8>, String> {
+    brainfuck_only("wasm", code)?;
+    let too_big = || "The program is too big for --emit=wasm".into();
+    let cell_bytes = options.cell_size as u32 / 8;
+    let input_len = u32::try_from(options.input.len()).map_err(|_| too_big())?;
+    // The cells start aligned to their size
+    let mem = (INPUT + input_len).next_multiple_of(4);
+    let start = u32::try_from(options.offset)
+        .ok()
+        .and_then(|offset| offset.checked_mul(cell_bytes)?.checked_add(mem))
+        .ok_or_else(too_big)?;
+    let end = mem as u64 + options.mem_size as u64 * cell_bytes as u64;
+    let pages = end.div_ceil(PAGE).max(1);
+    if pages > 65536 {
+        return Err(too_big());
+    }
+
+    let mut writer = Writer {
+        code: Vec::new(),
+        cell_bytes,
+        unbuffered: options.unbuffered,
+        guard: ir::needs_guard(code, options.offset, options.mem_size),
+        too_big: false,
+    };
+    writer.constant(start as i32);
+    writer.op(0x21); // local.set ptr
+    writer.unsigned(0);
+    writer.block(code);
+    writer.call(FLUSH);
+    // The same status as the interpreter exits with
+    match options.exit_cell {
+        Some(ExitCell::Current) => writer.load(0),
+        Some(ExitCell::Cell(cell)) => {
+            let at = u32::try_from(cell)
+                .ok()
+                .and_then(|cell| cell.checked_mul(cell_bytes)?.checked_add(mem))
+                .ok_or_else(too_big)?;
+            writer.constant(0);
+            writer.memory(writer.load_op(), at);
+        }
+        None => {
+            writer.constant(0);
+        }
+    }
+    writer.call(PROC_EXIT);
+    if writer.too_big {
+        return Err(too_big());
+    }
+    let main = writer.code;
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    let mut types = Vec::new();
+    leb(&mut types, TYPES.len() as u64);
+    for (params, results) in TYPES {
+        types.push(0x60);
+        leb(&mut types, params.len() as u64);
+        types.extend_from_slice(params);
+        leb(&mut types, results.len() as u64);
+        types.extend_from_slice(results);
+    }
+    section(&mut module, 1, &types);
+
+    let mut imports = Vec::new();
+    leb(&mut imports, 3);
+    for (name, kind) in [("fd_write", 0), ("fd_read", 0), ("proc_exit", 1)] {
+        name_of(&mut imports, "wasi_snapshot_preview1");
+        name_of(&mut imports, name);
+        imports.push(0); // a function
+        leb(&mut imports, kind);
+    }
+    section(&mut module, 2, &imports);
+
+    // `_start`, `putchar`, `flush` and `getch`
+    section(&mut module, 3, &[4, 2, 1, 2, 3]);
+    let mut memory = vec![1, 0];
+    leb(&mut memory, pages);
+    section(&mut module, 5, &memory);
+
+    let mut exports = Vec::new();
+    leb(&mut exports, 2);
+    name_of(&mut exports, "_start");
+    exports.push(0); // a function
+    leb(&mut exports, START as u64);
+    name_of(&mut exports, "memory");
+    exports.push(2); // a memory
+    leb(&mut exports, 0);
+    section(&mut module, 7, &exports);
+
+    let mut functions = Vec::new();
+    leb(&mut functions, 4);
+    for body in [main, putchar(), flush(), getch(input_len)] {
+        // Every function has one local of its own, the pointer in `_start`
+        let mut function = vec![1, 1, I32];
+        function.extend_from_slice(&body);
+        function.push(0x0b); // end
+        leb(&mut functions, function.len() as u64);
+        functions.extend_from_slice(&function);
+    }
+    section(&mut module, 10, &functions);
+
+    // What `,` reads before stdin
+    let mut data = Vec::new();
+    leb(&mut data, 1);
+    data.push(0); // put in the memory when the module starts
+    data.push(0x41); // i32.const INPUT
+    sleb(&mut data, INPUT as i64);
+    data.push(0x0b); // end
+    leb(&mut data, input_len as u64);
+    data.extend_from_slice(options.input);
+    section(&mut module, 11, &data);
+    Ok(module)
+}
+
+/// Adds the byte in the parameter to the output, and writes it when the buffer is full
+fn putchar() -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.constant(0);
+    writer.memory(0x28, BUFFER_LEN); // i32.load
+    writer.local(0x22, 1); // local.tee len
+    writer.local(0x20, 0); // local.get byte
+    writer.memory(0x3a, BUFFER); // i32.store8
+    writer.constant(0);
+    writer.local(0x20, 1); // local.get len
+    writer.constant(1);
+    writer.op(0x6a); // i32.add
+    writer.local(0x22, 1); // local.tee len
+    writer.memory(0x36, BUFFER_LEN); // i32.store
+    writer.local(0x20, 1); // local.get len
+    writer.constant(BUFFER_SIZE as i32);
+    writer.op(0x46); // i32.eq
+    writer.op(0x04); // if
+    writer.op(VOID);
+    writer.call(FLUSH);
+    writer.op(0x0b); // end
+    writer.code
+}
+
+/// Writes the output to stdout. When it cannot be written, it is dropped
+fn flush() -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.constant(0);
+    writer.constant(BUFFER as i32);
+    writer.memory(0x36, IOVEC); // i32.store
+    writer.constant(0);
+    writer.constant(0);
+    writer.memory(0x28, BUFFER_LEN); // i32.load
+    writer.memory(0x36, IOVEC + 4); // i32.store
+    writer.op(0x02); // block
+    writer.op(VOID);
+    writer.op(0x03); // loop
+    writer.op(VOID);
+    writer.constant(0);
+    writer.memory(0x28, IOVEC + 4); // i32.load
+    writer.op(0x45); // i32.eqz
+    writer.branch(0x0d, 1); // br_if out of the block
+    for value in [1, IOVEC, 1, DONE] {
+        writer.constant(value as i32);
+    }
+    writer.call(FD_WRITE);
+    writer.branch(0x0d, 1); // br_if out of the block on an error
+    writer.constant(0);
+    writer.memory(0x28, DONE); // i32.load
+    writer.local(0x22, 0); // local.tee written
+    writer.op(0x45); // i32.eqz
+    writer.branch(0x0d, 1); // br_if out of the block
+                            // The rest of the output is what is still to write
+    for (field, op) in [(IOVEC, 0x6a), (IOVEC + 4, 0x6b)] {
+        writer.constant(0);
+        writer.constant(0);
+        writer.memory(0x28, field); // i32.load
+        writer.local(0x20, 0); // local.get written
+        writer.op(op); // i32.add or i32.sub
+        writer.memory(0x36, field); // i32.store
+    }
+    writer.branch(0x0c, 0); // br to the loop
+    writer.op(0x0b); // end
+    writer.op(0x0b); // end
+    writer.constant(0);
+    writer.constant(0);
+    writer.memory(0x36, BUFFER_LEN); // i32.store
+    writer.code
+}
+
+/// Reads a byte for `,`, from the given input and then stdin, where the end of the input reads
+/// as 0 like in the interpreter
+fn getch(input_len: u32) -> Vec<u8> {
+    let mut writer = Writer::new();
+    writer.constant(0);
+    writer.memory(0x28, INPUT_POS); // i32.load
+    writer.local(0x22, 0); // local.tee pos
+    writer.constant(input_len as i32);
+    writer.op(0x49); // i32.lt_u
+    writer.op(0x04); // if
+    writer.op(VOID);
+    writer.constant(0);
+    writer.local(0x20, 0); // local.get pos
+    writer.constant(1);
+    writer.op(0x6a); // i32.add
+    writer.memory(0x36, INPUT_POS); // i32.store
+    writer.local(0x20, 0); // local.get pos
+    writer.memory(0x2d, INPUT); // i32.load8_u
+    writer.op(0x0f); // return
+    writer.op(0x0b); // end
+    writer.call(FLUSH);
+    writer.constant(0);
+    writer.constant(BYTE as i32);
+    writer.memory(0x36, IOVEC); // i32.store
+    writer.constant(0);
+    writer.constant(1);
+    writer.memory(0x36, IOVEC + 4); // i32.store
+    for value in [0, IOVEC, 1, DONE] {
+        writer.constant(value as i32);
+    }
+    writer.call(FD_READ);
+    writer.op(0x04); // if there was an error
+    writer.op(VOID);
+    writer.constant(0);
+    writer.op(0x0f); // return
+    writer.op(0x0b); // end
+    writer.constant(0);
+    writer.memory(0x28, DONE); // i32.load
+    writer.op(0x04); // if a byte was read
+    writer.op(I32);
+    writer.constant(0);
+    writer.memory(0x2d, BYTE); // i32.load8_u
+    writer.op(0x05); // else
+    writer.constant(0);
+    writer.op(0x0b); // end
+    writer.code
+}
+
+/// Writes a section with its id and size
+fn section(module: &mut Vec<u8>, id: u8, contents: &[u8]) {
+    module.push(id);
+    leb(module, contents.len() as u64);
+    module.extend_from_slice(contents);
+}
+
+/// Writes a name with its length
+fn name_of(bytes: &mut Vec<u8>, name: &str) {
+    leb(bytes, name.len() as u64);
+    bytes.extend_from_slice(name.as_bytes());
+}
+
+/// Writes `value` in unsigned LEB128
+fn leb(bytes: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = value as u8 & 0x7f;
+        value >>= 7;
+        match value {
+            0 => return bytes.push(byte),
+            _ => bytes.push(byte | 0x80),
+        }
+    }
+}
+
+/// Writes `value` in signed LEB128
+fn sleb(bytes: &mut Vec<u8>, mut value: i64) {
+    loop {
+        let byte = value as u8 & 0x7f;
+        value >>= 7;
+        if (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0) {
+            return bytes.push(byte);
+        }
+        bytes.push(byte | 0x80);
+    }
+}
+
+/// Writes the code of a function
+struct Writer {
+    code: Vec<u8>,
+    /// The bytes of a cell, 1, 2 or 4
+    cell_bytes: u32,
+    /// Whether the output is written after every `.`
+    unbuffered: bool,
+    /// See [`ir::needs_guard`]
+    guard: bool,
+    /// Whether a move or an offset did not fit in 32 bits
+    too_big: bool,
+}
+
+impl Writer {
+    /// A writer for the runtime, which has no cells
+    fn new() -> Writer {
+        Writer {
+            code: Vec::new(),
+            cell_bytes: 1,
+            unbuffered: false,
+            guard: false,
+            too_big: false,
+        }
+    }
+
+    fn op(&mut self, op: u8) {
+        self.code.push(op);
+    }
+
+    fn unsigned(&mut self, value: u64) {
+        leb(&mut self.code, value);
+    }
+
+    fn signed(&mut self, value: i64) {
+        sleb(&mut self.code, value);
+    }
+
+    fn constant(&mut self, value: i32) {
+        self.op(0x41); // i32.const
+        self.signed(value as i64);
+    }
+
+    fn local(&mut self, op: u8, index: u32) {
+        self.op(op);
+        self.unsigned(index as u64);
+    }
+
+    fn call(&mut self, function: u32) {
+        self.op(0x10);
+        self.unsigned(function as u64);
+    }
+
+    /// `br` or `br_if` out of `depth` blocks
+    fn branch(&mut self, op: u8, depth: u32) {
+        self.op(op);
+        self.unsigned(depth as u64);
+    }
+
+    /// A load or store `offset` bytes from the address on the stack, which is not aligned
+    fn memory(&mut self, op: u8, offset: u32) {
+        self.op(op);
+        self.unsigned(0);
+        self.unsigned(offset as u64);
+    }
+
+    /// The load of a cell, which is unsigned so that cells are never negative
+    fn load_op(&self) -> u8 {
+        match self.cell_bytes {
+            1 => 0x2d, // i32.load8_u
+            2 => 0x2f, // i32.load16_u
+            _ => 0x28, // i32.load
+        }
+    }
+
+    fn store_op(&self) -> u8 {
+        match self.cell_bytes {
+            1 => 0x3a, // i32.store8
+            2 => 0x3b, // i32.store16
+            _ => 0x36, // i32.store
+        }
+    }
+
+    /// Pushes the address of the cell `offset` away from the pointer, and gives the offset the
+    /// load or store of it takes, which cannot be negative
+    fn address(&mut self, offset: isize) -> u32 {
+        let bytes = i32::try_from(offset)
+            .ok()
+            .and_then(|offset| offset.checked_mul(self.cell_bytes as i32));
+        self.local(0x20, 0); // local.get ptr
+        match bytes {
+            Some(bytes) if bytes >= 0 => bytes as u32,
+            Some(bytes) => {
+                self.constant(bytes);
+                self.op(0x6a); // i32.add
+                0
+            }
+            None => {
+                self.too_big = true;
+                0
+            }
+        }
+    }
+
+    /// Pushes the cell `offset` away from the pointer
+    fn load(&mut self, offset: isize) {
+        let offset = self.address(offset);
+        self.memory(self.load_op(), offset);
+    }
+
+    /// Stores the value `value` pushes to the cell `offset` away from the pointer
+    fn store(&mut self, offset: isize, value: impl FnOnce(&mut Writer)) {
+        let at = self.address(offset);
+        value(self);
+        self.memory(self.store_op(), at);
+    }
+
+    fn putchar(&mut self) {
+        self.call(PUTCHAR);
+        if self.unbuffered {
+            self.call(FLUSH);
+        }
+    }
+
+    fn block(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.node(node);
+        }
+    }
+
+    fn node(&mut self, node: &Node) {
+        match *node {
+            Node::Move(amount) => {
+                let bytes = match i32::try_from(amount) {
+                    Ok(amount) => amount.wrapping_mul(self.cell_bytes as i32),
+                    Err(_) => {
+                        self.too_big = true;
+                        0
+                    }
+                };
+                self.local(0x20, 0); // local.get ptr
+                self.constant(bytes);
+                self.op(0x6a); // i32.add
+                self.local(0x21, 0); // local.set ptr
+            }
+            // The amounts and factors are bytes, which are negative the same way for every size
+            Node::Add { offset, amount } => self.store(offset, |writer| {
+                writer.load(offset);
+                writer.constant(amount as i8 as i32);
+                writer.op(0x6a); // i32.add
+            }),
+            Node::SetZero => self.store(0, |writer| writer.constant(0)),
+            Node::Scan(amount) => {
+                self.op(0x02); // block
+                self.op(VOID);
+                self.op(0x03); // loop
+                self.op(VOID);
+                self.load(0);
+                self.op(0x45); // i32.eqz
+                self.branch(0x0d, 1); // br_if out of the block
+                self.node(&Node::Move(amount));
+                self.branch(0x0c, 0); // br to the loop
+                self.op(0x0b); // end
+                self.op(0x0b); // end
+            }
+            Node::MulAdd { offset, factor } => {
+                if self.guard {
+                    self.load(0);
+                    self.op(0x04); // if
+                    self.op(VOID);
+                }
+                self.store(offset, |writer| {
+                    writer.load(offset);
+                    writer.load(0);
+                    writer.constant(factor as i8 as i32);
+                    writer.op(0x6c); // i32.mul
+                    writer.op(0x6a); // i32.add
+                });
+                if self.guard {
+                    self.op(0x0b); // end
+                }
+            }
+            Node::Output { offset } => {
+                self.load(offset);
+                self.putchar();
+            }
+            Node::Print(value) => {
+                self.constant(value as i32);
+                self.putchar();
+            }
+            Node::Input { offset } => self.store(offset, |writer| writer.call(GETCH)),
+            Node::Loop { ref body, .. } => {
+                self.load(0);
+                self.op(0x04); // if
+                self.op(VOID);
+                self.op(0x03); // loop
+                self.op(VOID);
+                self.block(body);
+                self.load(0);
+                self.branch(0x0d, 0); // br_if to the loop
+                self.op(0x0b); // end
+                self.op(0x0b); // end
+            }
+            // There is no debug mode in the program
+            Node::DebugCell | Node::DebugMem => (),
+            // Turned down by `program`
+            _ => (),
+        }
+    }
+}